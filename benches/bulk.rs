@@ -0,0 +1,64 @@
+//! Compare [`Octree::from_voxels`] against inserting each voxel through [`Octree::set_leaf`].
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use eightfold::{Octant, Octree, VoxelPoint};
+
+/// Height of the voxel grid.
+const HEIGHT: u32 = 8;
+
+/// Pseudo-random voxels within the grid, from a fixed seed.
+fn voxels(count: usize) -> Vec<(VoxelPoint<u32>, u32)> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 40) as u32 % (1 << HEIGHT)
+    };
+    (0..count)
+        .map(|i| (VoxelPoint::new(next(), next(), next()), i as u32))
+        .collect()
+}
+
+/// Insert each voxel in turn, splitting from the root down.
+fn incremental(voxels: Vec<(VoxelPoint<u32>, u32)>) -> Octree<u32, u32> {
+    let mut res = Octree::new();
+    for (p, data) in voxels {
+        let mut idx = res.root_idx();
+        for depth in (0..HEIGHT).rev() {
+            let oct = Octant::new(
+                (p.x >> depth) & 1 == 1,
+                (p.y >> depth) & 1 == 1,
+                (p.z >> depth) & 1 == 1,
+            );
+            idx = res.split(idx).unwrap().0[usize::from(oct)];
+        }
+        res.set_leaf(idx, data);
+    }
+    res
+}
+
+fn bulk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    for count in [1_000, 10_000] {
+        let input = voxels(count);
+        group.bench_with_input(BenchmarkId::new("set_leaf", count), &input, |b, input| {
+            b.iter_batched(|| input.clone(), incremental, BatchSize::LargeInput);
+        });
+        group.bench_with_input(
+            BenchmarkId::new("from_voxels", count),
+            &input,
+            |b, input| {
+                b.iter_batched(
+                    || input.clone(),
+                    |input| Octree::<u32, u32>::from_voxels(input, |_, b| b).unwrap(),
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bulk);
+criterion_main!(benches);
@@ -5,6 +5,7 @@ use std::ops::{AddAssign, Shl, Shr, SubAssign};
 use num_traits::{AsPrimitive, PrimInt};
 
 pub mod macros;
+pub mod morton;
 
 /// Trait for types which can act as indices within an array (or an array-like structure).
 ///
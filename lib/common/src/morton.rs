@@ -0,0 +1,75 @@
+//! Morton (Z-order) codes for points within a cubical voxel grid.
+//!
+//! A code interleaves the bits of a point's coordinates as `…XYZXYZ`, so that each group of three
+//! bits, read from most to least significant, is the octant containing the point at the next
+//! level of subdivision (with `X` as the most significant bit of each group).
+
+use crate::ArrayIndex;
+
+/// The maximum number of bits per axis which fit in a [u128] Morton code.
+pub const MAX_BITS: u32 = u128::BITS / 3;
+
+/// The number of bits required to store `v`.
+#[inline]
+fn significant_bits<Idx: ArrayIndex>(v: Idx) -> u32 {
+    Idx::ZERO.count_zeros() - v.leading_zeros()
+}
+
+/// Interleave the bits of a point's `[X, Y, Z]` coordinates into a Morton code.
+///
+/// # Panics
+///
+/// * (debug only) any coordinate ≥ `2^MAX_BITS`
+pub fn encode<Idx: ArrayIndex>([x, y, z]: [Idx; 3]) -> u128 {
+    let bits = significant_bits(x | y | z);
+    debug_assert!(
+        bits <= MAX_BITS,
+        "coordinates too large for a u128 Morton code"
+    );
+    let bit = |v: Idx, b: u32| u128::from((v >> b as usize) & Idx::ONE == Idx::ONE);
+    (0..bits).fold(0u128, |code, b| {
+        code | (bit(x, b) << (3 * b + 2)) | (bit(y, b) << (3 * b + 1)) | (bit(z, b) << (3 * b))
+    })
+}
+
+/// Extract a point's `[X, Y, Z]` coordinates from a Morton code.
+///
+/// Bits which don't fit within `Idx` are discarded.
+pub fn decode<Idx: ArrayIndex>(code: u128) -> [Idx; 3] {
+    let bits = MAX_BITS.min(Idx::ZERO.count_zeros());
+    let bit = |b: u32| {
+        if (code >> b) & 1 == 1 {
+            Idx::ONE
+        } else {
+            Idx::ZERO
+        }
+    };
+    (0..bits).fold([Idx::ZERO; 3], |[x, y, z], b| {
+        [
+            x | (bit(3 * b + 2) << b as usize),
+            y | (bit(3 * b + 1) << b as usize),
+            z | (bit(3 * b) << b as usize),
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn encode() {
+        assert_eq!(super::encode([0u8, 0, 0]), 0);
+        assert_eq!(super::encode([1u8, 0, 0]), 0b100);
+        assert_eq!(super::encode([0u8, 1, 1]), 0b011);
+        assert_eq!(super::encode([2u16, 3, 1]), 0b110_011);
+        assert_eq!(super::encode([u8::MAX; 3]), (1 << 24) - 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        for p in [[0u32, 0, 0], [5, 9, 200], [u32::MAX >> 1, 17, 1 << 30]] {
+            assert_eq!(super::decode::<u32>(super::encode(p)), p);
+        }
+        let p = [(1u64 << 41) + 3, 7, 1 << 40];
+        assert_eq!(super::decode::<u64>(super::encode(p)), p);
+    }
+}
@@ -147,6 +147,7 @@ impl<T: Clone> Clone for StableVec<T> {
         for i in self.flags.iter_ones() {
             unsafe { res.data[i].write(self.data[i].assume_init_ref().clone()) };
         }
+        res.flags.clone_from(&self.flags);
         res.count = self.count;
         res
     }
@@ -328,6 +329,7 @@ impl<T> StableVec<T> {
                 new_cap,
             ))
         };
+        self.cap = new_cap;
         self.flags.truncate(new_cap);
         self.flags.shrink_to_fit();
     }
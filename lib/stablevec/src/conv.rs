@@ -60,7 +60,7 @@ impl<T> From<Vec<T>> for StableVec<T> {
                 res
             },
             count: len,
-            cap: len,
+            cap,
         }
     }
 }
@@ -18,6 +18,8 @@ mod alloc;
 mod conv;
 mod debug;
 mod macros;
+#[cfg(feature = "serde")]
+pub mod ser;
 
 // reexport so the macro works outside of this package
 pub use bitvec;
@@ -176,12 +178,36 @@ impl<T> StableVec<T> {
         }
     }
 
+    /// Get the value at a specific index, if initialized, without borrowing any other value; so,
+    /// unlike through [`Self::get_mut`], references to distinct values can be held at once.
+    ///
+    /// # Safety
+    ///
+    /// * `this` must be valid for `'a`, during which the vector must only be accessed through this
+    ///   function
+    /// * no `index` may be given more than once during `'a`
+    #[inline]
+    pub unsafe fn get_disjoint_mut<'a>(this: *mut Self, index: usize) -> Option<&'a mut T> {
+        unsafe {
+            match (*this).flags.get(index).as_deref() {
+                None | Some(false) => None,
+                // projecting through the raw pointer never borrows the rest of `data`
+                Some(true) => {
+                    let data = ptr::addr_of_mut!(*(*this).data).cast::<MaybeUninit<T>>();
+                    Some((*data.add(index)).assume_init_mut())
+                }
+            }
+        }
+    }
+
     pub fn push(&mut self, data: T) -> usize {
         let index = match self.flags.first_zero() {
             Some(i) => i,
             None => {
+                // the first new slot
+                let index = self.capacity();
                 self.grow_amortized(1);
-                self.capacity() - 1
+                index
             }
         };
         unsafe { self.set_unchecked(index, data) };
@@ -215,7 +241,7 @@ impl<T> StableVec<T> {
     }
 
     pub fn extend_from_other(&mut self, mut other: Self) -> HashMap<usize, usize> {
-        let last_init = match self.flags.last_one() {
+        let last_init = match other.flags.last_one() {
             Some(l) => l,
             None => return HashMap::with_capacity(0),
         };
@@ -243,14 +269,27 @@ impl<T> StableVec<T> {
         self.flags.iter_ones().map(|i| &self[i])
     }
 
-    // pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
-    //     self.flags.iter_ones().map(|i| todo!())
-    // }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.data
+            .iter_mut()
+            .zip(self.flags.iter().by_vals())
+            // SAFETY: only slots flagged as initialized are yielded
+            .filter_map(|(d, init)| init.then(|| unsafe { d.assume_init_mut() }))
+    }
 
     pub fn enumerate(&self) -> impl Iterator<Item = (usize, &T)> {
         self.flags.iter_ones().map(|i| (i, &self[i]))
     }
 
+    pub fn enumerate_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        self.data
+            .iter_mut()
+            .zip(self.flags.iter().by_vals())
+            .enumerate()
+            // SAFETY: only slots flagged as initialized are yielded
+            .filter_map(|(i, (d, init))| init.then(|| (i, unsafe { d.assume_init_mut() })))
+    }
+
     pub fn is_init(&self, index: usize) -> bool {
         self.flags.get(index).map_or(false, |b| *b)
     }
@@ -258,7 +297,7 @@ impl<T> StableVec<T> {
     pub fn is_fragmented(&self) -> bool {
         match self.flags.first_zero() {
             None => false,
-            Some(i) => i <= self.count,
+            Some(i) => i < self.count,
         }
     }
 
@@ -311,7 +350,7 @@ impl<T> StableVec<T> {
                 }
                 res.insert(i, first_uninit);
                 first_uninit = match self.flags[(first_uninit + 1)..i].first_zero() {
-                    Some(z) => z,
+                    Some(z) => first_uninit + 1 + z,
                     None => break, // fully partitioned
                 };
             }
@@ -331,7 +370,7 @@ impl<T> StableVec<T> {
         if self.is_fragmented() {
             None
         } else {
-            Some(unsafe { mem::transmute::<&[MaybeUninit<T>], &[T]>(&*self.data) })
+            Some(unsafe { mem::transmute::<&[MaybeUninit<T>], &[T]>(&self.data[..self.count]) })
         }
     }
 
@@ -339,7 +378,9 @@ impl<T> StableVec<T> {
         if self.is_fragmented() {
             None
         } else {
-            Some(unsafe { mem::transmute::<&mut [MaybeUninit<T>], &mut [T]>(&mut *self.data) })
+            Some(unsafe {
+                mem::transmute::<&mut [MaybeUninit<T>], &mut [T]>(&mut self.data[..self.count])
+            })
         }
     }
 }
@@ -0,0 +1,52 @@
+//! [Serde](https://serde.rs) support for [`StableVec`].
+//!
+//! By default, a [`StableVec`] is encoded as a sequence of `Option<T>`, so that indices are
+//! preserved across a round trip. Use [`compact`] with `#[serde(with = "...")]` to encode only the
+//! initialized elements instead.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::StableVec;
+
+impl<T: Serialize> Serialize for StableVec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // trailing vacant slots carry no information
+        let len = self.flags.last_one().map_or(0, |i| i + 1);
+        serializer.collect_seq((0..len).map(|i| self.get(i)))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for StableVec<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let slots = Vec::<Option<T>>::deserialize(deserializer)?;
+        let mut res = Self::with_capacity(slots.len());
+        for (i, slot) in slots.into_iter().enumerate() {
+            if let Some(data) = slot {
+                res.set(i, data);
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Encode a [`StableVec`] as a sequence of only its initialized elements.
+///
+/// Indices are *not* preserved unless the [`StableVec`] is unfragmented.
+pub mod compact {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::StableVec;
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        vec: &StableVec<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(vec.iter())
+    }
+
+    pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<StableVec<T>, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(StableVec::from)
+    }
+}
@@ -13,3 +13,89 @@ fn zst() {
     zst.reserve_exact(0);
     zst.reserve_exact(1);
 }
+
+/// Test that defragmentation packs every entry into a prefix
+#[test]
+fn defragment() {
+    let mut vec = StableVec::<u32>::new();
+    let indices = (0..8).map(|i| vec.push(i)).collect::<Vec<_>>();
+    for i in [1, 2, 5] {
+        vec.remove(indices[i]);
+    }
+    vec.defragment();
+    assert!(!vec.is_fragmented());
+    let mut res = vec.as_slice().unwrap().to_vec();
+    res.sort_unstable();
+    assert_eq!(res, [0, 3, 4, 6, 7]);
+}
+
+/// Test that extending from another `StableVec` moves every entry of the other
+#[test]
+fn extend_from_other() {
+    let mut vec = StableVec::<u32>::new();
+    let mut other = StableVec::<u32>::new();
+    let indices = (0..6).map(|i| other.push(i)).collect::<Vec<_>>();
+    other.remove(indices[2]);
+    let swaps = vec.extend_from_other(other);
+    assert_eq!(swaps.len(), 5);
+    assert_eq!(vec.len_init(), 5);
+    vec.push(10);
+    let swaps = vec.extend_from_other(StableVec::from(vec![20, 21]));
+    assert_eq!(swaps.len(), 2);
+    assert_eq!(vec.len_init(), 8);
+}
+
+/// Test that a `StableVec` converted from a `Vec` with spare capacity can grow
+#[test]
+fn from_vec() {
+    let mut v = Vec::with_capacity(8);
+    v.extend([0u32, 1, 2]);
+    let mut vec = StableVec::from(v);
+    assert_eq!(vec.capacity(), 8);
+    assert_eq!(vec.spare_capacity(), 5);
+    vec.reserve(16);
+    assert_eq!(vec.init_flags().len(), vec.capacity());
+    for i in 3..24 {
+        vec.push(i);
+    }
+    let mut res = vec.iter().copied().collect::<Vec<_>>();
+    res.sort_unstable();
+    assert!(res.into_iter().eq(0..24));
+}
+
+/// Test that compression shrinks the capacity along with the allocation
+#[test]
+fn compress() {
+    let mut vec = StableVec::<u32>::new();
+    let indices = (0..12).map(|i| vec.push(i)).collect::<Vec<_>>();
+    vec.remove(indices[4]);
+    vec.compress();
+    assert_eq!(vec.capacity(), 11);
+    assert_eq!(vec.init_flags().len(), vec.capacity());
+    vec.reserve(1);
+    let i = vec.push(12);
+    assert!(vec.is_init(i));
+    assert_eq!(vec.len_init(), 12);
+}
+
+/// Test that pushing into a full `StableVec` uses the first new slot, in order
+#[test]
+fn push_order() {
+    let mut vec = StableVec::<u32>::new();
+    for i in 0..20 {
+        assert_eq!(vec.push(i), i as usize);
+    }
+    assert!(vec.iter().copied().eq(0..20));
+}
+
+/// Test that a clone holds the same entries at the same indices
+#[test]
+fn clone() {
+    let mut vec = StableVec::<String>::new();
+    let indices = (0..6).map(|i| vec.push(i.to_string())).collect::<Vec<_>>();
+    vec.remove(indices[1]);
+    let res = vec.clone();
+    assert_eq!(res.len_init(), 5);
+    assert_eq!(res.init_flags(), vec.init_flags());
+    assert!(res.enumerate().eq(vec.enumerate()));
+}
@@ -246,6 +246,16 @@ pub fn main() {
     #[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
     trace_memory_stats(Some(memstats));
 
+    // jemalloc only sees the process as a whole, so also break down the tree's own storage
+    let tree_stats = tree.stats();
+    tracing::info!(
+        bytes_used = tree_stats.tree.bytes_used(),
+        bytes_reserved = tree_stats.tree.bytes_reserved(),
+        occupancy = tree_stats.occupancy(),
+        ?tree_stats,
+        "tree stats"
+    );
+
     tracing::debug!(%tree, "done");
 
     tracing::info!(?stats, %duration, "done");
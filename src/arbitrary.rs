@@ -0,0 +1,169 @@
+//! [Quickcheck](https://docs.rs/quickcheck) support, so that downstream crates can property-test
+//! their own code against arbitrary trees.
+
+use std::ops::Range;
+
+use eightfold_common::ArrayIndex;
+use num_traits::{AsPrimitive, NumCast};
+use quickcheck::{Arbitrary, Gen};
+
+use crate::{Arena, NodePoint, Octant, Octree, OctreeStorage};
+
+impl Arbitrary for Octant {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self(u8::arbitrary(g) % 8)
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new((0..self.0).map(Self))
+    }
+}
+
+impl<Idx: ArrayIndex> Arbitrary for NodePoint<Idx> {
+    /// Generates a point within the `2ᴰ` voxel grid, at a depth shallow enough for every node
+    /// along the way to be addressable by `Idx`.
+    fn arbitrary(g: &mut Gen) -> Self {
+        let bits = std::mem::size_of::<Idx>() * 8;
+        let depth = usize::arbitrary(g) % bits.min(64);
+        let mask = (1u64 << depth) - 1;
+        let mut coord = || <Idx as NumCast>::from(u64::arbitrary(g) & mask).unwrap();
+        Self::new(
+            coord(),
+            coord(),
+            coord(),
+            <Idx as NumCast>::from(depth).unwrap(),
+        )
+    }
+
+    /// Shrinks towards the root, through each ancestor of `self`.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let p = self.0;
+        let depth = AsPrimitive::<usize>::as_(p.w);
+        Box::new((0..depth).rev().map(move |d| {
+            let shift = <Idx as NumCast>::from(depth - d).unwrap();
+            Self::new(
+                p.x >> shift,
+                p.y >> shift,
+                p.z >> shift,
+                NumCast::from(d).unwrap(),
+            )
+        }))
+    }
+}
+
+impl<T: Arbitrary, Idx: ArrayIndex, S: OctreeStorage + 'static> Arbitrary for Octree<T, Idx, S>
+where
+    Self: Clone,
+    usize: AsPrimitive<Idx>,
+    Range<Idx>: Iterator,
+{
+    /// Generates a tree by applying a random sequence of splits, insertions, removals & growths,
+    /// numbering at most [`Gen::size`], through the public API.
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut res = Self::new();
+        for _ in 0..usize::arbitrary(g) % g.size().max(1) {
+            let nodes = res
+                .proxies()
+                .enumerate()
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            let target = g.choose(&nodes).unwrap().as_();
+            // operations which would exhaust `Idx`, or which don't apply to the target, are
+            // skipped
+            let _ = match u8::arbitrary(g) % 8 {
+                0..=2 => res.split(target).map(|_| ()),
+                3..=5 => res.try_set_leaf(target, T::arbitrary(g)).map(|_| ()),
+                6 => {
+                    res.remove(target);
+                    Ok(())
+                }
+                _ => res.try_grow(Octant::arbitrary(g)).map(|_| ()),
+            };
+        }
+        res
+    }
+
+    /// Shrinks by removing one leaf at a time, pruning any branches left void.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let tree = self.clone();
+        let leafs = self
+            .proxies()
+            .enumerate()
+            .filter(|(_, p)| p.is_leaf())
+            .map(|(i, _)| i.as_())
+            .collect::<Vec<Idx>>();
+        Box::new(leafs.into_iter().map(move |leaf| {
+            let mut res = tree.clone();
+            res.remove(leaf);
+            res.prune();
+            res
+        }))
+    }
+}
+
+#[cfg(feature = "spatial")]
+mod spatial {
+    use nalgebra::Point3;
+    use num_traits::NumCast;
+    use quickcheck::{Arbitrary, Gen};
+
+    use crate::spatial::{Aabb, Float};
+
+    /// Generate a finite value within `-bound..bound`.
+    fn finite<Real: Float>(g: &mut Gen, bound: f64) -> Real {
+        let v = f64::arbitrary(g);
+        let v = if v.is_finite() { v % bound } else { 0.0 };
+        <Real as NumCast>::from(v).unwrap()
+    }
+
+    impl<Real: Float> Arbitrary for Aabb<Real> {
+        /// Generates a box with finite bounds & positive extents.
+        fn arbitrary(g: &mut Gen) -> Self {
+            let mins = Point3::new(finite(g, 1e6), finite(g, 1e6), finite(g, 1e6));
+            let mut extent = || {
+                let e = num_traits::Float::abs(finite::<Real>(g, 1e6));
+                if e > Real::ZERO {
+                    e
+                } else {
+                    Real::ONE
+                }
+            };
+            let maxs = Point3::new(mins.x + extent(), mins.y + extent(), mins.z + extent());
+            Self::new(mins, maxs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickcheck::Arbitrary;
+    use quickcheck_macros::quickcheck;
+
+    use crate::{NodePoint, Octree, OctreeSlice, VecStorage};
+
+    #[quickcheck]
+    #[allow(clippy::needless_pass_by_value)]
+    fn arbitrary_trees_are_valid(tree: Octree<u8, u8>) -> bool {
+        tree.validate().is_ok() && tree.shrink().all(|t| t.validate().is_ok())
+    }
+
+    #[quickcheck]
+    #[allow(clippy::needless_pass_by_value)]
+    fn arbitrary_vec_storage_trees_are_valid(tree: Octree<u8, u8, VecStorage>) -> bool {
+        tree.validate().is_ok() && tree.shrink().all(|t| t.validate().is_ok())
+    }
+
+    #[quickcheck]
+    fn arbitrary_points_fit_their_grid(p: NodePoint<u16>) -> bool {
+        let size = 1u32 << p.0.w;
+        [p.0.x, p.0.y, p.0.z].iter().all(|&c| u32::from(c) < size)
+            && p.shrink().all(|s| s.0.w < p.0.w)
+    }
+
+    #[quickcheck]
+    #[allow(clippy::needless_pass_by_value)]
+    fn clones_are_equal(tree: Octree<u8, u16>) -> bool {
+        let clone = tree.clone();
+        clone.validate().is_ok() && clone.leaf_dfi().eq(tree.leaf_dfi())
+    }
+}
@@ -1,2 +1,4 @@
+mod direction;
 mod octant;
+pub use direction::*;
 pub use octant::*;
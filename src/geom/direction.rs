@@ -0,0 +1,218 @@
+use std::{fmt::Display, ops::Neg};
+
+use nalgebra::Vector3;
+
+use crate::Octant;
+
+/// The ways in which two neighboring nodes can touch.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Adjacency {
+    /// The nodes share a face; one axis differs.
+    Face,
+    /// The nodes share an edge; two axes differ.
+    Edge,
+    /// The nodes share a corner; all three axes differ.
+    Corner,
+}
+
+/// A direction from a node towards one of its 26 neighbors at the same depth: across one of its
+/// 6 faces, 12 edges, or 8 corners.
+///
+/// Each component is one of `-1`, `0`, or `1`, using the same `IJK` axes as [Octant]; at least one
+/// component is non-zero.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Direction {
+    i: i8,
+    j: i8,
+    k: i8,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Direction({}, {}, {})", self.i, self.j, self.k)
+    }
+}
+
+impl Neg for Direction {
+    type Output = Self;
+    /// Get the [Direction] pointing the opposite way.
+    #[inline]
+    fn neg(self) -> Self::Output {
+        Self {
+            i: -self.i,
+            j: -self.j,
+            k: -self.k,
+        }
+    }
+}
+
+impl From<Octant> for Direction {
+    /// Get the [Direction] from the center of a volume towards the corner contained by `oct`.
+    #[inline]
+    fn from(oct: Octant) -> Self {
+        Self::corner(oct)
+    }
+}
+
+impl Direction {
+    /// The 6 face directions, in `-I, +I, -J, +J, -K, +K` order.
+    #[rustfmt::skip]
+    pub const FACES: [Self; 6] = [
+        Self::raw(-1,  0,  0), Self::raw( 1,  0,  0),
+        Self::raw( 0, -1,  0), Self::raw( 0,  1,  0),
+        Self::raw( 0,  0, -1), Self::raw( 0,  0,  1),
+    ];
+
+    /// The 12 edge directions.
+    #[rustfmt::skip]
+    pub const EDGES: [Self; 12] = [
+        Self::raw(-1, -1,  0), Self::raw(-1,  1,  0), Self::raw( 1, -1,  0), Self::raw( 1,  1,  0),
+        Self::raw(-1,  0, -1), Self::raw(-1,  0,  1), Self::raw( 1,  0, -1), Self::raw( 1,  0,  1),
+        Self::raw( 0, -1, -1), Self::raw( 0, -1,  1), Self::raw( 0,  1, -1), Self::raw( 0,  1,  1),
+    ];
+
+    /// The 8 corner directions, in [Octant] order.
+    #[rustfmt::skip]
+    pub const CORNERS: [Self; 8] = [
+        Self::corner(Octant(0)), Self::corner(Octant(1)),
+        Self::corner(Octant(2)), Self::corner(Octant(3)),
+        Self::corner(Octant(4)), Self::corner(Octant(5)),
+        Self::corner(Octant(6)), Self::corner(Octant(7)),
+    ];
+
+    #[inline]
+    const fn raw(i: i8, j: i8, k: i8) -> Self {
+        Self { i, j, k }
+    }
+
+    /// Construct a [Direction] from its components.
+    ///
+    /// Returns `None` if any component ∉ `-1..=1`, or if all components are `0`.
+    #[inline]
+    pub const fn new(i: i8, j: i8, k: i8) -> Option<Self> {
+        if i < -1 || i > 1 || j < -1 || j > 1 || k < -1 || k > 1 || (i == 0 && j == 0 && k == 0) {
+            return None;
+        }
+        Some(Self { i, j, k })
+    }
+
+    /// The [Direction] from the center of a volume towards the corner contained by `oct`.
+    #[inline]
+    #[rustfmt::skip]
+    pub const fn corner(oct: Octant) -> Self {
+        const fn sign(b: u8) -> i8 {
+            if b == 0 { -1 } else { 1 }
+        }
+        Self {
+            i: sign(oct.i()),
+            j: sign(oct.j()),
+            k: sign(oct.k()),
+        }
+    }
+
+    /// Iterate through all 26 directions: faces, then edges, then corners.
+    pub fn all() -> impl Iterator<Item = Self> {
+        Self::FACES
+            .into_iter()
+            .chain(Self::EDGES)
+            .chain(Self::CORNERS)
+    }
+
+    /// The `i` component of `self`.
+    #[inline]
+    pub const fn i(self) -> i8 {
+        self.i
+    }
+    /// The `j` component of `self`.
+    #[inline]
+    pub const fn j(self) -> i8 {
+        self.j
+    }
+    /// The `k` component of `self`.
+    #[inline]
+    pub const fn k(self) -> i8 {
+        self.k
+    }
+
+    /// Get `self` as a [Vector3\<i8\>](Vector3).
+    #[inline]
+    pub const fn vector(self) -> Vector3<i8> {
+        nalgebra::vector![self.i, self.j, self.k]
+    }
+
+    /// Whether `self` crosses a face, an edge, or a corner.
+    #[inline]
+    pub const fn adjacency(self) -> Adjacency {
+        match (self.i != 0) as u8 + (self.j != 0) as u8 + (self.k != 0) as u8 {
+            1 => Adjacency::Face,
+            2 => Adjacency::Edge,
+            _ => Adjacency::Corner,
+        }
+    }
+
+    /// Step an [Octant] one unit in this direction within its parent volume.
+    ///
+    /// Returns the resulting octant, along with the remainder of `self` that crossed out of the
+    /// parent volume, if any.
+    #[inline]
+    pub(crate) const fn step(self, oct: Octant) -> (Octant, Option<Self>) {
+        /// Step one axis; returns (new bit, carried component).
+        #[rustfmt::skip]
+        const fn axis(bit: bool, d: i8) -> (bool, i8) {
+            match (bit, d) {
+                (false,  1) => (true,  0),
+                (true,   1) => (false, 1),
+                (true,  -1) => (false, 0),
+                (false, -1) => (true, -1),
+                (b, _) => (b, 0),
+            }
+        }
+        let (i, ci) = axis(oct.i() != 0, self.i);
+        let (j, cj) = axis(oct.j() != 0, self.j);
+        let (k, ck) = axis(oct.k() != 0, self.k);
+        (Octant::new(i, j, k), Self::new(ci, cj, ck))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Adjacency, Direction, Octant};
+
+    #[test]
+    fn counts() {
+        let all = Direction::all().collect::<Vec<_>>();
+        assert_eq!(all.len(), 26);
+        for (a, d) in all.iter().enumerate() {
+            assert!(!all[(a + 1)..].contains(d));
+        }
+        assert!(Direction::FACES
+            .iter()
+            .all(|d| d.adjacency() == Adjacency::Face));
+        assert!(Direction::EDGES
+            .iter()
+            .all(|d| d.adjacency() == Adjacency::Edge));
+        assert!(Direction::CORNERS
+            .iter()
+            .all(|d| d.adjacency() == Adjacency::Corner));
+    }
+
+    #[test]
+    fn new() {
+        assert_eq!(Direction::new(0, 0, 0), None);
+        assert_eq!(Direction::new(2, 0, 0), None);
+        assert_eq!(Direction::new(-1, 1, 0), Some(Direction::EDGES[1]));
+    }
+
+    #[test]
+    fn step() {
+        let pos_i = Direction::FACES[1];
+        assert_eq!(pos_i.step(Octant(0)), (Octant(4), None));
+        assert_eq!(pos_i.step(Octant(4)), (Octant(0), Some(pos_i)));
+        let neg_ijk = Direction::corner(Octant(0));
+        assert_eq!(neg_ijk.step(Octant(7)), (Octant(0), None));
+        assert_eq!(
+            neg_ijk.step(Octant(3)),
+            (Octant(4), Direction::new(-1, 0, 0))
+        );
+    }
+}
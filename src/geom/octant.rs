@@ -23,8 +23,18 @@ use crate::NodePoint;
 /// So, you can think of it as being a right-handed coordinate system.
 #[repr(transparent)]
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
 pub struct Octant(pub u8);
 
+/// Error returned when converting an out-of-range integer to an [Octant].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Octant out of range: 0..8 ∌ {0}")]
+pub struct InvalidOctant(pub u8);
+
 impl Display for Octant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("Octant").field(&self.0).finish()
@@ -117,12 +127,25 @@ impl From<Octant> for u8 {
     }
 }
 
+impl TryFrom<u8> for Octant {
+    type Error = InvalidOctant;
+    #[inline]
+    fn try_from(o: u8) -> Result<Self, Self::Error> {
+        if o < 8 {
+            Ok(Self(o))
+        } else {
+            Err(InvalidOctant(o))
+        }
+    }
+}
+
+// the child's coordinates within the grid one level deeper are `2 * parent + octant bit`
 macro_rules! np_add_impl {
     ($np:ident, $o:ident) => {
         $crate::nodepoint![
-            $np.0.x + $o.i().as_(),
-            $np.0.y + $o.j().as_(),
-            $np.0.z + $o.k().as_(),
+            ($np.0.x << Idx::ONE) + ($o.i() >> 2).as_(),
+            ($np.0.y << Idx::ONE) + ($o.j() >> 1).as_(),
+            ($np.0.z << Idx::ONE) + $o.k().as_(),
             $np.0.w + Idx::ONE
         ]
     };
@@ -134,7 +157,7 @@ where
 {
     type Output = NodePoint<Idx>;
 
-    /// Get the [`NodePoint`] of an [Octant] of self
+    /// Get the [`NodePoint`] of an [Octant] of self, one level deeper than `self`.
     #[inline]
     fn add(self, o: Octant) -> Self::Output {
         np_add_impl!(self, o)
@@ -147,7 +170,7 @@ where
 {
     type Output = NodePoint<Idx>;
 
-    /// Get the [`NodePoint`] of an [Octant] of self
+    /// Get the [`NodePoint`] of an [Octant] of self, one level deeper than `self`.
     #[inline]
     fn add(self, o: Octant) -> Self::Output {
         np_add_impl!(self, o)
@@ -156,7 +179,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::Octant;
+    use crate::{NodePoint, Octant};
 
     #[test]
     #[rustfmt::skip]
@@ -171,6 +194,18 @@ mod tests {
         assert_eq!(Octant::new( true,  true,  true), Octant(7));
     }
 
+    #[test]
+    fn add_node_point() {
+        let parent = NodePoint::<u32>::new(1, 1, 0, 1);
+        assert_eq!(parent + Octant(0), NodePoint::new(2, 2, 0, 2));
+        assert_eq!(parent + Octant(3), NodePoint::new(2, 3, 1, 2));
+        assert_eq!(parent + Octant(7), NodePoint::new(3, 3, 1, 2));
+        assert_eq!(
+            NodePoint::<u8>::new(0, 0, 0, 0) + Octant(4),
+            NodePoint::new(1, 0, 0, 1)
+        );
+    }
+
     #[test]
     fn not() {
         assert_eq!(!Octant(7), Octant(0));
@@ -7,6 +7,8 @@
 // release build lints
 #![cfg_attr(not(debug_assertions), deny(unreachable_pub), warn(missing_docs))]
 
+#[cfg(feature = "quickcheck")]
+mod arbitrary;
 mod geom;
 #[cfg(feature = "mesh")]
 pub mod mesh;
@@ -34,6 +36,7 @@ pub type VoxelPoint<Idx> = Point3<Idx>;
 ///
 /// In voxel terms, a `NodePoint` is a point `XYZ` within a voxel grid of size `2ᴰ`.
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NodePoint<Idx: ArrayIndex>(pub Point4<Idx>);
 
 impl<Idx: ArrayIndex> NodePoint<Idx> {
@@ -41,6 +44,20 @@ impl<Idx: ArrayIndex> NodePoint<Idx> {
     pub const fn new(x: Idx, y: Idx, z: Idx, d: Idx) -> Self {
         Self(nalgebra::point![x, y, z, d])
     }
+
+    /// The [Morton code](eightfold_common::morton) of `XYZ` within the `2ᴰ` voxel grid.
+    #[inline]
+    pub fn morton(&self) -> u128 {
+        eightfold_common::morton::encode([self.0.x, self.0.y, self.0.z])
+    }
+
+    /// Construct a [`NodePoint`] at depth `d` from the [Morton code](eightfold_common::morton) of
+    /// its `XYZ` coordinates.
+    #[inline]
+    pub fn from_morton(code: u128, d: Idx) -> Self {
+        let [x, y, z] = eightfold_common::morton::decode(code);
+        Self::new(x, y, z, d)
+    }
 }
 
 /// Quickly construct a [`NodePoint`]
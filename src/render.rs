@@ -1 +1,4 @@
 //! Utilities for rendering an [Octree](crate::Octree).
+
+mod esvo;
+pub use esvo::*;
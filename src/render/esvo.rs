@@ -0,0 +1,415 @@
+use std::ops::Range;
+
+use eightfold_common::ArrayIndex;
+use nalgebra::Point3;
+
+use crate::{
+    spatial::{Aabb, Float, VoxelOctree},
+    Arena, FormatError, MappedLeaf, Octree, OctreeSlice, OctreeStorage, ProxyData,
+};
+
+/// The width of each node word in an [`EsvoExport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeWidth {
+    /// 32-bit child descriptors, `[child pointer: 15 | far: 1 | valid mask: 8 | leaf mask: 8]`,
+    /// from most to least significant bit.
+    ///
+    /// The child pointer is relative to the descriptor. If the far bit is set, it instead points to
+    /// a far word, which holds the full 32-bit relative pointer; far words are stored directly
+    /// after the block containing the descriptor.
+    Bits32,
+    /// 64-bit child descriptors, `[reserved: 16 | valid mask: 8 | leaf mask: 8 | child pointer: 32]`,
+    /// from most to least significant bit.
+    ///
+    /// The child pointer is the absolute index of the first child.
+    Bits64,
+}
+
+impl NodeWidth {
+    /// The number of bytes in each node word.
+    #[inline]
+    pub const fn bytes(self) -> usize {
+        match self {
+            Self::Bits32 => 4,
+            Self::Bits64 => 8,
+        }
+    }
+}
+
+/// A description of the buffers within an [`EsvoExport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EsvoLayout {
+    /// The width of each node word.
+    pub node_width: NodeWidth,
+    /// The number of node words, including far words.
+    pub node_count: usize,
+    /// The number of bytes in each attribute.
+    pub attribute_size: usize,
+    /// The number of attributes.
+    pub attribute_count: usize,
+    /// The height of the exported tree.
+    pub height: u32,
+    /// The kind of the root node; the descriptor of a branch root is the first node word, and
+    /// the attribute of a leaf root is the first attribute.
+    pub root: ProxyData<u32>,
+}
+
+/// An [Octree] flattened into a contiguous array of child descriptors, in the style of
+/// [Efficient Sparse Voxel Octrees](https://research.nvidia.com/publication/2010-02_efficient-sparse-voxel-octrees)
+/// (Laine & Karras, 2010), with leaf data in a separate attribute array.
+///
+/// Each branch is represented by a child descriptor, with a valid mask giving which of its
+/// [Octants](crate::Octant) are non-void, and a leaf mask giving which of those are leafs. Its
+/// non-void children are stored as a contiguous block of node words, in [Octant](crate::Octant)
+/// order, starting at its child pointer; branch children are child descriptors, and leaf children
+/// are indices into the attribute array. Blocks are stored breadth-first.
+///
+/// All words & attributes are little-endian.
+#[derive(Debug, Clone)]
+pub struct EsvoExport {
+    /// The layout of [`Self::nodes`] & [`Self::attributes`].
+    pub layout: EsvoLayout,
+    /// Node words.
+    pub nodes: Vec<u8>,
+    /// Leaf data, as written by [`MappedLeaf::to_bytes`].
+    pub attributes: Vec<u8>,
+}
+
+/// The largest relative child pointer which fits within a 32-bit child descriptor.
+const NEAR_LIMIT: usize = 1 << 15;
+
+/// A non-void node, as an entry in a block of node words.
+struct Entry<Idx> {
+    node: Idx,
+    /// For branches: the block containing its children, and its valid & leaf masks.
+    branch: Option<(usize, u8, u8)>,
+}
+
+impl EsvoExport {
+    /// Flatten a tree, using far pointers in 32-bit descriptors when a relative pointer is
+    /// `>= far_limit`.
+    fn build<T: MappedLeaf, Idx: ArrayIndex, S: OctreeStorage>(
+        tree: &Octree<T, Idx, S>,
+        width: NodeWidth,
+        far_limit: usize,
+    ) -> Result<Self, FormatError> {
+        let proxies = tree.proxies();
+        let branch_data = tree.branch_data();
+        let leaf_data = tree.leaf_data();
+        let height = tree.height().to_u32().ok_or(FormatError::IndexOverflow)?;
+
+        // collect blocks of non-void entries, breadth-first
+        let mut entries: Vec<Entry<Idx>> = Vec::new();
+        let mut blocks: Vec<Range<usize>> = Vec::new();
+        let root = match proxies[tree.root_idx().as_()].data {
+            ProxyData::Void => ProxyData::Void,
+            ProxyData::Leaf(_) => ProxyData::Leaf(0),
+            ProxyData::Branch(_) => ProxyData::Branch(0),
+        };
+        if !matches!(root, ProxyData::Void) {
+            entries.push(Entry {
+                node: tree.root_idx(),
+                branch: None,
+            });
+            blocks.push(0..1);
+        }
+        let mut e = 0;
+        while e < entries.len() {
+            if let ProxyData::Branch(b_idx) = proxies[entries[e].node.as_()].data {
+                let start = entries.len();
+                let (mut valid, mut leaf) = (0u8, 0u8);
+                for (o, &child) in branch_data[b_idx.as_()].iter().enumerate() {
+                    match proxies[child.as_()].data {
+                        ProxyData::Void => continue,
+                        ProxyData::Leaf(_) => leaf |= 1 << o,
+                        ProxyData::Branch(_) => {}
+                    }
+                    valid |= 1 << o;
+                    entries.push(Entry {
+                        node: child,
+                        branch: None,
+                    });
+                }
+                entries[e].branch = Some((blocks.len(), valid, leaf));
+                blocks.push(start..entries.len());
+            }
+            e += 1;
+        }
+
+        // assign word positions, adding far words until every relative pointer fits
+        let mut far = vec![false; entries.len()];
+        let (pos, far_pos, block_pos, node_count) = loop {
+            let mut pos = vec![0; entries.len()];
+            let mut far_pos = vec![0; entries.len()];
+            let mut block_pos = vec![0; blocks.len()];
+            let mut cursor = 0;
+            for (b, block) in blocks.iter().enumerate() {
+                block_pos[b] = cursor;
+                for e in block.clone() {
+                    pos[e] = cursor;
+                    cursor += 1;
+                }
+                for e in block.clone().filter(|e| far[*e]) {
+                    far_pos[e] = cursor;
+                    cursor += 1;
+                }
+            }
+            let mut changed = false;
+            if width == NodeWidth::Bits32 {
+                for (e, entry) in entries.iter().enumerate() {
+                    if let Some((b, _, _)) = entry.branch {
+                        if !far[e] && block_pos[b] - pos[e] >= far_limit {
+                            far[e] = true;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if !changed {
+                break (pos, far_pos, block_pos, cursor);
+            }
+        };
+        if node_count > u32::MAX as usize {
+            return Err(FormatError::IndexOverflow);
+        }
+
+        let mut words = vec![0u64; node_count];
+        let mut attributes = Vec::with_capacity(leaf_data.len_init() * T::SIZE);
+        let mut attribute_count = 0u64;
+        for (e, entry) in entries.iter().enumerate() {
+            words[pos[e]] = match (entry.branch, proxies[entry.node.as_()].data) {
+                (Some((b, valid, leaf)), _) => {
+                    let masks = (u64::from(valid) << 8) | u64::from(leaf);
+                    match width {
+                        NodeWidth::Bits64 => (masks << 32) | block_pos[b] as u64,
+                        NodeWidth::Bits32 if far[e] => {
+                            words[far_pos[e]] = (block_pos[b] - pos[e]) as u64;
+                            (((far_pos[e] - pos[e]) as u64) << 17) | (1 << 16) | masks
+                        }
+                        NodeWidth::Bits32 => (((block_pos[b] - pos[e]) as u64) << 17) | masks,
+                    }
+                }
+                (None, ProxyData::Leaf(l_idx)) => {
+                    let start = attributes.len();
+                    attributes.resize(start + T::SIZE, 0);
+                    leaf_data[l_idx.as_()].to_bytes(&mut attributes[start..]);
+                    attribute_count += 1;
+                    attribute_count - 1
+                }
+                // only branches & leafs are entries
+                (None, _) => unreachable!(),
+            };
+        }
+
+        let nodes = match width {
+            NodeWidth::Bits32 => words
+                .into_iter()
+                .flat_map(|w| (w as u32).to_le_bytes())
+                .collect(),
+            NodeWidth::Bits64 => words.into_iter().flat_map(u64::to_le_bytes).collect(),
+        };
+        Ok(Self {
+            layout: EsvoLayout {
+                node_width: width,
+                node_count,
+                attribute_size: T::SIZE,
+                attribute_count: attribute_count as usize,
+                height,
+                root,
+            },
+            nodes,
+            attributes,
+        })
+    }
+
+    /// Read the node word at a specific index.
+    ///
+    /// # Panics
+    ///
+    /// * `index` ≥ `self.layout.node_count`
+    pub fn word(&self, index: u32) -> u64 {
+        let bytes = self.layout.node_width.bytes();
+        let i = index as usize * bytes;
+        let word = &self.nodes[i..(i + bytes)];
+        match self.layout.node_width {
+            NodeWidth::Bits32 => u32::from_le_bytes(word.try_into().unwrap()).into(),
+            NodeWidth::Bits64 => u64::from_le_bytes(word.try_into().unwrap()),
+        }
+    }
+
+    /// Decode the child descriptor at a specific index, as `(valid mask, leaf mask, index of
+    /// first child)`, following far pointers.
+    ///
+    /// # Panics
+    ///
+    /// * `index` ≥ `self.layout.node_count`
+    pub fn descriptor(&self, index: u32) -> (u8, u8, u32) {
+        let word = self.word(index);
+        match self.layout.node_width {
+            NodeWidth::Bits32 => {
+                let mut ptr = (word >> 17) as u32;
+                if word & (1 << 16) != 0 {
+                    ptr = self.word(index + ptr) as u32;
+                }
+                ((word >> 8) as u8, word as u8, index + ptr)
+            }
+            NodeWidth::Bits64 => ((word >> 40) as u8, (word >> 32) as u8, word as u32),
+        }
+    }
+
+    /// Get the attribute bytes at a specific index.
+    ///
+    /// # Panics
+    ///
+    /// * `index` ≥ `self.layout.attribute_count`
+    pub fn attribute(&self, index: u32) -> &[u8] {
+        let size = self.layout.attribute_size;
+        let i = index as usize * size;
+        &self.attributes[i..(i + size)]
+    }
+
+    /// Reference traversal: find the deepest node containing a point `p`, within a tree bounded
+    /// by `aabb`.
+    ///
+    /// Returns the node's bounding volume, its kind (as the index of its descriptor or attribute),
+    /// and its depth, matching [`VoxelOctree::node_containing`]; or `None` if `p` ∉ `aabb`.
+    pub fn node_containing<Real: Float>(
+        &self,
+        aabb: &Aabb<Real>,
+        p: &Point3<Real>,
+    ) -> Option<(Aabb<Real>, ProxyData<u32>, u32)> {
+        if !aabb.contains(p) {
+            return None;
+        }
+        let mut aabb = *aabb;
+        let mut depth = 0;
+        let mut desc = match self.layout.root {
+            ProxyData::Branch(d) => d,
+            other => return Some((aabb, other, depth)),
+        };
+        loop {
+            let (valid, leaf, first) = self.descriptor(desc);
+            let oct = aabb.octant_of(p);
+            aabb = aabb.child(oct);
+            depth += 1;
+            let bit = 1u8 << oct.0;
+            if valid & bit == 0 {
+                return Some((aabb, ProxyData::Void, depth));
+            }
+            let slot = first + (valid & (bit - 1)).count_ones();
+            if leaf & bit != 0 {
+                return Some((aabb, ProxyData::Leaf(self.word(slot) as u32), depth));
+            }
+            desc = slot;
+        }
+    }
+}
+
+impl<T: MappedLeaf, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Flatten `self` into an [`EsvoExport`].
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexOverflow`](FormatError::IndexOverflow) if the export needs more than `2³²` node
+    ///   words.
+    pub fn to_esvo(&self, width: NodeWidth) -> Result<EsvoExport, FormatError> {
+        EsvoExport::build(self, width, NEAR_LIMIT)
+    }
+}
+
+impl<T: MappedLeaf, Real: Float, Idx: ArrayIndex, S: OctreeStorage> VoxelOctree<T, Real, Idx, S> {
+    /// Flatten `self` into an [`EsvoExport`]; traverse it using [`Self::aabb`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Octree::to_esvo`].
+    pub fn to_esvo(&self, width: NodeWidth) -> Result<EsvoExport, FormatError> {
+        self.base().to_esvo(width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector, Point3};
+
+    use super::{EsvoExport, NodeWidth};
+    use crate::{
+        spatial::VoxelOctree, MappedLeaf, NodeData, Octant, OctreeStorage, ProxyData,
+        StableStorage, VecStorage,
+    };
+
+    /// Build a tree of height 3 with voxels of size 1.
+    fn tree<S: OctreeStorage>() -> VoxelOctree<u16, f32, u32, S> {
+        let mut tree = VoxelOctree::<u16, f32, u32, S>::new(vector![1.0, 1.0, 1.0]);
+        for _ in 0..3 {
+            tree.grow(Octant(0));
+        }
+        let voxels: [(Point3<f32>, u16); 4] = [
+            (point![0.5, 0.5, 0.5], 1),
+            (point![1.5, 0.5, 0.5], 2),
+            (point![7.5, 0.5, 3.5], 3),
+            (point![4.5, 6.5, 6.5], 4),
+        ];
+        for (p, data) in voxels {
+            tree.node_at_mut(&p)
+                .unwrap()
+                .leaf_data_or_insert_with(|| data)
+                .unwrap();
+        }
+        tree
+    }
+
+    fn check<S: OctreeStorage>(tree: &VoxelOctree<u16, f32, u32, S>, esvo: &EsvoExport) {
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    let p = point![x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
+                    let (aabb, node, depth) = tree.node_containing(&p).unwrap();
+                    let (e_aabb, e_node, e_depth) = esvo.node_containing(tree.aabb(), &p).unwrap();
+                    assert_eq!((aabb, depth), (e_aabb, e_depth));
+                    match (node.into_inner().3, e_node) {
+                        (NodeData::Leaf(l), ProxyData::Leaf(a)) => {
+                            assert_eq!(*l, u16::from_bytes(esvo.attribute(a)));
+                        }
+                        (NodeData::Void, ProxyData::Void) => {}
+                        other => panic!("mismatched nodes: {other:?}"),
+                    }
+                }
+            }
+        }
+        assert!(esvo
+            .node_containing(tree.aabb(), &point![8.5, 0.0, 0.0])
+            .is_none());
+    }
+
+    #[test]
+    fn reference_traversal() {
+        let tree = tree::<StableStorage>();
+        for width in [NodeWidth::Bits32, NodeWidth::Bits64] {
+            let esvo = tree.to_esvo(width).unwrap();
+            // the root, then 3 branches at each of depths 1 & 2, then 4 leafs
+            assert_eq!(esvo.layout.node_count, 1 + 3 + 3 + 4);
+            assert_eq!(esvo.layout.attribute_count, 4);
+            assert_eq!(esvo.nodes.len(), 11 * width.bytes());
+            check(&tree, &esvo);
+        }
+    }
+
+    #[test]
+    fn far_pointers() {
+        let tree = tree::<StableStorage>();
+        let esvo = EsvoExport::build(tree.base(), NodeWidth::Bits32, 2).unwrap();
+        assert!(esvo.layout.node_count > 11);
+        check(&tree, &esvo);
+    }
+
+    #[test]
+    fn vec_storage() {
+        let expected = tree::<StableStorage>().to_esvo(NodeWidth::Bits32).unwrap();
+        let tree = tree::<VecStorage>();
+        let esvo = tree.to_esvo(NodeWidth::Bits32).unwrap();
+        assert_eq!(esvo.nodes, expected.nodes);
+        assert_eq!(esvo.attributes, expected.attributes);
+        check(&tree, &esvo);
+    }
+}
@@ -1,6 +1,8 @@
 //! [Octrees](crate::Octree) with a defined relation to a 3D space.
 
+mod boolean;
 mod bounding_cube;
+mod bulk;
 mod debug;
 use std::ops::Range;
 
@@ -9,22 +11,43 @@ mod bounding_box;
 pub use bounding_box::*;
 mod error;
 pub use error::*;
+mod format;
 pub(crate) mod macros;
 mod octant;
+#[cfg(feature = "serde")]
+mod ser;
+mod stats;
 mod traits;
+mod visit;
 use num_traits::AsPrimitive;
+pub use stats::*;
 use tracing::instrument;
 pub use traits::*;
+pub use visit::*;
 
 use eightfold_common::ArrayIndex;
 use nalgebra::{Point3, Vector3};
 
-use crate::{LeafMut, Node, NodeMut, Octant, Octree, OctreeSlice, Proxy, ProxyData};
+use crate::{
+    Direction, LeafMut, Node, NodeMut, Octant, Octree, OctreeStorage, Proxy, ProxyData,
+    StableStorage,
+};
 
-/// An [Octree] indexing a defined voxel space.
+/// A node of a [`VoxelOctree`] along with its bounding volume, if the node exists.
+pub type BoundedNode<'tree, T, Real, Idx, S = StableStorage> =
+    Option<(Aabb<Real>, Node<'tree, T, Idx, S>)>;
+
+/// An [Octree] indexing a defined voxel space, stored as chosen by `S`.
 #[derive(Debug)]
-pub struct VoxelOctree<T, Real: Float, Idx: ArrayIndex> {
-    base: Octree<T, Idx>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Real: serde::Serialize, Idx: serde::Serialize, Octree<T, Idx, S>: serde::Serialize"
+    ))
+)]
+pub struct VoxelOctree<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    base: Octree<T, Idx, S>,
     /// The number of branches between the root and the voxel grid.
     height: Idx,
     /// The dimensions of a single voxel.
@@ -33,7 +56,7 @@ pub struct VoxelOctree<T, Real: Float, Idx: ArrayIndex> {
     aabb: Aabb<Real>,
 }
 
-impl<T, Real: Float, Idx: ArrayIndex> VoxelOctree<T, Real, Idx> {
+impl<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage> VoxelOctree<T, Real, Idx, S> {
     /// Construct a [`VoxelTree`] encompassing the voxel at [0,0,0].
     pub fn new(voxel_size: Vector3<Real>) -> Self {
         Self {
@@ -47,6 +70,12 @@ impl<T, Real: Float, Idx: ArrayIndex> VoxelOctree<T, Real, Idx> {
         }
     }
 
+    /// The underlying [Octree].
+    #[inline]
+    pub fn base(&self) -> &Octree<T, Idx, S> {
+        &self.base
+    }
+
     #[inline]
     pub fn aabb(&self) -> &Aabb<Real> {
         &self.aabb
@@ -102,6 +131,27 @@ impl<T, Real: Float, Idx: ArrayIndex> VoxelOctree<T, Real, Idx> {
         self.grow_to_contain(&vol.maxs) || g1
     }
 
+    /// Collapse every branch whose children are all void; see [`Octree::prune`].
+    #[inline]
+    pub fn prune(&mut self) -> usize {
+        self.base.prune()
+    }
+
+    /// [Prune](Self::prune) `self`, then [shrink](Octree::shrink) it while its root has only one
+    /// non-void child, updating its height & bounding volume to match, and return the [Octant] of
+    /// each promoted child.
+    ///
+    /// This is the inverse of [`Self::grow`]; the voxel size is unchanged.
+    pub fn shrink_to_fit(&mut self) -> Vec<Octant> {
+        self.base.prune();
+        let path = self.base.shrink();
+        for oct in &path {
+            self.height -= Idx::ONE;
+            self.aabb = self.aabb.child(*oct);
+        }
+        path
+    }
+
     /// Get the index of the deepest node containing a given [point](Point3) `p`.
     ///
     /// # Errors
@@ -112,7 +162,7 @@ impl<T, Real: Float, Idx: ArrayIndex> VoxelOctree<T, Real, Idx> {
     pub fn node_containing<'tree>(
         &'tree self,
         p: &Point3<Real>,
-    ) -> Result<(Aabb<Real>, Node<'tree, T, Idx>, Idx), Error<Idx, Real>> {
+    ) -> Result<(Aabb<Real>, Node<'tree, T, Idx, S>, Idx), Error<Idx, Real>> {
         if !self.aabb.contains(p) {
             return Err(Error::PointOutOfBounds(self.aabb, *p));
         }
@@ -129,7 +179,7 @@ impl<T, Real: Float, Idx: ArrayIndex> VoxelOctree<T, Real, Idx> {
     pub unsafe fn node_containing_unchecked<'tree>(
         &'tree self,
         p: &Point3<Real>,
-    ) -> (Aabb<Real>, Node<'tree, T, Idx>, Idx) {
+    ) -> (Aabb<Real>, Node<'tree, T, Idx, S>, Idx) {
         let branch_data = self.base.branch_data();
         let mut depth = Idx::ZERO;
         let mut oct;
@@ -145,6 +195,42 @@ impl<T, Real: Float, Idx: ArrayIndex> VoxelOctree<T, Real, Idx> {
         (aabb, self.base.node(idx).unwrap(), depth)
     }
 
+    /// Get the bounding volume of the node at a specific index.
+    ///
+    /// # Panics
+    ///
+    /// * `index` ∉ `self`
+    pub fn aabb_of_unchecked(&self, mut index: Idx) -> Aabb<Real> {
+        let mut path = Vec::new();
+        while let Some(oct) = self.base.octant_of_unchecked(index) {
+            path.push(oct);
+            index = self.base.get(index).parent;
+        }
+        path.into_iter()
+            .rev()
+            .fold(self.aabb, |aabb, oct| aabb.child(oct))
+    }
+
+    /// Get the neighbor, in a given [Direction], of the deepest node containing a point `p`.
+    ///
+    /// If the neighbor at the same depth doesn't exist, its deepest existing ancestor is given
+    /// instead; the result is `None` if the neighbor would lie outside of `self`. See
+    /// [`Octree::neighbor_of_unchecked`].
+    ///
+    /// # Errors
+    ///
+    /// * [`PointOutOfBounds`](Error::PointOutOfBounds) if `p` ∉ `self`.
+    pub fn neighbor_containing<'tree>(
+        &'tree self,
+        p: &Point3<Real>,
+        dir: Direction,
+    ) -> Result<BoundedNode<'tree, T, Real, Idx, S>, Error<Idx, Real>> {
+        let (_, node, _) = self.node_containing(p)?;
+        Ok(node
+            .neighbor(dir)
+            .map(|n| (self.aabb_of_unchecked(n.index()), n)))
+    }
+
     /// Insert data into a leaf node encompassing the voxel at point `p`.
     ///
     /// # Errors
@@ -177,23 +263,27 @@ impl<T, Real: Float, Idx: ArrayIndex> VoxelOctree<T, Real, Idx> {
 
     /// Gets a mutable reference to a leaf node encompassing the voxel at point `p`. The node and
     /// its parents will be created if it doesn't exist.
+    #[allow(unsafe_code)]
     pub fn node_at_mut<'tree>(
         &'tree mut self,
         p: &Point3<Real>,
-    ) -> Result<NodeMut<'tree, T, Idx>, Error<Idx, Real>>
+    ) -> Result<NodeMut<'tree, T, Idx, S>, Error<Idx, Real>>
     where
         usize: AsPrimitive<Idx>,
         Range<Idx>: Iterator,
     {
+        if !self.aabb.contains(p) {
+            return Err(Error::PointOutOfBounds(self.aabb, *p));
+        }
         let mut oct: Octant;
-        let (mut aabb, node, mut depth) = self.node_containing(p)?;
-        let mut node = self.base.node_mut(node.index()).unwrap();
-        while depth != self.height {
+        let mut aabb = self.aabb;
+        let mut cursor = self.base.cursor_mut();
+        while cursor.depth() != self.height {
+            // safety: `p` was already confirmed to lie within `aabb`
             (oct, aabb) = unsafe { aabb.child_containing_unchecked(p) };
-            node = node.split()?.child(oct);
-            depth += Idx::ONE;
+            cursor.split()?.move_to_child(oct)?;
         }
-        Ok(node)
+        Ok(cursor.into_node_mut())
     }
 }
 
@@ -0,0 +1,286 @@
+use eightfold_common::ArrayIndex;
+use nalgebra::{Point3, Vector3};
+use num_traits::AsPrimitive;
+
+use super::{Error, Float, VoxelOctree};
+use crate::{Octant, Octree, OctreeStorage};
+
+/// The position of `p` relative to `origin`, in whole voxels, if `p` lies on the voxel grid
+/// starting at `origin`.
+fn grid_offset<Real: Float>(
+    origin: &Point3<Real>,
+    p: &Point3<Real>,
+    voxel_size: &Vector3<Real>,
+) -> Option<[i128; 3]> {
+    let tolerance = Real::epsilon().sqrt();
+    let mut res = [0; 3];
+    for (axis, r) in res.iter_mut().enumerate() {
+        let d = (p[axis] - origin[axis]) / voxel_size[axis];
+        if (d - d.round()).abs() > tolerance {
+            return None;
+        }
+        *r = d.round().to_i128()?;
+    }
+    Some(res)
+}
+
+/// Along one axis, given two trees covering `[a, a + 2^h_a)` & `[b, b + 2^h_b)` voxels, find the
+/// lowest height & the start of a node at that height covering both, which both trees can reach
+/// by growing alone.
+fn common_cell(a: i128, h_a: u32, b: i128, h_b: u32) -> Option<(u32, i128)> {
+    // the start of the taller tree only moves by multiples of its own size
+    let ((a, h_a), (b, h_b)) = if h_a <= h_b {
+        ((a, h_a), (b, h_b))
+    } else {
+        ((b, h_b), (a, h_a))
+    };
+    if (b - a).rem_euclid(1 << h_a) != 0 {
+        return None;
+    }
+    let step = 1i128 << h_b;
+    let start = if b > a {
+        b - (b - a + step - 1).div_euclid(step) * step
+    } else {
+        b
+    };
+    let end = (a + (1 << h_a)).max(b + step);
+    let mut height = h_b;
+    while start + (1 << height) < end {
+        height += 1;
+    }
+    Some((height, start))
+}
+
+/// Where two trees lie within the grid they can both grow into, in voxels from its origin.
+struct Alignment {
+    a: [i128; 3],
+    b: [i128; 3],
+    height: u32,
+}
+
+impl<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage> VoxelOctree<T, Real, Idx, S> {
+    /// Grow `self` up to `height`, through the nodes of a grid in which `self` lies `offset`
+    /// voxels from the origin.
+    fn grow_into(&mut self, offset: [i128; 3], height: u32)
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        let mut h = AsPrimitive::<usize>::as_(self.height) as u32;
+        while h < height {
+            let [i, j, k] = offset.map(|o| (o >> h) & 1 == 1);
+            self.grow(Octant::new(i, j, k));
+            h += 1;
+        }
+    }
+
+    /// Grow `self` & `other` until they share a bounding volume, so that their nodes correspond
+    /// one-to-one.
+    ///
+    /// # Errors
+    ///
+    /// * [`VoxelSizeMismatch`](Error::VoxelSizeMismatch) if the voxel sizes of `self` & `other`
+    ///   differ.
+    /// * [`MisalignedGrids`](Error::MisalignedGrids) if no bounding volume is reachable from both
+    ///   by growing alone, such as when their voxel grids are offset by a fraction of a voxel.
+    pub fn align_with(&mut self, other: &mut Self) -> Result<(), Error<Idx, Real>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        let Alignment { a, b, height } = self.alignment(other)?;
+        self.grow_into(a, height);
+        other.grow_into(b, height);
+        // both grew into the same volume, up to floating-point error
+        debug_assert!(
+            grid_offset(&self.aabb.mins, &other.aabb.mins, &self.voxel_size) == Some([0; 3])
+                && grid_offset(&self.aabb.maxs, &other.aabb.maxs, &self.voxel_size) == Some([0; 3]),
+            "aligned bounding volumes differ: {:?} & {:?}",
+            self.aabb,
+            other.aabb
+        );
+        Ok(())
+    }
+
+    /// The grid [`Self::align_with`] grows `self` & `other` into.
+    fn alignment(&self, other: &Self) -> Result<Alignment, Error<Idx, Real>> {
+        if self.voxel_size != other.voxel_size {
+            return Err(Error::VoxelSizeMismatch(self.voxel_size, other.voxel_size));
+        }
+        let misaligned = || Error::MisalignedGrids(self.aabb, other.aabb);
+        let b = grid_offset(&self.aabb.mins, &other.aabb.mins, &self.voxel_size)
+            .ok_or_else(misaligned)?;
+        let h_a = AsPrimitive::<usize>::as_(self.height) as u32;
+        let h_b = AsPrimitive::<usize>::as_(other.height) as u32;
+        let mut height = 0;
+        let mut start = [0; 3];
+        for axis in 0..3 {
+            let (h, s) = common_cell(0, h_a, b[axis], h_b).ok_or_else(misaligned)?;
+            height = height.max(h);
+            start[axis] = s;
+        }
+        Ok(Alignment {
+            a: start.map(|s| -s),
+            b: [0, 1, 2].map(|axis| b[axis] - start[axis]),
+            height,
+        })
+    }
+
+    /// Apply `op` to copies of `self` & `other`, aligned as by [`Self::align_with`].
+    fn set_op(
+        &self,
+        other: &Self,
+        op: impl FnOnce(
+            &Octree<T, Idx, S>,
+            &Octree<T, Idx, S>,
+        ) -> Result<Octree<T, Idx, S>, crate::Error<Idx>>,
+    ) -> Result<Self, Error<Idx, Real>>
+    where
+        T: Clone,
+        Octree<T, Idx, S>: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        let Alignment { a, b, height } = self.alignment(other)?;
+        let copy = |tree: &Self, offset| {
+            let mut res = Self {
+                base: tree.base.clone(),
+                height: tree.height,
+                voxel_size: tree.voxel_size,
+                aabb: tree.aabb,
+            };
+            res.grow_into(offset, height);
+            res
+        };
+        let (a, b) = (copy(self, a), copy(other, b));
+        Ok(Self {
+            base: op(&a.base, &b.base)?,
+            ..a
+        })
+    }
+
+    /// The union of `self` & `other`, after [aligning](Self::align_with) them; see
+    /// [`Octree::union`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::align_with`] & [`Octree::union`].
+    pub fn union(
+        &self,
+        other: &Self,
+        combine: impl FnMut(&T, &T) -> T,
+    ) -> Result<Self, Error<Idx, Real>>
+    where
+        T: Clone,
+        Octree<T, Idx, S>: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, |a, b| a.union(b, combine))
+    }
+
+    /// The intersection of `self` & `other`, after [aligning](Self::align_with) them; see
+    /// [`Octree::intersection`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::align_with`] & [`Octree::intersection`].
+    pub fn intersection(
+        &self,
+        other: &Self,
+        combine: impl FnMut(&T, &T) -> T,
+    ) -> Result<Self, Error<Idx, Real>>
+    where
+        T: Clone,
+        Octree<T, Idx, S>: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, |a, b| a.intersection(b, combine))
+    }
+
+    /// `self` without `other`, after [aligning](Self::align_with) them; see
+    /// [`Octree::difference`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::align_with`] & [`Octree::difference`].
+    pub fn difference(&self, other: &Self) -> Result<Self, Error<Idx, Real>>
+    where
+        T: Clone,
+        Octree<T, Idx, S>: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, Octree::difference)
+    }
+
+    /// The symmetric difference of `self` & `other`, after [aligning](Self::align_with) them; see
+    /// [`Octree::symmetric_difference`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::align_with`] & [`Octree::symmetric_difference`].
+    pub fn symmetric_difference(&self, other: &Self) -> Result<Self, Error<Idx, Real>>
+    where
+        T: Clone,
+        Octree<T, Idx, S>: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, Octree::symmetric_difference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector, Point3};
+
+    use crate::{spatial::VoxelOctree, OctreeSlice};
+
+    fn tree(points: &[Point3<f32>]) -> VoxelOctree<u8, f32, u32> {
+        VoxelOctree::from_points(
+            points.iter().map(|p| (*p, 1)),
+            vector![1.0, 1.0, 1.0],
+            |a, _| a,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn align_with() {
+        let mut a = tree(&[point![0.5, 0.5, 0.5], point![1.5, 0.5, 0.5]]);
+        let mut b = tree(&[point![1.5, 0.5, 0.5], point![-2.5, 3.5, 0.5]]);
+        a.align_with(&mut b).unwrap();
+        assert_eq!(a.aabb(), b.aabb());
+        assert_eq!(a.height, b.height);
+        assert!(a.contains(&point![1.5, 0.5, 0.5]));
+        assert!(a.contains(&point![-2.5, 3.5, 0.5]));
+        assert_eq!(a.base().leaf_dfi().count(), 2);
+        assert_eq!(b.base().leaf_dfi().count(), 2);
+
+        // x ∈ [0,2) can only grow into nodes starting at even x, [-3,1) into ones at odd x
+        let mut a = tree(&[point![0.5, 0.5, 0.5], point![1.5, 0.5, 0.5]]);
+        let mut b = tree(&[point![-2.5, 3.5, 0.5]]);
+        assert!(matches!(
+            a.align_with(&mut b),
+            Err(crate::spatial::Error::MisalignedGrids(..))
+        ));
+
+        let mut c = VoxelOctree::<u8, f32, u32>::new(vector![2.0, 1.0, 1.0]);
+        assert!(a.align_with(&mut c).is_err());
+    }
+
+    #[test]
+    fn set_ops() {
+        let a = tree(&[point![0.5, 0.5, 0.5], point![1.5, 0.5, 0.5]]);
+        let b = tree(&[point![1.5, 0.5, 0.5], point![-2.5, 3.5, 0.5]]);
+        let count = |t: VoxelOctree<u8, f32, u32>| t.base().leaf_dfi().count();
+        assert_eq!(count(a.union(&b, |a, b| a + b).unwrap()), 3);
+        assert_eq!(count(a.intersection(&b, |a, b| a + b).unwrap()), 1);
+        assert_eq!(count(a.difference(&b).unwrap()), 1);
+        assert_eq!(count(a.symmetric_difference(&b).unwrap()), 2);
+        // the operands are left as they were, even on failure
+        let c = tree(&[point![-2.5, 3.5, 0.5]]);
+        assert!(a.union(&c, |a, b| a + b).is_err());
+        assert_eq!(a.height, 1);
+        assert_eq!(a.base().leaf_dfi().count(), 2);
+
+        let union = a.union(&b, |a, b| a + b).unwrap();
+        assert_eq!(union.base().leaf_dfi().map(|(l, _)| *l).max(), Some(2));
+        assert!(union.contains(&point![-2.5, 3.5, 0.5]));
+    }
+}
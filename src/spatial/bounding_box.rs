@@ -14,6 +14,7 @@ pub enum AabbError<Real: Float> {
 ///
 /// Similar to [`parry3d::Aabb`], except generic over the Real type.
 #[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Aabb<Real: Float> {
     pub mins: Point3<Real>,
     pub maxs: Point3<Real>,
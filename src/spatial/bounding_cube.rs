@@ -12,6 +12,7 @@ pub enum AabcError<Real: Float> {
 
 /// Axis-Aligned Bounding Cube
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Aabc<R: Float> {
     pub origin: Point3<R>,
     pub length: R,
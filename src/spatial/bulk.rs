@@ -0,0 +1,91 @@
+use eightfold_common::{morton, ArrayIndex};
+use nalgebra::{Point3, Vector3};
+use num_traits::AsPrimitive;
+
+use super::{Float, VoxelOctree};
+use crate::{Error, Octree, OctreeStorage};
+
+impl<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage> VoxelOctree<T, Real, Idx, S> {
+    /// Build a tree from a set of points in one pass, combining the data of points within the same
+    /// voxel with `merge` (such as [`LeafMerge::leaf_merge`](crate::LeafMerge::leaf_merge)), in
+    /// input order.
+    ///
+    /// The result is equivalent to starting from [`Self::new`] & inserting each point in turn
+    /// through [`Self::node_at_mut`], growing as necessary, but avoids a descent from the root for
+    /// every point.
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the tree would need more nodes than `Idx`
+    ///   can address.
+    pub fn from_points(
+        points: impl IntoIterator<Item = (Point3<Real>, T)>,
+        voxel_size: Vector3<Real>,
+        merge: impl FnMut(T, T) -> T,
+    ) -> Result<Self, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        let mut res = Self::new(voxel_size);
+        // grow the bounding volume alone, exactly as incremental insertion would
+        let points = points
+            .into_iter()
+            .map(|(p, data)| {
+                while !res.aabb.contains(&p) {
+                    res.aabb = res.aabb.parent(!res.aabb.octant_of(&p));
+                    res.height += Idx::ONE;
+                }
+                (p, data)
+            })
+            .collect::<Vec<_>>();
+        let height: usize = res.height.as_();
+        let leafs = points
+            .into_iter()
+            .map(|(p, data)| {
+                // descend through the bounding volume, as in `Self::node_containing`
+                let mut aabb = res.aabb;
+                let mut code = 0u128;
+                for _ in 0..height {
+                    let oct = aabb.octant_of(&p);
+                    code = (code << 3) | u128::from(oct.0);
+                    aabb = aabb.child(oct);
+                }
+                (code, data)
+            })
+            .collect();
+        debug_assert!(height <= morton::MAX_BITS as usize);
+        res.base = Octree::from_codes(res.height, leafs, merge)?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::{spatial::VoxelOctree, OctreeSlice};
+
+    #[test]
+    fn from_points() {
+        let points = [
+            (point![0.5, 0.5, 0.5], 1),
+            (point![3.5, -2.5, 0.5], 2),
+            (point![0.25, 0.75, 0.5], 4),
+            (point![-1.5, 5.5, 2.5], 8),
+        ];
+        let tree =
+            VoxelOctree::<u8, f32, u32>::from_points(points, vector![1.0, 1.0, 1.0], |a, b| a + b)
+                .unwrap();
+
+        let mut expected = VoxelOctree::<u8, f32, u32>::new(vector![1.0, 1.0, 1.0]);
+        for (p, data) in points {
+            expected.grow_to_contain(&p);
+            let mut node = expected.node_at_mut(&p).unwrap();
+            *node.leaf_data_or_insert_with(|| 0).unwrap() += data;
+        }
+
+        assert_eq!(tree.aabb(), expected.aabb());
+        assert!(tree.base().leaf_dfi().eq(expected.base().leaf_dfi()));
+        assert_eq!(tree.base().leaf_dfi().count(), 3);
+    }
+}
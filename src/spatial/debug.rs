@@ -4,8 +4,10 @@ use eightfold_common::ArrayIndex;
 use num_traits::AsPrimitive;
 
 use super::{Float, VoxelOctree};
+use crate::OctreeStorage;
 
-impl<Real: Float, T: std::fmt::Debug, Idx: ArrayIndex> Display for VoxelOctree<T, Real, Idx>
+impl<Real: Float, T: std::fmt::Debug, Idx: ArrayIndex, S: OctreeStorage> Display
+    for VoxelOctree<T, Real, Idx, S>
 where
     u8: AsPrimitive<Idx>,
 {
@@ -1,5 +1,5 @@
 use eightfold_common::ArrayIndex;
-use nalgebra::Point3;
+use nalgebra::{Point3, Vector3};
 
 use super::{Aabb, Float};
 
@@ -9,4 +9,8 @@ pub enum Error<Idx: ArrayIndex, Real: Float> {
     Octree(#[from] crate::tree::Error<Idx>),
     #[error("volume {0:?} does not contain point {1:?}")]
     PointOutOfBounds(Aabb<Real>, Point3<Real>),
+    #[error("voxel sizes {0:?} and {1:?} differ")]
+    VoxelSizeMismatch(Vector3<Real>, Vector3<Real>),
+    #[error("volumes {0:?} and {1:?} have no common ancestor")]
+    MisalignedGrids(Aabb<Real>, Aabb<Real>),
 }
@@ -0,0 +1,238 @@
+use std::{
+    io::{Read, Write},
+    mem,
+    ops::Range,
+};
+
+use eightfold_common::ArrayIndex;
+use nalgebra::{Point3, Vector3};
+use num_traits::{AsPrimitive, NumCast};
+
+use super::{Aabb, Float, VoxelOctree};
+use crate::{
+    tree::format::{read_header, write_header, FLAG_SPATIAL},
+    FormatError, LeafCodec, Octree, OctreeSlice, OctreeStorage,
+};
+
+/// Write a real number at its native width.
+fn write_real<Real: Float, W: Write>(w: &mut W, r: Real) -> std::io::Result<()> {
+    if mem::size_of::<Real>() == 4 {
+        w.write_all(&r.to_f32().unwrap_or(f32::NAN).to_le_bytes())
+    } else {
+        w.write_all(&r.to_f64().unwrap_or(f64::NAN).to_le_bytes())
+    }
+}
+
+/// Read a real number written by [`write_real`].
+fn read_real<Real: Float, R: Read>(r: &mut R) -> Result<Real, FormatError> {
+    let res = if mem::size_of::<Real>() == 4 {
+        let mut buf = [0; 4];
+        r.read_exact(&mut buf)?;
+        <Real as NumCast>::from(f32::from_le_bytes(buf))
+    } else {
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf)?;
+        <Real as NumCast>::from(f64::from_le_bytes(buf))
+    };
+    res.ok_or(FormatError::InvalidSpatialHeader("unrepresentable real"))
+}
+
+fn read_vector<Real: Float, R: Read>(r: &mut R) -> Result<Vector3<Real>, FormatError> {
+    Ok(Vector3::new(read_real(r)?, read_real(r)?, read_real(r)?))
+}
+
+/// Check that a voxel grid of `height` with voxels of `voxel_size` spans exactly `mins..maxs`, or
+/// describe how it doesn't.
+pub(super) fn check_bounds<Real: Float, Idx: ArrayIndex>(
+    voxel_size: &Vector3<Real>,
+    height: Idx,
+    mins: &Vector3<Real>,
+    maxs: &Vector3<Real>,
+) -> Result<(), &'static str> {
+    if !voxel_size
+        .iter()
+        .chain(mins.iter())
+        .chain(maxs.iter())
+        .all(|r| r.is_finite())
+    {
+        return Err("non-finite voxel space");
+    }
+    if !mins.iter().zip(maxs.iter()).all(|(min, max)| min < max) {
+        return Err("empty bounding volume");
+    }
+    let scale = (Real::ONE + Real::ONE).powi(height.to_i32().unwrap_or(i32::MAX));
+    let tolerance = Real::epsilon().sqrt();
+    for axis in 0..3 {
+        let expected = voxel_size[axis] * scale;
+        if ((maxs[axis] - mins[axis]) - expected).abs() > expected * tolerance {
+            return Err("bounding volume doesn't match the voxel grid");
+        }
+    }
+    Ok(())
+}
+
+impl<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage> VoxelOctree<T, Real, Idx, S> {
+    /// Write `self` in the eightfold binary format, using `codec` to encode leaf data.
+    ///
+    /// This is the format written by [`Octree::write_to`], with a spatial header following the
+    /// base header:
+    ///
+    /// | Field      | Size       | Contents                    |
+    /// |------------|------------|-----------------------------|
+    /// | real width | 1          | `size_of::<Real>()`         |
+    /// | height     | 8          | height of the voxel grid    |
+    /// | voxel size | `3 × real` | `XYZ` dimensions of a voxel |
+    /// | AABB mins  | `3 × real` |                             |
+    /// | AABB maxs  | `3 × real` |                             |
+    ///
+    /// # Errors
+    ///
+    /// * Any error produced by `w` or `codec`.
+    pub fn write_to<W: Write, C: LeafCodec<T>>(
+        &self,
+        mut w: W,
+        codec: &mut C,
+    ) -> Result<(), FormatError> {
+        write_header::<Idx, W>(&mut w, FLAG_SPATIAL)?;
+        w.write_all(&[mem::size_of::<Real>() as u8])?;
+        w.write_all(&self.height.to_u64().unwrap_or(u64::MAX).to_le_bytes())?;
+        for r in self
+            .voxel_size
+            .iter()
+            .chain(self.aabb.mins.iter())
+            .chain(self.aabb.maxs.iter())
+        {
+            write_real(&mut w, *r)?;
+        }
+        self.base.write_body(&mut w, codec)?;
+        Ok(())
+    }
+
+    /// Read a tree written by [`Self::write_to`], using `codec` to decode leaf data.
+    ///
+    /// # Errors
+    ///
+    /// * Any error given by [`Octree::read_from`].
+    /// * [`NotSpatial`](FormatError::NotSpatial) if the file was written by an [Octree].
+    /// * [`RealWidth`](FormatError::RealWidth) if the file was written with a different `Real`.
+    /// * [`InvalidSpatialHeader`](FormatError::InvalidSpatialHeader) if the spatial header
+    ///   doesn't describe a valid voxel space, or the tree is deeper than its voxel grid.
+    pub fn read_from<R: Read, C: LeafCodec<T>>(mut r: R, codec: &mut C) -> Result<Self, FormatError>
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        if read_header::<Idx, R>(&mut r)? & FLAG_SPATIAL == 0 {
+            return Err(FormatError::NotSpatial);
+        }
+        let mut real_width = [0];
+        r.read_exact(&mut real_width)?;
+        let expected = mem::size_of::<Real>() as u8;
+        if real_width[0] != expected {
+            return Err(FormatError::RealWidth {
+                expected,
+                found: real_width[0],
+            });
+        }
+        let mut height = [0; 8];
+        r.read_exact(&mut height)?;
+        let height = <Idx as NumCast>::from(u64::from_le_bytes(height)).ok_or(
+            FormatError::InvalidSpatialHeader("height exceeds index type"),
+        )?;
+        let voxel_size = read_vector::<Real, R>(&mut r)?;
+        if !voxel_size.iter().all(|s| *s > Real::ZERO) {
+            return Err(FormatError::InvalidSpatialHeader("non-positive voxel size"));
+        }
+        let mins = read_vector::<Real, R>(&mut r)?;
+        let maxs = read_vector::<Real, R>(&mut r)?;
+        check_bounds(&voxel_size, height, &mins, &maxs)
+            .map_err(FormatError::InvalidSpatialHeader)?;
+        let aabb = Aabb::new(Point3::from(mins), Point3::from(maxs));
+        let base = Octree::read_body(&mut r, codec)?;
+        if base.height() > height {
+            return Err(FormatError::InvalidSpatialHeader(
+                "tree is deeper than its voxel grid",
+            ));
+        }
+        Ok(Self {
+            base,
+            height,
+            voxel_size,
+            aabb,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::{spatial::VoxelOctree, FormatError, LittleEndian, Octree, OctreeSlice};
+
+    /// Build a tree with leafs at two points, on a grid of non-cubic voxels.
+    fn tree() -> VoxelOctree<u8, f32, u16> {
+        VoxelOctree::from_points(
+            [(point![0.5, 0.5, 0.25], 1), (point![-2.5, 3.5, 0.25], 2)],
+            vector![1.0, 2.0, 0.5],
+            |a, _| a,
+        )
+        .unwrap()
+    }
+
+    fn write(tree: &VoxelOctree<u8, f32, u16>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        tree.write_to(&mut buf, &mut LittleEndian).unwrap();
+        buf
+    }
+
+    #[test]
+    fn round_trip() {
+        let tree = tree();
+        let buf = write(&tree);
+        let res =
+            VoxelOctree::<u8, f32, u16>::read_from(buf.as_slice(), &mut LittleEndian).unwrap();
+        assert_eq!(res.aabb(), tree.aabb());
+        assert_eq!(res.height, tree.height);
+        assert_eq!(res.voxel_size, tree.voxel_size);
+        assert!(res.base().leaf_dfi().eq(tree.base().leaf_dfi()));
+
+        // the spatial header is skipped when reading a plain tree
+        let base = Octree::<u8, u16>::read_from(buf.as_slice(), &mut LittleEndian).unwrap();
+        assert!(base.leaf_dfi().eq(tree.base().leaf_dfi()));
+        // ...but a plain tree can't be read as a spatial one
+        let mut plain = Vec::new();
+        base.write_to(&mut plain, &mut LittleEndian).unwrap();
+        assert!(matches!(
+            VoxelOctree::<u8, f32, u16>::read_from(plain.as_slice(), &mut LittleEndian),
+            Err(FormatError::NotSpatial)
+        ));
+    }
+
+    #[test]
+    fn header() {
+        let buf = write(&tree());
+        assert!(matches!(
+            VoxelOctree::<u8, f64, u16>::read_from(buf.as_slice(), &mut LittleEndian),
+            Err(FormatError::RealWidth {
+                expected: 8,
+                found: 4
+            })
+        ));
+
+        // base header, real width, height, voxel size, then the bounds start at byte 28
+        let read = |buf: &[u8]| VoxelOctree::<u8, f32, u16>::read_from(buf, &mut LittleEndian);
+        let with_min_x = |x: f32| {
+            let mut bad = buf.clone();
+            bad[28..32].copy_from_slice(&x.to_le_bytes());
+            bad
+        };
+        let min_x = f32::from_le_bytes(buf[28..32].try_into().unwrap());
+        assert!(read(&with_min_x(min_x)).is_ok());
+        for x in [f32::NAN, f32::NEG_INFINITY, min_x + 1.0, min_x + 8.0] {
+            assert!(matches!(
+                read(&with_min_x(x)),
+                Err(FormatError::InvalidSpatialHeader(_))
+            ));
+        }
+    }
+}
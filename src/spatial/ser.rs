@@ -0,0 +1,56 @@
+//! [Serde](https://serde.rs) support for [`VoxelOctree`].
+
+use eightfold_common::ArrayIndex;
+use nalgebra::Vector3;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use super::{format::check_bounds, Aabb, Float, VoxelOctree};
+use crate::{Octree, OctreeSlice, OctreeStorage};
+
+/// Mirror of [`VoxelOctree`], deserialized before its invariants have been checked.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "Real: Deserialize<'de>, Idx: Deserialize<'de>, \
+    Octree<T, Idx, S>: Deserialize<'de>"))]
+struct RawVoxelOctree<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage> {
+    base: Octree<T, Idx, S>,
+    height: Idx,
+    voxel_size: Vector3<Real>,
+    aabb: Aabb<Real>,
+}
+
+impl<'de, T, Real, Idx, S: OctreeStorage> Deserialize<'de> for VoxelOctree<T, Real, Idx, S>
+where
+    Real: Float + Deserialize<'de>,
+    Idx: ArrayIndex + Deserialize<'de>,
+    Octree<T, Idx, S>: Deserialize<'de>,
+{
+    /// Deserialize a [`VoxelOctree`], rejecting any input which doesn't describe a well-formed
+    /// tree.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let RawVoxelOctree {
+            base,
+            height,
+            voxel_size,
+            aabb,
+        } = RawVoxelOctree::<T, Real, Idx, S>::deserialize(deserializer)?;
+        if base.height() > height {
+            return Err(D::Error::custom(format!(
+                "tree of height {:?} exceeds voxel grid of height {height:?}",
+                base.height()
+            )));
+        }
+        if !voxel_size.iter().all(|s| *s > Real::ZERO) {
+            return Err(D::Error::custom(format!(
+                "voxel size must be positive: {voxel_size:?}"
+            )));
+        }
+        check_bounds(&voxel_size, height, &aabb.mins.coords, &aabb.maxs.coords)
+            .map_err(|e| D::Error::custom(format!("{e}: {aabb:?}")))?;
+        Ok(Self {
+            base,
+            height,
+            voxel_size,
+            aabb,
+        })
+    }
+}
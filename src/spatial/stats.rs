@@ -0,0 +1,74 @@
+use eightfold_common::ArrayIndex;
+use num_traits::NumCast;
+
+use crate::{OctreeStats, OctreeStorage};
+
+use super::{Float, VoxelOctree};
+
+/// An [`OctreeStats`] report on a [`VoxelOctree`], along with the world-space volume it occupies;
+/// see [`VoxelOctree::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelOctreeStats<Real: Float> {
+    /// Memory usage & shape of the underlying [`crate::Octree`].
+    pub tree: OctreeStats,
+    /// Volume of the bounding box of the tree.
+    pub volume: Real,
+    /// Total volume of all leafs.
+    pub occupied_volume: Real,
+}
+
+impl<Real: Float> VoxelOctreeStats<Real> {
+    /// The fraction of the bounding volume occupied by leafs.
+    #[inline]
+    pub fn occupancy(&self) -> Real {
+        self.occupied_volume / self.volume
+    }
+}
+
+impl<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage> VoxelOctree<T, Real, Idx, S> {
+    /// Measure the memory usage, shape & occupancy of this tree; see [`crate::Octree::stats`].
+    pub fn stats(&self) -> VoxelOctreeStats<Real> {
+        let tree = self.base.stats();
+        let extents = self.aabb.maxs - self.aabb.mins;
+        let volume = extents.x * extents.y * extents.z;
+        // each level down divides a node's volume by 8
+        let eighth = Real::ONE / (Real::TWO * Real::TWO * Real::TWO);
+        let mut node_volume = volume;
+        let mut occupied_volume = Real::ZERO;
+        for &count in &tree.leaf_depths {
+            occupied_volume += node_volume * <Real as NumCast>::from(count).unwrap();
+            node_volume = node_volume * eighth;
+        }
+        VoxelOctreeStats {
+            tree,
+            volume,
+            occupied_volume,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::spatial::VoxelOctree;
+
+    #[test]
+    fn occupancy() {
+        let mut tree = VoxelOctree::<u8, f32, u32>::new(vector![1.0, 2.0, 1.0]);
+        tree.grow_to_contain(&point![3.5, 0.5, 0.5]);
+        for p in [point![0.5, 0.5, 0.5], point![3.5, 0.5, 0.5]] {
+            *tree
+                .node_at_mut(&p)
+                .unwrap()
+                .leaf_data_or_insert_with(|| 0)
+                .unwrap() += 1;
+        }
+
+        let stats = tree.stats();
+        assert_eq!(stats.tree.height(), 2);
+        assert_eq!(stats.volume, 4.0 * 8.0 * 4.0);
+        assert_eq!(stats.occupied_volume, 2.0 * 2.0);
+        assert_eq!(stats.occupancy(), 1.0 / 32.0);
+    }
+}
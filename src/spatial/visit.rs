@@ -0,0 +1,222 @@
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use super::{Aabb, Float, VoxelOctree};
+use crate::{NodePoint, Octant, OctreeStorage, OctreeVisitor, Proxy, VisitControl};
+
+/// Callbacks for a depth-first traversal of a [`VoxelOctree`], by [Octant] ordering; see
+/// [`VoxelOctree::visit`].
+///
+/// Like [`OctreeVisitor`], except that each callback also receives the bounding volume of its
+/// node.
+pub trait VoxelOctreeVisitor<T, Real: Float, Idx: ArrayIndex> {
+    /// Called on reaching a branch, before any of its children.
+    #[inline]
+    fn enter_branch(
+        &mut self,
+        _index: Idx,
+        _point: NodePoint<Idx>,
+        _proxy: Proxy<Idx>,
+        _aabb: &Aabb<Real>,
+    ) -> VisitControl {
+        VisitControl::Descend
+    }
+
+    /// Called after visiting every child of a branch, if [`Self::enter_branch`] returned
+    /// [`Descend`](VisitControl::Descend).
+    #[inline]
+    fn exit_branch(
+        &mut self,
+        _index: Idx,
+        _point: NodePoint<Idx>,
+        _proxy: Proxy<Idx>,
+        _aabb: &Aabb<Real>,
+    ) {
+    }
+
+    /// Called on reaching a leaf, with its data.
+    #[inline]
+    fn visit_leaf(
+        &mut self,
+        _index: Idx,
+        _point: NodePoint<Idx>,
+        _proxy: Proxy<Idx>,
+        _aabb: &Aabb<Real>,
+        _data: &T,
+    ) {
+    }
+
+    /// Called on reaching a void.
+    #[inline]
+    fn visit_void(
+        &mut self,
+        _index: Idx,
+        _point: NodePoint<Idx>,
+        _proxy: Proxy<Idx>,
+        _aabb: &Aabb<Real>,
+    ) {
+    }
+}
+
+/// Adapts a [`VoxelOctreeVisitor`] to an [`OctreeVisitor`] by tracking the bounding volumes of
+/// the branches being traversed.
+struct WithAabb<'v, V: ?Sized, Real: Float> {
+    inner: &'v mut V,
+    root: Aabb<Real>,
+    /// Bounding volumes of the branches entered so far, by depth.
+    branches: Vec<Aabb<Real>>,
+}
+
+impl<'v, V: ?Sized, Real: Float> WithAabb<'v, V, Real> {
+    fn aabb_of<Idx: ArrayIndex>(&self, point: &NodePoint<Idx>) -> Aabb<Real> {
+        match self.branches.last() {
+            // every node other than the root is a child of the last branch entered
+            Some(parent) if point.0.w != Idx::ZERO => parent.child(Octant::new(
+                point.0.x & Idx::ONE == Idx::ONE,
+                point.0.y & Idx::ONE == Idx::ONE,
+                point.0.z & Idx::ONE == Idx::ONE,
+            )),
+            _ => self.root,
+        }
+    }
+}
+
+impl<'v, T, Real: Float, Idx: ArrayIndex, V: VoxelOctreeVisitor<T, Real, Idx> + ?Sized>
+    OctreeVisitor<T, Idx> for WithAabb<'v, V, Real>
+{
+    fn enter_branch(
+        &mut self,
+        index: Idx,
+        point: NodePoint<Idx>,
+        proxy: Proxy<Idx>,
+    ) -> VisitControl {
+        let aabb = self.aabb_of(&point);
+        let res = self.inner.enter_branch(index, point, proxy, &aabb);
+        if res == VisitControl::Descend {
+            self.branches.push(aabb);
+        }
+        res
+    }
+
+    fn exit_branch(&mut self, index: Idx, point: NodePoint<Idx>, proxy: Proxy<Idx>) {
+        if let Some(aabb) = self.branches.pop() {
+            self.inner.exit_branch(index, point, proxy, &aabb);
+        }
+    }
+
+    fn visit_leaf(&mut self, index: Idx, point: NodePoint<Idx>, proxy: Proxy<Idx>, data: &T) {
+        let aabb = self.aabb_of(&point);
+        self.inner.visit_leaf(index, point, proxy, &aabb, data);
+    }
+
+    fn visit_void(&mut self, index: Idx, point: NodePoint<Idx>, proxy: Proxy<Idx>) {
+        let aabb = self.aabb_of(&point);
+        self.inner.visit_void(index, point, proxy, &aabb);
+    }
+}
+
+impl<T, Real: Float, Idx: ArrayIndex, S: OctreeStorage> VoxelOctree<T, Real, Idx, S> {
+    /// Traverse every node depth-first, by [Octant] ordering, calling the matching callback of
+    /// `visitor` with the bounding volume of each, and return whether the traversal ran to
+    /// completion.
+    ///
+    /// See [`Octree::visit`](crate::Octree::visit).
+    pub fn visit<V: VoxelOctreeVisitor<T, Real, Idx> + ?Sized>(&self, visitor: &mut V) -> bool
+    where
+        u8: AsPrimitive<Idx>,
+    {
+        self.base.visit(&mut WithAabb {
+            inner: visitor,
+            root: self.aabb,
+            branches: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector, Point3};
+
+    use super::VoxelOctreeVisitor;
+    use crate::{
+        spatial::{Aabb, VoxelOctree},
+        NodePoint, Proxy, VisitControl,
+    };
+
+    /// Collects the leafs intersecting a region, skipping branches outside of it.
+    struct Query {
+        region: Aabb<f32>,
+        branches: usize,
+        leafs: Vec<Point3<f32>>,
+    }
+
+    fn intersects(a: &Aabb<f32>, b: &Aabb<f32>) -> bool {
+        (0..3).all(|i| a.mins[i] < b.maxs[i] && b.mins[i] < a.maxs[i])
+    }
+
+    impl VoxelOctreeVisitor<u8, f32, u32> for Query {
+        fn enter_branch(
+            &mut self,
+            _: u32,
+            _: NodePoint<u32>,
+            _: Proxy<u32>,
+            aabb: &Aabb<f32>,
+        ) -> VisitControl {
+            self.branches += 1;
+            if intersects(aabb, &self.region) {
+                VisitControl::Descend
+            } else {
+                VisitControl::Skip
+            }
+        }
+
+        fn visit_leaf(
+            &mut self,
+            _: u32,
+            _: NodePoint<u32>,
+            _: Proxy<u32>,
+            aabb: &Aabb<f32>,
+            _: &u8,
+        ) {
+            if intersects(aabb, &self.region) {
+                self.leafs.push(aabb.center());
+            }
+        }
+    }
+
+    #[test]
+    fn visit() {
+        let points = [
+            point![0.5, 0.5, 0.5],
+            point![1.5, 0.5, 0.5],
+            point![-2.5, 3.5, 0.5],
+            point![-3.5, 2.5, 1.5],
+        ];
+        let tree = VoxelOctree::<u8, f32, u32>::from_points(
+            points.iter().map(|p| (*p, 1)),
+            vector![1.0, 1.0, 1.0],
+            |a, _| a,
+        )
+        .unwrap();
+
+        let mut all = Query {
+            region: *tree.aabb(),
+            branches: 0,
+            leafs: Vec::new(),
+        };
+        assert!(tree.visit(&mut all));
+        assert_eq!(all.leafs.len(), points.len());
+        for p in points {
+            assert!(all.leafs.contains(&p));
+        }
+
+        let mut query = Query {
+            region: Aabb::new(point![0.0, 0.0, 0.0], point![2.0, 1.0, 1.0]),
+            branches: 0,
+            leafs: Vec::new(),
+        };
+        assert!(tree.visit(&mut query));
+        assert_eq!(query.leafs, [points[0], points[1]]);
+        assert!(query.branches < all.branches);
+    }
+}
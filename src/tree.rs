@@ -1,70 +1,148 @@
+mod augmented;
+mod boolean;
+mod bulk;
+mod checked;
+mod collapse;
+mod cursor;
+mod dag;
+mod diff;
+mod dynamic;
+mod entry;
 mod error;
+pub(crate) mod format;
 mod iter;
+mod linear;
+mod mapped;
 mod merge;
+mod neighbor;
 mod node;
+#[cfg(feature = "rayon")]
+mod par;
+mod persistent;
 mod proxy;
+mod prune;
 mod sample;
+#[cfg(feature = "serde")]
+mod ser;
 mod slice;
+mod stats;
+mod storage;
+mod validate;
+mod visit;
 
 mod debug;
 
 use std::{
-    collections::HashMap,
     convert::TryInto,
-    ops::{Index, Range, ShlAssign, ShrAssign},
+    ops::{Index, ShlAssign, ShrAssign},
 };
 
+pub use augmented::*;
+pub use cursor::*;
+pub use dag::*;
+pub use diff::*;
+pub use dynamic::*;
 use eightfold_common::ArrayIndex;
+pub use entry::*;
 pub use error::*;
+pub use format::*;
 pub use iter::*;
+pub use linear::*;
+pub use mapped::*;
 pub use merge::*;
 pub use node::*;
 use num_traits::AsPrimitive;
+#[cfg(feature = "rayon")]
+pub use par::*;
+pub use persistent::*;
 pub use proxy::*;
 pub use sample::*;
 use simba::scalar::ClosedMul;
 pub use slice::*;
+pub use stats::*;
+pub use storage::*;
 #[cfg(feature = "tracing")]
 use tracing::instrument;
+pub use visit::*;
 
-use crate::{stablevec::StableVec, NodePoint, Octant, VoxelPoint};
-
-use stablevec::stablevec;
+use crate::{NodePoint, Octant, VoxelPoint};
 
 /// A data structure for partitioning data in a 3D space.
-#[derive(Debug)]
-pub struct Octree<T, Idx: ArrayIndex> {
-    proxies: StableVec<Proxy<Idx>>,
+///
+/// Nodes, branches & leaf data are kept in [Arenas](Arena) chosen by `S`; see [`OctreeStorage`].
+///
+/// With the `serde` feature, an [Octree] serializes its internal storage as-is, including any
+/// vacant entries; call [`Self::compress`] first for a more compact encoding. Deserialization
+/// rejects input which doesn't describe a well-formed tree.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Idx: serde::Serialize, S::Arena<Proxy<Idx>>: serde::Serialize, \
+        S::Arena<[Idx; 8]>: serde::Serialize, S::Arena<T>: serde::Serialize"
+    ))
+)]
+pub struct Octree<T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    proxies: S::Arena<Proxy<Idx>>,
     /// Store for indices of the children of branches
-    branch_data: StableVec<[Idx; 8]>,
-    leaf_data: StableVec<T>,
+    branch_data: S::Arena<[Idx; 8]>,
+    leaf_data: S::Arena<T>,
     root: Idx,
+    /// Whether removals prune newly void-only branches; see [`Self::set_auto_prune`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    auto_prune: bool,
+    /// Leaf equality used to collapse uniform branches on insertion; see
+    /// [`Self::set_collapse_on_insert`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    collapse_eq: Option<fn(&T, &T) -> bool>,
 }
 
-impl<T, Idx: ArrayIndex> Index<Idx> for Octree<T, Idx> {
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Index<Idx> for Octree<T, Idx, S> {
     type Output = Proxy<Idx>;
     fn index(&self, i: Idx) -> &Self::Output {
         &self.proxies[i.as_()]
     }
 }
 
-impl<T, Idx: ArrayIndex> Default for Octree<T, Idx> {
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Default for Octree<T, Idx, S> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T, Idx: ArrayIndex> Octree<T, Idx> {
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Clone for Octree<T, Idx, S>
+where
+    S::Arena<Proxy<Idx>>: Clone,
+    S::Arena<[Idx; 8]>: Clone,
+    S::Arena<T>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            proxies: self.proxies.clone(),
+            branch_data: self.branch_data.clone(),
+            leaf_data: self.leaf_data.clone(),
+            root: self.root,
+            auto_prune: self.auto_prune,
+            collapse_eq: self.collapse_eq,
+        }
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
     /// Construct a new tree with a void root.
     pub fn new() -> Self {
+        let mut proxies = S::Arena::<Proxy<Idx>>::default();
+        proxies.push(Proxy {
+            parent: Idx::ZERO,
+            data: ProxyData::Void,
+        });
         Self {
-            proxies: stablevec![Proxy {
-                parent: Idx::ZERO,
-                data: ProxyData::Void,
-            }],
-            branch_data: StableVec::default(),
-            leaf_data: StableVec::default(),
+            proxies,
+            branch_data: Default::default(),
+            leaf_data: Default::default(),
             root: Idx::ZERO,
+            auto_prune: false,
+            collapse_eq: None,
         }
     }
 
@@ -98,22 +176,29 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
     /// children are returned.
     ///
     /// The children of a branch are always stored and given in [Octant] order.
+    ///
+    /// # Errors
+    ///
+    /// * [`CannotSplitLeaf`](Error::CannotSplitLeaf) if the target voxel is a leaf.
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the new nodes wouldn't be addressable by
+    ///   `Idx`; see [`Self::try_reserve`].
     pub fn split(&mut self, target: Idx) -> Result<(&[Idx; 8], Proxy<Idx>), Error<Idx>>
     where
         usize: AsPrimitive<Idx>,
-        Range<Idx>: Iterator,
     {
         let prox = self.proxies[target.as_()];
         match prox.data {
             ProxyData::Branch(children) => Ok((&self.branch_data[children.as_()], prox)),
             ProxyData::Leaf(_) => Err(Error::CannotSplitLeaf),
             ProxyData::Void => {
+                self.try_reserve(8, 1, 0)?;
                 let children: [Idx; 8] = self
                     .proxies
-                    .push_iter((0..8).map(|_| Proxy {
+                    .extend_from_iter((0..8).map(|_| Proxy {
                         parent: target,
                         data: ProxyData::Void,
                     }))
+                    .into_iter()
                     .map(AsPrimitive::<Idx>::as_)
                     .collect::<Vec<_>>()
                     .as_slice()
@@ -129,7 +214,6 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         }
     }
 
-    #[allow(unsafe_code)]
     fn flatten_branch(
         &mut self,
         target: Idx,
@@ -139,22 +223,17 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         self.proxies[target.as_()].data = new_data;
 
         let mut res = Vec::with_capacity(8);
-        let mut to_remove = unsafe {
-            self.branch_data
-                .remove_unchecked(children_idx.as_())
-                .unwrap()
-                .to_vec()
-        };
+        let mut to_remove = self
+            .branch_data
+            .remove(children_idx.as_())
+            .unwrap()
+            .to_vec();
         while let Some(c_idx) = to_remove.pop() {
-            match unsafe { self.proxies.remove_unchecked(c_idx.as_()).unwrap().data } {
+            match self.proxies.remove(c_idx.as_()).unwrap().data {
                 ProxyData::Void => {}
-                ProxyData::Leaf(t) => {
-                    res.push(unsafe { self.leaf_data.remove_unchecked(t.as_()).unwrap() })
-                }
+                ProxyData::Leaf(t) => res.push(self.leaf_data.remove(t.as_()).unwrap()),
                 ProxyData::Branch(c_idx) => {
-                    to_remove.extend_from_slice(unsafe {
-                        &self.branch_data.remove_unchecked(c_idx.as_()).unwrap()
-                    });
+                    to_remove.extend_from_slice(&self.branch_data.remove(c_idx.as_()).unwrap());
                 }
             }
         }
@@ -165,27 +244,35 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
     /// Clear a voxel and clean up any data it represented. Leaf data is returned as a Vec, if any
     /// is removed.
     ///
-    /// If the voxel is a branch, the branch's children are voided as well.
+    /// If the voxel is a branch, the branch's children are voided as well. With
+    /// [auto-pruning](Self::set_auto_prune) enabled, any ancestors left with only void children
+    /// are pruned afterwards.
     pub fn remove(&mut self, target: Idx) -> Vec<T> {
-        match self.proxies[target.as_()].data {
-            ProxyData::Void => Vec::with_capacity(0),
+        let res = match self.proxies[target.as_()].data {
+            ProxyData::Void => return Vec::with_capacity(0),
             ProxyData::Leaf(l) => {
                 self.proxies[target.as_()].data = ProxyData::Void;
                 vec![self.leaf_data.remove(l.as_()).unwrap()]
             }
             ProxyData::Branch(c) => self.flatten_branch(target, c, ProxyData::Void),
+        };
+        if self.auto_prune {
+            self.prune_ancestors(target);
         }
+        res
     }
 
     /// Set the leaf data of a voxel and, if extant, return its previous leaf data.
     ///
-    /// If the voxel is a branch, the branch is voided first.
+    /// If the voxel is a branch, the branch is voided first. With
+    /// [collapse-on-insert](Self::set_collapse_on_insert) enabled, any ancestors left with eight
+    /// equal leaf children are collapsed afterwards.
     pub fn set_leaf(&mut self, target: Idx, data: T) -> Vec<T>
     where
         usize: AsPrimitive<Idx>,
     {
-        match self.proxies[target.as_()].data {
-            ProxyData::Leaf(l) => vec![self.leaf_data.set(l.as_(), data).unwrap()],
+        let res = match self.proxies[target.as_()].data {
+            ProxyData::Leaf(l) => vec![std::mem::replace(&mut self.leaf_data[l.as_()], data)],
             ProxyData::Void => {
                 self.proxies[target.as_()].data = ProxyData::Leaf(self.leaf_data.push(data).as_());
                 Vec::with_capacity(0)
@@ -195,7 +282,9 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
                 self.proxies[target.as_()].data = ProxyData::Leaf(self.leaf_data.push(data).as_());
                 res
             }
-        }
+        };
+        self.collapse_ancestors(target);
+        res
     }
 
     /// Grow a tree by adding a parent branch to the old root, and return the index of the new root.
@@ -208,50 +297,57 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
 
         self.proxies.reserve(8);
 
+        let new_root: Idx = self
+            .proxies
+            .push(Proxy {
+                parent: old_root,
+                data: ProxyData::Void,
+            })
+            .as_();
+        self.proxies[new_root.as_()].parent = new_root;
+
         let mut children: Vec<Idx> = self
             .proxies
-            .push_iter(
+            .extend_from_iter(
                 std::iter::repeat(Proxy {
-                    parent: self.root,
+                    parent: new_root,
                     data: ProxyData::Void,
                 })
                 .take(7),
             )
+            .into_iter()
             .map(usize::as_)
             .collect::<Vec<Idx>>();
         children.insert(usize::from(oct), old_root);
 
-        self.root = self
-            .proxies
-            .push(Proxy {
-                parent: self.root,
-                data: ProxyData::Branch(
-                    self.branch_data
-                        .push(children.as_slice().try_into().unwrap())
-                        .as_(),
-                ),
-            })
-            .as_();
+        self.proxies[new_root.as_()].data = ProxyData::Branch(
+            self.branch_data
+                .push(children.as_slice().try_into().unwrap())
+                .as_(),
+        );
+        self.root = new_root;
         self.proxies[old_root.as_()].parent = self.root;
 
         self.root
     }
 
-    /// Code shared by [`voxel_at`] and [`voxel_at_unchecked`]
+    /// Descend from the root towards the node at depth `depth` whose coordinates within the
+    /// `2ᵈᵉᵖᵗʰ` grid are `p`, stopping at the deepest existing node.
     #[inline]
-    fn internal_voxel_at(&self, p: &VoxelPoint<Idx>, size: Idx) -> Idx
-    where
-        Idx: ShrAssign<Idx> + PartialOrd,
-    {
-        let mut idx: Idx = Idx::ZERO;
-        let mut vox = self.proxies[self.root.as_()];
-        let mut s2 = size >> Idx::ONE; // size / 2 // half of the cube occupied by vox
-        while let ProxyData::Branch(ch_idx) = vox.data {
-            let children = &self.branch_data[ch_idx.as_()];
-            let oct = Octant::new(p.x > s2, p.y > s2, p.z > s2);
-            idx = children[oct.0 as usize];
-            vox = self.proxies[idx.as_()];
-            s2 >>= Idx::ONE; // s2 /= 2
+    fn internal_node_at(&self, p: &VoxelPoint<Idx>, mut depth: Idx) -> Idx {
+        let mut idx = self.root;
+        while let ProxyData::Branch(ch_idx) = self.proxies[idx.as_()].data {
+            if depth == Idx::ZERO {
+                break;
+            }
+            depth -= Idx::ONE;
+            // the octant at each level is given by the corresponding bit of each coordinate
+            let oct = Octant::new(
+                (p.x >> depth) & Idx::ONE == Idx::ONE,
+                (p.y >> depth) & Idx::ONE == Idx::ONE,
+                (p.z >> depth) & Idx::ONE == Idx::ONE,
+            );
+            idx = self.branch_data[ch_idx.as_()][usize::from(oct)];
         }
         idx
     }
@@ -267,7 +363,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
     where
         Idx: ShrAssign<Idx> + PartialOrd,
     {
-        self.internal_voxel_at(p, self.grid_size())
+        self.internal_node_at(p, self.height())
     }
 
     /// Get the index of the deepest voxel containing a specific [`VoxelPoint`].
@@ -281,11 +377,12 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
     where
         Idx: ShrAssign<Idx> + PartialOrd,
     {
-        let size = self.grid_size();
+        let height = self.height();
+        let size = Idx::ONE << height;
         if p.x >= size || p.y >= size || p.z >= size {
             return Err(Error::VoxelOutOfGrid(size, *p));
         }
-        Ok(self.internal_voxel_at(p, size))
+        Ok(self.internal_node_at(p, height))
     }
 
     /// Get the index of the deepest voxel encompassing a specific [`NodePoint`].
@@ -293,28 +390,11 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
     where
         Idx: ShlAssign<Idx> + ClosedMul + ShrAssign<Idx>,
     {
-        let mut idx = Idx::ZERO;
-        let mut vox = &self.proxies[self.root.as_()];
-        let ps = Idx::ONE << p.0.w; // 2ʷ // grid size at depth of `p`
-        let mut s2 = ps >> Idx::ONE; // ps / 2 // 1/2 the size of the `p`-grid space occupied by vox
-        let psp = VoxelPoint::new(p.0.x * ps, p.0.y * ps, p.0.z * ps); // voxelpoint of target in
-                                                                       // grid sized to depth of `p`
-        while let ProxyData::Branch(ch_idx) = &vox.data {
-            if s2 == Idx::ZERO {
-                break;
-            };
-            let children = &self.branch_data[ch_idx.as_()];
-            let oct = Octant::new(psp.x > s2, psp.y > s2, psp.z > s2);
-            idx = children[oct.0 as usize];
-            vox = &self.proxies[idx.as_()];
-            s2 >>= Idx::ONE; // s2 /= 2
-        }
-
-        idx
+        self.internal_node_at(&p.0.xyz(), p.0.w)
     }
 
     /// Convert this tree to one with a wider index type.
-    pub fn upcast<NIdx: ArrayIndex>(mut self) -> Octree<T, NIdx>
+    pub fn upcast<NIdx: ArrayIndex>(mut self) -> Octree<T, NIdx, S>
     where
         usize: AsPrimitive<Idx>,
         Idx: AsPrimitive<NIdx>,
@@ -334,14 +414,18 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
                         ProxyData::Branch(b_idx) => ProxyData::Branch(b_idx.as_()),
                     },
                 })
-                .collect(),
+                .collect::<Vec<_>>()
+                .into(),
             branch_data: self
                 .branch_data
                 .iter()
                 .map(|b| b.map(|c| c.as_()))
-                .collect(),
+                .collect::<Vec<_>>()
+                .into(),
             leaf_data: self.leaf_data,
             root: self.root.as_(),
+            auto_prune: self.auto_prune,
+            collapse_eq: self.collapse_eq,
         }
     }
 
@@ -350,10 +434,10 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
     where
         usize: AsPrimitive<Idx>,
     {
-        let p_swaps = HashMap::<usize, usize>::from_iter(self.proxies.defragment());
-        let l_swaps = HashMap::<usize, usize>::from_iter(self.leaf_data.defragment());
-        let b_swaps = HashMap::<usize, usize>::from_iter(self.branch_data.defragment());
-        for p in self.proxies.as_slice_mut().unwrap().iter_mut() {
+        let p_swaps = self.proxies.defragment();
+        let l_swaps = self.leaf_data.defragment();
+        let b_swaps = self.branch_data.defragment();
+        for p in self.proxies.iter_mut() {
             if let Some(&parent) = p_swaps.get(&p.parent.as_()) {
                 p.parent = parent.as_();
             }
@@ -396,6 +480,21 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         self.leaf_data.iter()
     }
 
+    /// Iterate mutably through all leaf data, in storage order.
+    pub fn leaf_unordered_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.leaf_data.iter_mut()
+    }
+
+    /// Depth-first iterator through mutable references to all leafs, by [Octant] ordering.
+    pub fn leaf_dfi_mut(&mut self) -> LeafIterMut<'_, T, Idx, S> {
+        let root = self.root;
+        LeafIterMut::new(
+            self,
+            root,
+            NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
+        )
+    }
+
     /// Calculate the [`NodePoint`] of a specific node.
     ///
     /// # Panics
@@ -406,26 +505,13 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         let mut y = Idx::ZERO;
         let mut z = Idx::ZERO;
         let mut d = Idx::ZERO;
-        let mut p = self.proxies[index.as_()];
-        while p.parent != index {
+        // walking upwards, each octant gives the next most significant bit of each coordinate
+        while let Some(oct) = self.octant_of_unchecked(index) {
+            x += <Idx as From<u8>>::from(oct.i() >> 2) << d;
+            y += <Idx as From<u8>>::from(oct.j() >> 1) << d;
+            z += <Idx as From<u8>>::from(oct.k()) << d;
             d += Idx::ONE;
-            match self.proxies[p.parent.as_()].data {
-                ProxyData::Branch(b_idx) => {
-                    let oct = Octant(
-                        self.branch_data[b_idx.as_()]
-                            .into_iter()
-                            .find(|&c| c == index)
-                            .unwrap()
-                            .as_(),
-                    );
-                    x += oct.i().into();
-                    y += oct.j().into();
-                    z += oct.k().into();
-                }
-                _ => unreachable!(),
-            }
-            index = p.parent;
-            p = self.proxies[index.as_()];
+            index = self.proxies[index.as_()].parent;
         }
         NodePoint::new(x, y, z, d)
     }
@@ -443,13 +529,12 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
     /// # Panics
     ///
     /// * `node` ∉ `self.proxies`
-    #[allow(unsafe_code)]
     pub fn graft_unchecked(&mut self, mut other: Self, node: Idx)
     where
         usize: AsPrimitive<Idx>,
     {
         // remove other.root from other and replace `node` with it
-        let o_root = unsafe { other.proxies.remove_unchecked(other.root.as_()) }.unwrap();
+        let o_root = other.proxies.remove(other.root.as_()).unwrap();
         self.proxies[node.as_()].data = o_root.data;
 
         // insert all the new data into self, collecting swap index information
@@ -477,9 +562,9 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
                     // update child indices and add them to the update queue
                     let c_idx = b_swaps[&b_idx.as_()];
                     for c in &mut self.branch_data[c_idx] {
-                        let ci = c.as_();
+                        let ci = p_swaps[&c.as_()];
                         // update child index in children
-                        *c = p_swaps[&ci].as_();
+                        *c = ci.as_();
                         // add child to the update queue
                         node_stack.push((ci, self.proxies[ci]));
                     }
@@ -505,7 +590,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
                 .get(node.as_())
                 .ok_or(Error::InvalidIndex(node))?
                 .data,
-            ProxyData::Branch(_)
+            ProxyData::Void
         ) {
             return Err(Error::NotAVoid(node));
         }
@@ -0,0 +1,427 @@
+use std::ops::{Deref, Range};
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{
+    Arena, Error, LeafMerge, LeafSample, Node, Octree, OctreeStorage, Proxy, ProxyData,
+    StableStorage,
+};
+
+/// An aggregate of the leafs within a subtree, cached for every node of an [`AugmentedOctree`].
+///
+/// Examples include leaf counts, the bounds of occupied voxels, sums or maxima of a value, and
+/// downsampled colors.
+pub trait Summary<T>: Sized {
+    /// The summary of a single leaf.
+    fn summarize(leaf: &T) -> Self;
+
+    /// Combine the summaries of the children of a branch, by [Octant](crate::Octant).
+    ///
+    /// Children without any leafs, such as voids, are `None`; at least one child is `Some`.
+    fn combine(children: [Option<&Self>; 8]) -> Self;
+}
+
+/// The merged leaf data of a subtree, as given by [`Octree::sample_branch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample<T>(pub T);
+
+impl<T: LeafSample + Clone> Summary<T> for Sample<T> {
+    #[inline]
+    fn summarize(leaf: &T) -> Self {
+        Self(leaf.clone())
+    }
+
+    fn combine(children: [Option<&Self>; 8]) -> Self {
+        let mut children = children.into_iter().flatten();
+        let first = children
+            .next()
+            .expect("branches are only summarized if they have leafs")
+            .0
+            .clone();
+        Self(children.fold(first, |res, c| T::leaf_sample(&res, &c.0)))
+    }
+}
+
+/// A [Node] of an [`AugmentedOctree`], which also gives the [Summary] of its subtree.
+///
+/// Dereferences to the underlying [Node].
+#[derive(Debug)]
+pub struct AugmentedNode<'tree, T, S, Idx: ArrayIndex, St: OctreeStorage = StableStorage> {
+    tree: &'tree AugmentedOctree<T, S, Idx, St>,
+    node: Node<'tree, T, Idx, St>,
+}
+
+impl<'tree, T, S: Summary<T>, Idx: ArrayIndex, St: OctreeStorage>
+    AugmentedNode<'tree, T, S, Idx, St>
+{
+    /// The summary of the subtree rooted at this node, or `None` if it has no leafs.
+    #[inline]
+    pub fn summary(&self) -> Option<&'tree S> {
+        self.tree.summary(self.node.index())
+    }
+
+    /// Get the parent of this node, unless it's the root.
+    pub fn parent(&self) -> Option<Self> {
+        self.node.parent().and_then(|p| self.tree.node(p.index()))
+    }
+}
+
+impl<'tree, T, S, Idx: ArrayIndex, St: OctreeStorage> Deref
+    for AugmentedNode<'tree, T, S, Idx, St>
+{
+    type Target = Node<'tree, T, Idx, St>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.node
+    }
+}
+
+/// An [Octree] caching a [Summary] of every subtree, such that aggregate queries over a subtree
+/// take *O(1)* rather than a full traversal.
+///
+/// Summaries are updated along the path to each changed node, in *O(depth)*.
+/// [Auto-pruning](Octree::set_auto_prune) & [collapse-on-insert](Octree::set_collapse_on_insert)
+/// aren't supported, and are disabled for trees converted through [`From`]. Like [Octree], `St`
+/// determines how its nodes are stored.
+#[derive(Debug)]
+pub struct AugmentedOctree<T, S, Idx: ArrayIndex, St: OctreeStorage = StableStorage> {
+    base: Octree<T, Idx, St>,
+    /// The summary of each node with leafs, by proxy index.
+    summaries: Vec<Option<S>>,
+}
+
+impl<T, S: Summary<T>, Idx: ArrayIndex, St: OctreeStorage> Default
+    for AugmentedOctree<T, S, Idx, St>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, S: Summary<T>, Idx: ArrayIndex, St: OctreeStorage> From<Octree<T, Idx, St>>
+    for AugmentedOctree<T, S, Idx, St>
+{
+    fn from(mut base: Octree<T, Idx, St>) -> Self {
+        base.auto_prune = false;
+        base.collapse_eq = None;
+        let root = base.root;
+        let mut res = Self {
+            base,
+            summaries: Vec::new(),
+        };
+        res.summarize_subtree(root);
+        res
+    }
+}
+
+impl<T, S: Summary<T>, Idx: ArrayIndex, St: OctreeStorage> AugmentedOctree<T, S, Idx, St> {
+    /// Construct a new tree with a void root.
+    pub fn new() -> Self {
+        Self {
+            base: Octree::new(),
+            summaries: Vec::new(),
+        }
+    }
+
+    /// The underlying [Octree].
+    #[inline]
+    pub fn base(&self) -> &Octree<T, Idx, St> {
+        &self.base
+    }
+
+    /// Discard the cached summaries, and return the underlying [Octree].
+    #[inline]
+    pub fn into_inner(self) -> Octree<T, Idx, St> {
+        self.base
+    }
+
+    /// The summary of the subtree rooted at `index`, or `None` if it has no leafs.
+    #[inline]
+    pub fn summary(&self, index: Idx) -> Option<&S> {
+        self.summaries
+            .get(AsPrimitive::<usize>::as_(index))
+            .and_then(Option::as_ref)
+    }
+
+    /// The summary of the whole tree, or `None` if it has no leafs.
+    #[inline]
+    pub fn root_summary(&self) -> Option<&S> {
+        self.summary(self.base.root)
+    }
+
+    /// Get the node at a specific index, with access to its summary.
+    #[inline]
+    pub fn node(&self, index: Idx) -> Option<AugmentedNode<'_, T, S, Idx, St>> {
+        self.base
+            .node(index)
+            .map(|node| AugmentedNode { tree: self, node })
+    }
+
+    /// Compute the summary of a node from its leaf data or the cached summaries of its children.
+    fn compute(&self, index: Idx) -> Option<S> {
+        match self.base.proxies[index.as_()].data {
+            ProxyData::Void => None,
+            ProxyData::Leaf(l_idx) => Some(S::summarize(&self.base.leaf_data[l_idx.as_()])),
+            ProxyData::Branch(b_idx) => {
+                let children = self.base.branch_data[b_idx.as_()].map(|c| self.summary(c));
+                children
+                    .iter()
+                    .any(Option::is_some)
+                    .then(|| S::combine(children))
+            }
+        }
+    }
+
+    fn set_summary(&mut self, index: Idx, summary: Option<S>) {
+        let index: usize = index.as_();
+        if index >= self.summaries.len() {
+            if summary.is_none() {
+                return;
+            }
+            self.summaries.resize_with(index + 1, || None);
+        }
+        self.summaries[index] = summary;
+    }
+
+    /// Forget the summaries of a node & its descendants, before they're removed.
+    fn clear_subtree(&mut self, index: Idx) {
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            self.set_summary(index, None);
+            if let ProxyData::Branch(b_idx) = self.base.proxies[index.as_()].data {
+                stack.extend(self.base.branch_data[b_idx.as_()]);
+            }
+        }
+    }
+
+    /// Summarize a node & its descendants, bottom-up.
+    fn summarize_subtree(&mut self, index: Idx) {
+        if let ProxyData::Branch(b_idx) = self.base.proxies[index.as_()].data {
+            for c in self.base.branch_data[b_idx.as_()] {
+                self.summarize_subtree(c);
+            }
+        }
+        let summary = self.compute(index);
+        self.set_summary(index, summary);
+    }
+
+    /// Recompute the summaries of a node & each of its ancestors.
+    fn update_path(&mut self, mut index: Idx) {
+        loop {
+            let summary = self.compute(index);
+            self.set_summary(index, summary);
+            let parent = self.base.proxies[index.as_()].parent;
+            if parent == index {
+                break;
+            }
+            index = parent;
+        }
+    }
+
+    /// See [`Octree::split`]; a new branch has no leafs, so no summaries change.
+    #[inline]
+    pub fn split(&mut self, target: Idx) -> Result<(&[Idx; 8], Proxy<Idx>), Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        self.base.split(target)
+    }
+
+    /// See [`Octree::set_leaf`].
+    pub fn set_leaf(&mut self, target: Idx, data: T) -> Vec<T>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        self.clear_subtree(target);
+        let res = self.base.set_leaf(target, data);
+        self.update_path(target);
+        res
+    }
+
+    /// See [`Octree::remove`].
+    pub fn remove(&mut self, target: Idx) -> Vec<T> {
+        self.clear_subtree(target);
+        let res = self.base.remove(target);
+        self.update_path(target);
+        res
+    }
+
+    /// See [`Octree::graft`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Octree::graft`].
+    pub fn graft(&mut self, other: Octree<T, Idx, St>, node: Idx) -> Result<(), Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        self.base.graft(other, node)?;
+        self.summarize_subtree(node);
+        self.update_path(node);
+        Ok(())
+    }
+
+    /// See [`Octree::merge_branch`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Octree::merge_branch`].
+    pub fn merge_branch(&mut self, branch_idx: Idx) -> Result<&T, Error<Idx>>
+    where
+        T: LeafMerge,
+        usize: AsPrimitive<Idx>,
+    {
+        if !self.base.proxies.is_init(branch_idx.as_()) {
+            return Err(Error::InvalidIndex(branch_idx));
+        }
+        self.clear_subtree(branch_idx);
+        let res = self.base.merge_branch(branch_idx).map(|_| ());
+        self.update_path(branch_idx);
+        res?;
+        match self.base.proxies[branch_idx.as_()].data {
+            ProxyData::Leaf(l_idx) => Ok(&self.base.leaf_data[l_idx.as_()]),
+            _ => Err(Error::NotALeaf(branch_idx)),
+        }
+    }
+}
+
+impl<T: LeafSample + Clone, Idx: ArrayIndex, St: OctreeStorage>
+    AugmentedOctree<T, Sample<T>, Idx, St>
+{
+    /// Return the merged leaf data of the descendants of a specific branch, from the cache.
+    ///
+    /// See [`Octree::sample_branch`].
+    ///
+    /// # Errors
+    ///
+    /// * [`InvalidIndex`](Error::InvalidIndex) if `branch_idx` isn't the index of a node.
+    /// * [`NotABranch`](Error::NotABranch) if the node isn't a branch.
+    /// * [`NoLeafs`](Error::NoLeafs) if the branch has no leaf descendants.
+    pub fn sample_branch(&self, branch_idx: Idx) -> Result<&T, Error<Idx>> {
+        match self
+            .base
+            .proxies
+            .get(branch_idx.as_())
+            .ok_or(Error::InvalidIndex(branch_idx))?
+            .data
+        {
+            ProxyData::Branch(_) => self
+                .summary(branch_idx)
+                .map(|s| &s.0)
+                .ok_or(Error::NoLeafs(branch_idx)),
+            _ => Err(Error::NotABranch(branch_idx)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AugmentedOctree, Sample, Summary};
+    use crate::{LeafMerge, LeafSample, Octree};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Max(u32);
+
+    impl LeafSample for Max {
+        fn leaf_sample(a: &Self, b: &Self) -> Self {
+            Self(a.0.max(b.0))
+        }
+    }
+
+    impl LeafMerge for Max {
+        fn leaf_merge(a: Self, b: Self) -> Self {
+            Self(a.0 + b.0)
+        }
+    }
+
+    /// The number of leafs in a subtree.
+    #[derive(Debug, PartialEq)]
+    struct Count(usize);
+
+    impl<T> Summary<T> for Count {
+        fn summarize(_: &T) -> Self {
+            Self(1)
+        }
+
+        fn combine(children: [Option<&Self>; 8]) -> Self {
+            Self(children.into_iter().flatten().map(|c| c.0).sum())
+        }
+    }
+
+    fn check(tree: &AugmentedOctree<Max, Sample<Max>, u32>) {
+        for (index, prox) in tree.base().proxies().enumerate() {
+            if prox.is_branch() {
+                assert_eq!(
+                    tree.sample_branch(index as u32).ok(),
+                    tree.base().sample_branch(index as u32).ok().as_ref()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn summaries() {
+        let mut tree = AugmentedOctree::<Max, Count, u32>::new();
+        assert_eq!(tree.root_summary(), None);
+        let root = *tree.split(0).unwrap().0;
+        assert_eq!(tree.root_summary(), None);
+        let inner = *tree.split(root[2]).unwrap().0;
+        for (i, c) in inner.into_iter().enumerate() {
+            tree.set_leaf(c, Max(i as u32));
+        }
+        tree.set_leaf(root[5], Max(10));
+        assert_eq!(tree.root_summary(), Some(&Count(9)));
+        let node = tree.node(inner[4]).unwrap();
+        assert_eq!(node.index(), inner[4]);
+        assert_eq!(node.summary(), Some(&Count(1)));
+        assert_eq!(node.parent().unwrap().summary(), Some(&Count(8)));
+        assert_eq!(
+            node.parent().unwrap().parent().unwrap().summary(),
+            Some(&Count(9))
+        );
+
+        tree.remove(inner[3]);
+        assert_eq!(tree.root_summary(), Some(&Count(8)));
+        tree.set_leaf(root[2], Max(1));
+        assert_eq!(tree.root_summary(), Some(&Count(2)));
+        assert!(tree.node(inner[0]).is_none() || tree.summary(inner[0]).is_none());
+
+        let mut other = Octree::new();
+        let children = *other.split(0).unwrap().0;
+        other.set_leaf(children[0], Max(7));
+        other.set_leaf(children[7], Max(8));
+        tree.graft(other, root[6]).unwrap();
+        assert_eq!(tree.summary(root[6]), Some(&Count(2)));
+        assert_eq!(tree.root_summary(), Some(&Count(4)));
+
+        tree.merge_branch(root[6]).unwrap();
+        assert_eq!(tree.root_summary(), Some(&Count(3)));
+
+        let rebuilt = AugmentedOctree::<Max, Count, u32>::from(tree.into_inner());
+        assert_eq!(rebuilt.root_summary(), Some(&Count(3)));
+    }
+
+    #[test]
+    fn sample_branch() {
+        let mut tree = AugmentedOctree::<Max, Sample<Max>, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[1]).unwrap().0;
+        tree.set_leaf(inner[4], Max(3));
+        tree.set_leaf(root[7], Max(5));
+        check(&tree);
+        assert_eq!(tree.sample_branch(0).unwrap(), &Max(5));
+
+        tree.set_leaf(root[7], Max(1));
+        check(&tree);
+        assert_eq!(tree.sample_branch(0).unwrap(), &Max(3));
+        assert!(tree.sample_branch(root[7]).is_err());
+
+        tree.remove(root[1]);
+        tree.remove(root[7]);
+        check(&tree);
+        assert!(tree.sample_branch(0).is_err());
+    }
+}
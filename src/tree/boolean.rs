@@ -0,0 +1,322 @@
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{Error, Octree, OctreeStorage, ProxyData};
+
+/// A boolean set operation between two trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+}
+
+/// A node of one operand, as seen while descending both operands in lockstep.
+#[derive(Debug)]
+pub(super) enum Operand<'tree, T, Idx: ArrayIndex> {
+    Void,
+    Leaf(&'tree T),
+    Branch(&'tree [Idx; 8]),
+}
+
+impl<'tree, T, Idx: ArrayIndex> Clone for Operand<'tree, T, Idx> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'tree, T, Idx: ArrayIndex> Copy for Operand<'tree, T, Idx> {}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    pub(super) fn operand(&self, index: Idx) -> Operand<'_, T, Idx> {
+        match self.proxies[index.as_()].data {
+            ProxyData::Void => Operand::Void,
+            ProxyData::Leaf(l_idx) => Operand::Leaf(&self.leaf_data[l_idx.as_()]),
+            ProxyData::Branch(b_idx) => Operand::Branch(&self.branch_data[b_idx.as_()]),
+        }
+    }
+
+    /// The child of an operand in the [Octant](crate::Octant) `i`; voids & leafs cover their
+    /// whole volume, so they act as their own children.
+    fn operand_child<'tree>(
+        &'tree self,
+        node: Operand<'tree, T, Idx>,
+        i: usize,
+    ) -> Operand<'tree, T, Idx> {
+        match node {
+            Operand::Branch(children) => self.operand(children[i]),
+            _ => node,
+        }
+    }
+
+    /// Copy an operand into the void node `target`.
+    fn copy_operand(
+        &mut self,
+        target: Idx,
+        src: &Self,
+        node: Operand<'_, T, Idx>,
+    ) -> Result<(), Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        match node {
+            Operand::Void => {}
+            Operand::Leaf(data) => {
+                self.try_set_leaf(target, data.clone())?;
+            }
+            Operand::Branch(children) => {
+                let res_children = *self.split(target)?.0;
+                for (r, c) in res_children.into_iter().zip(children) {
+                    self.copy_operand(r, src, src.operand(*c))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the result of `op` between two operands into the void node `target`.
+    fn apply_set_op(
+        &mut self,
+        target: Idx,
+        op: SetOp,
+        (a_tree, a): (&Self, Operand<'_, T, Idx>),
+        (b_tree, b): (&Self, Operand<'_, T, Idx>),
+        combine: &mut impl FnMut(&T, &T) -> T,
+    ) -> Result<(), Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        use Operand::{Branch, Leaf, Void};
+        use SetOp::{Difference, Intersection, SymmetricDifference, Union};
+        match (op, a, b) {
+            // the result is void
+            (Intersection | Difference, Void, _)
+            | (Intersection, _, Void)
+            | (Difference, _, Leaf(_))
+            | (SymmetricDifference, Leaf(_), Leaf(_)) => {}
+            (Union | Difference | SymmetricDifference, _, Void) => {
+                self.copy_operand(target, a_tree, a)?;
+            }
+            (Union | SymmetricDifference, Void, _) => {
+                self.copy_operand(target, b_tree, b)?;
+            }
+            (Union | Intersection, Leaf(a), Leaf(b)) => {
+                self.try_set_leaf(target, combine(a, b))?;
+            }
+            (_, Branch(_), _) | (_, _, Branch(_)) => {
+                let children = *self.split(target)?.0;
+                for (i, c) in children.into_iter().enumerate() {
+                    self.apply_set_op(
+                        c,
+                        op,
+                        (a_tree, a_tree.operand_child(a, i)),
+                        (b_tree, b_tree.operand_child(b, i)),
+                        combine,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_op(
+        &self,
+        other: &Self,
+        op: SetOp,
+        mut combine: impl FnMut(&T, &T) -> T,
+    ) -> Result<Self, Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        let mut res = Self::new();
+        let root = res.root;
+        res.apply_set_op(
+            root,
+            op,
+            (self, self.operand(self.root)),
+            (other, other.operand(other.root)),
+            &mut combine,
+        )?;
+        res.prune();
+        Ok(res)
+    }
+
+    /// The union of the volumes covered by the leafs of `self` & `other`, whose roots are taken
+    /// to cover the same volume.
+    ///
+    /// Where leafs of both trees overlap, the resulting leaf data is given by `combine`; where a
+    /// leaf overlaps a deeper branch, it's copied into each of the branch's descendants. Branches
+    /// with only void children are pruned from the result.
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the result, before pruning, would need more
+    ///   nodes than `Idx` can address.
+    pub fn union(&self, other: &Self, combine: impl FnMut(&T, &T) -> T) -> Result<Self, Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, SetOp::Union, combine)
+    }
+
+    /// The intersection of the volumes covered by the leafs of `self` & `other`.
+    ///
+    /// See [`Self::union`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::union`].
+    pub fn intersection(
+        &self,
+        other: &Self,
+        combine: impl FnMut(&T, &T) -> T,
+    ) -> Result<Self, Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, SetOp::Intersection, combine)
+    }
+
+    /// The volume covered by the leafs of `self`, but not by those of `other`, keeping the leaf
+    /// data of `self`.
+    ///
+    /// See [`Self::union`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::union`].
+    pub fn difference(&self, other: &Self) -> Result<Self, Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, SetOp::Difference, |a, _| a.clone())
+    }
+
+    /// The volume covered by the leafs of exactly one of `self` & `other`, keeping the leaf data
+    /// of that tree.
+    ///
+    /// See [`Self::union`].
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::union`].
+    pub fn symmetric_difference(&self, other: &Self) -> Result<Self, Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        self.set_op(other, SetOp::SymmetricDifference, |a, _| a.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, NodePoint, Octree, OctreeSlice};
+
+    /// `a` has leafs at `root[0]` (1) & `root[7][0]` (2); `b` has leafs at `root[0][3]` (10) &
+    /// `root[7]` (20).
+    fn trees() -> (Octree<u8, u32>, Octree<u8, u32>) {
+        let mut a = Octree::new();
+        let root = *a.split(0).unwrap().0;
+        a.set_leaf(root[0], 1);
+        let inner = *a.split(root[7]).unwrap().0;
+        a.set_leaf(inner[0], 2);
+
+        let mut b = Octree::new();
+        let root = *b.split(0).unwrap().0;
+        let inner = *b.split(root[0]).unwrap().0;
+        b.set_leaf(inner[3], 10);
+        b.set_leaf(root[7], 20);
+        (a, b)
+    }
+
+    /// A tree whose nodes in each of `octants` are split twice more.
+    fn deep(octants: std::ops::Range<usize>) -> Octree<u8, u8> {
+        let mut tree = Octree::new();
+        let root = *tree.split(0).unwrap().0;
+        for oct in octants {
+            let inner = *tree.split(root[oct]).unwrap().0;
+            for i in inner {
+                tree.split(i).unwrap();
+            }
+        }
+        tree
+    }
+
+    fn leafs(tree: &Octree<u8, u32>) -> Vec<(u8, NodePoint<u32>)> {
+        tree.leaf_dfi().map(|(l, np)| (*l, np)).collect()
+    }
+
+    #[test]
+    fn union() {
+        let (a, b) = trees();
+        let res = leafs(&a.union(&b, |a, b| a + b).unwrap());
+        assert_eq!(res.len(), 16);
+        assert_eq!(res[3], (11, NodePoint::new(0, 1, 1, 2)));
+        assert_eq!(res[8], (22, NodePoint::new(2, 2, 2, 2)));
+        assert!(res[..8].iter().all(|(l, _)| *l == 1 || *l == 11));
+        assert!(res[9..].iter().all(|(l, _)| *l == 20));
+    }
+
+    #[test]
+    fn intersection() {
+        let (a, b) = trees();
+        assert_eq!(
+            leafs(&a.intersection(&b, |a, b| a + b).unwrap()),
+            [
+                (11, NodePoint::new(0, 1, 1, 2)),
+                (22, NodePoint::new(2, 2, 2, 2))
+            ]
+        );
+        assert!(a
+            .intersection(&Octree::new(), |a, _| *a)
+            .unwrap()
+            .leaf_dfi()
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn difference() {
+        let (a, b) = trees();
+        let res = leafs(&a.difference(&b).unwrap());
+        assert_eq!(res.len(), 7);
+        assert!(res.iter().all(|(l, np)| *l == 1 && np.0.w == 2));
+        assert!(!res.contains(&(1, NodePoint::new(0, 1, 1, 2))));
+
+        let res = leafs(&b.difference(&a).unwrap());
+        assert_eq!(res.len(), 7);
+        assert!(res.iter().all(|(l, np)| *l == 20 && np.0.w == 2));
+    }
+
+    #[test]
+    fn symmetric_difference() {
+        let (a, b) = trees();
+        let res = leafs(&a.symmetric_difference(&b).unwrap());
+        assert_eq!(res.len(), 14);
+        assert_eq!(res.iter().filter(|(l, _)| *l == 1).count(), 7);
+        assert_eq!(res.iter().filter(|(l, _)| *l == 20).count(), 7);
+    }
+
+    #[test]
+    fn index_exhausted() {
+        // each tree has 225 nodes, but their union has 441
+        let (a, b) = (deep(0..3), deep(5..8));
+        assert!(matches!(
+            a.union(&b, |a, _| *a),
+            Err(Error::IndexExhausted(_))
+        ));
+        assert!(matches!(
+            a.symmetric_difference(&b),
+            Err(Error::IndexExhausted(_))
+        ));
+        assert!(a.difference(&b).is_ok());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{Error, Octree, OctreeStorage, Proxy, ProxyData, VoxelPoint};
+use eightfold_common::{morton, ArrayIndex};
+use num_traits::AsPrimitive;
+
+/// Builds the storage of an [Octree] bottom-up from leafs sorted by Morton code, visiting each
+/// leaf once.
+///
+/// Only the branches on the path to the latest leaf are open at any time; each branch is written
+/// out once all of its children are, so parents are allocated after their children & the root
+/// comes last.
+struct BulkBuilder<T, Idx: ArrayIndex> {
+    height: usize,
+    proxies: Vec<Proxy<Idx>>,
+    branch_data: Vec<[Idx; 8]>,
+    leaf_data: Vec<T>,
+    /// The children of each open branch, from the root down, by [Octant](crate::Octant).
+    open: Vec<[Option<Idx>; 8]>,
+    /// The code of the latest leaf.
+    last: Option<u128>,
+}
+
+impl<T, Idx: ArrayIndex> BulkBuilder<T, Idx>
+where
+    usize: AsPrimitive<Idx>,
+{
+    /// The [Octant](crate::Octant) of the child at `depth + 1` on the path to the voxel `code`.
+    #[inline]
+    fn octant(&self, code: u128, depth: usize) -> usize {
+        ((code >> (3 * (self.height - depth - 1))) & 0b111) as usize
+    }
+
+    /// Allocate a new proxy, whose parent is set once its parent is allocated.
+    ///
+    /// Every proxy is allocated before its branch or leaf data, so if its index fits in `Idx`,
+    /// so does theirs.
+    #[inline]
+    fn alloc(&mut self, data: ProxyData<Idx>) -> Result<Idx, Error<Idx>> {
+        let index = self.proxies.len();
+        if index > AsPrimitive::<usize>::as_(Idx::max_value()) {
+            return Err(Error::IndexExhausted(index));
+        }
+        let res = index.as_();
+        self.proxies.push(Proxy { parent: res, data });
+        Ok(res)
+    }
+
+    /// Write out the deepest open branch, filling its missing children with voids, & return its
+    /// index.
+    fn close(&mut self) -> Result<Option<Idx>, Error<Idx>> {
+        let Some(open) = self.open.pop() else {
+            return Ok(None);
+        };
+        let mut children = [Idx::ZERO; 8];
+        for (child, c) in children.iter_mut().zip(open) {
+            *child = match c {
+                Some(c) => c,
+                None => self.alloc(ProxyData::Void)?,
+            };
+        }
+        let branch = self.alloc(ProxyData::Branch(self.branch_data.len().as_()))?;
+        for c in children {
+            self.proxies[AsPrimitive::<usize>::as_(c)].parent = branch;
+        }
+        self.branch_data.push(children);
+        Ok(Some(branch))
+    }
+
+    /// Close every open branch deeper than `depth`, attaching each to its parent.
+    fn close_to(&mut self, depth: usize) -> Result<(), Error<Idx>> {
+        while self.open.len() > depth + 1 {
+            let branch = self.close()?;
+            let depth = self.open.len() - 1;
+            let oct = self.octant(self.last.unwrap_or(0), depth);
+            self.open[depth][oct] = branch;
+        }
+        Ok(())
+    }
+
+    /// Add a leaf at the voxel `code`, which must follow any previous leaf.
+    fn push(&mut self, code: u128, data: T) -> Result<(), Error<Idx>> {
+        // the branches shared with the previous leaf stay open
+        let shared = match self.last {
+            Some(last) => (0..self.height)
+                .take_while(|d| self.octant(last, *d) == self.octant(code, *d))
+                .count(),
+            None => 0,
+        };
+        self.close_to(shared)?;
+        self.open.resize(self.height, [None; 8]);
+        self.last = Some(code);
+        let leaf = self.alloc(ProxyData::Leaf(self.leaf_data.len().as_()))?;
+        self.leaf_data.push(data);
+        match self.height.checked_sub(1) {
+            Some(depth) => {
+                let oct = self.octant(code, depth);
+                self.open[depth][oct] = Some(leaf);
+            }
+            None => self.open.clear(),
+        }
+        Ok(())
+    }
+
+    /// Close every open branch & return the index of the root, if any leafs were pushed.
+    fn finish(&mut self) -> Result<Option<Idx>, Error<Idx>> {
+        if self.last.is_none() {
+            return Ok(None);
+        }
+        if self.height == 0 {
+            return Ok(Some(Idx::ZERO));
+        }
+        self.close_to(0)?;
+        self.close()
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Build a tree from leafs at depth `height`, given by Morton code; duplicate codes are
+    /// combined with `merge`, in input order.
+    ///
+    /// Fails with [`IndexExhausted`](Error::IndexExhausted) if the tree would need more nodes than
+    /// `Idx` can address.
+    pub(crate) fn from_codes(
+        height: Idx,
+        mut leafs: Vec<(u128, T)>,
+        mut merge: impl FnMut(T, T) -> T,
+    ) -> Result<Self, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        // stable, so duplicates stay in input order
+        leafs.sort_by_key(|(code, _)| *code);
+        let mut merged: Vec<(u128, T)> = Vec::with_capacity(leafs.len());
+        for (code, data) in leafs {
+            match merged.pop() {
+                Some((prev, acc)) if prev == code => merged.push((code, merge(acc, data))),
+                Some(prev) => {
+                    merged.push(prev);
+                    merged.push((code, data));
+                }
+                None => merged.push((code, data)),
+            }
+        }
+        let mut builder = BulkBuilder {
+            height: height.as_(),
+            proxies: Vec::with_capacity(merged.len() * 2),
+            branch_data: Vec::new(),
+            leaf_data: Vec::with_capacity(merged.len()),
+            open: Vec::new(),
+            last: None,
+        };
+        for (code, data) in merged {
+            builder.push(code, data)?;
+        }
+        let mut res = Self::new();
+        if let Some(root) = builder.finish()? {
+            res.proxies = builder.proxies.into();
+            res.branch_data = builder.branch_data.into();
+            res.leaf_data = builder.leaf_data.into();
+            res.root = root;
+        }
+        Ok(res)
+    }
+
+    /// Build a tree from a set of voxels in one pass, combining the data of duplicate voxels with
+    /// `merge` (such as [`LeafMerge::leaf_merge`](crate::LeafMerge::leaf_merge)), in input order.
+    ///
+    /// Every voxel becomes a leaf at the depth of the smallest voxel grid containing all of them.
+    /// The result is equivalent to splitting down to each voxel & setting its leaf data in turn,
+    /// but avoids a descent from the root for every voxel.
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the tree would need more nodes than `Idx`
+    ///   can address.
+    ///
+    /// # Panics
+    ///
+    /// * (debug only) any coordinate ≥ `2^`[`MAX_BITS`](morton::MAX_BITS)
+    pub fn from_voxels(
+        voxels: impl IntoIterator<Item = (VoxelPoint<Idx>, T)>,
+        merge: impl FnMut(T, T) -> T,
+    ) -> Result<Self, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        let mut max = Idx::ZERO;
+        let leafs = voxels
+            .into_iter()
+            .map(|(p, data)| {
+                max = max | p.x | p.y | p.z;
+                (morton::encode([p.x, p.y, p.z]), data)
+            })
+            .collect::<Vec<_>>();
+        let height = (Idx::ZERO.count_zeros() - max.leading_zeros()) as usize;
+        Self::from_codes(height.as_(), leafs, merge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, Octree, OctreeSlice, VoxelPoint};
+
+    #[test]
+    fn from_voxels() {
+        let voxels = [
+            (VoxelPoint::new(3, 0, 2), 1),
+            (VoxelPoint::new(0, 0, 0), 2),
+            (VoxelPoint::new(3, 0, 2), 4),
+            (VoxelPoint::new(1, 2, 3), 8),
+        ];
+        let tree = Octree::<u8, u32>::from_voxels(voxels, |a, b| a + b).unwrap();
+
+        // incremental insertion
+        let mut expected = Octree::<u8, u32>::new();
+        for (p, data) in voxels {
+            let mut idx = expected.root_idx();
+            for depth in (0..2).rev() {
+                let oct = crate::Octant::new(
+                    (p.x >> depth) & 1 == 1,
+                    (p.y >> depth) & 1 == 1,
+                    (p.z >> depth) & 1 == 1,
+                );
+                idx = expected.split(idx).unwrap().0[usize::from(oct)];
+            }
+            let prev = expected.set_leaf(idx, data).pop().unwrap_or(0);
+            expected.set_leaf(idx, prev + data);
+        }
+
+        assert!(tree.validate().is_ok());
+        assert_eq!(tree.height(), 2);
+        assert!(tree.leaf_dfi().eq(expected.leaf_dfi()));
+        assert_eq!(tree.proxies().len_init(), expected.proxies().len_init());
+        assert_eq!(
+            tree.get(tree.voxel_at(&VoxelPoint::new(3, 0, 2)).unwrap())
+                .parent,
+            tree.get(tree.voxel_at(&VoxelPoint::new(2, 1, 3)).unwrap())
+                .parent
+        );
+
+        let single =
+            Octree::<u8, u32>::from_voxels([(VoxelPoint::new(0, 0, 0), 1)], |a, _| a).unwrap();
+        assert!(single
+            .leaf_dfi()
+            .eq([(&1, crate::NodePoint::new(0, 0, 0, 0))]));
+        assert!(Octree::<u8, u32>::from_voxels([], |a, _| a)
+            .unwrap()
+            .leaf_dfi()
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn index_exhausted() {
+        // every voxel of a grid of height 3 needs 512 leafs, 64 + 8 branches & the root
+        let voxels = (0..512u16).map(|i| (VoxelPoint::new(i & 7, (i >> 3) & 7, i >> 6), 0u8));
+        assert!(matches!(
+            Octree::<u8, u16>::from_voxels(voxels.clone(), |a, _| a),
+            Ok(tree) if tree.leaf_dfi().count() == 512
+        ));
+        let voxels = voxels.map(|(p, data)| (p.map(|c| c as u8), data));
+        assert!(matches!(
+            Octree::<u8, u8>::from_voxels(voxels, |a, _| a),
+            Err(Error::IndexExhausted(256))
+        ));
+    }
+}
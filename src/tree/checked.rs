@@ -0,0 +1,104 @@
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{Arena, Error, Octant, Octree, OctreeStorage};
+
+/// Reserve room for `additional` more values in `arena`, and confirm that each of their indices
+/// would be representable by `Idx`.
+fn reserve_checked<V, Idx: ArrayIndex>(
+    arena: &mut impl Arena<V>,
+    additional: usize,
+) -> Result<(), Error<Idx>> {
+    if additional == 0 {
+        return Ok(());
+    }
+    arena.reserve(additional);
+    let max = AsPrimitive::<usize>::as_(Idx::max_value());
+    if arena.capacity() <= max {
+        return Ok(());
+    }
+    let last = arena.next_index(additional - 1);
+    if last > max {
+        return Err(Error::IndexExhausted(last));
+    }
+    Ok(())
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Reserve room for `nodes` more nodes, `branches` more branches & `leafs` more leafs, and
+    /// confirm that their indices will be representable by `Idx`.
+    ///
+    /// Storage always grows past `Idx::MAX` if asked to, so anything inserting into a tree with a
+    /// narrow index type should go through this, or through one of the checked insertion
+    /// methods: [`Self::split`], [`Self::try_set_leaf`] & [`Self::try_grow`].
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if any of the new indices would exceed
+    ///   `Idx::MAX`.
+    pub fn try_reserve(
+        &mut self,
+        nodes: usize,
+        branches: usize,
+        leafs: usize,
+    ) -> Result<(), Error<Idx>> {
+        reserve_checked(&mut self.proxies, nodes)?;
+        reserve_checked(&mut self.branch_data, branches)?;
+        reserve_checked(&mut self.leaf_data, leafs)
+    }
+
+    /// [`Self::set_leaf`], unless the new leaf data wouldn't be addressable by `Idx`.
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the new leaf data would exceed `Idx::MAX`,
+    ///   in which case `self` is unchanged.
+    pub fn try_set_leaf(&mut self, target: Idx, data: T) -> Result<Vec<T>, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        if !self.proxies[target.as_()].is_leaf() {
+            self.try_reserve(0, 0, 1)?;
+        }
+        Ok(self.set_leaf(target, data))
+    }
+
+    /// [`Self::grow`], unless the new nodes wouldn't be addressable by `Idx`.
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the new nodes would exceed `Idx::MAX`, in
+    ///   which case `self` is unchanged.
+    pub fn try_grow(&mut self, oct: Octant) -> Result<Idx, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        self.try_reserve(8, 1, 0)?;
+        Ok(self.grow(oct))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, Octant, Octree, OctreeSlice};
+
+    #[test]
+    fn index_exhausted() {
+        let mut tree = Octree::<u8, u8>::new();
+        let mut target = tree.root_idx();
+        let mut splits = 0;
+        let err = loop {
+            match tree.split(target) {
+                Ok((children, _)) => target = children[0],
+                Err(e) => break e,
+            }
+            splits += 1;
+        };
+        assert!(matches!(err, Error::IndexExhausted(256)));
+        assert_eq!(splits, 255 / 8);
+        assert!(tree.proxies().len_init() <= 256);
+        assert!(tree.try_grow(Octant(0)).is_err());
+        assert!(tree.try_set_leaf(target, 1).unwrap().is_empty());
+        assert_eq!(tree.leaf_dfi().count(), 1);
+    }
+}
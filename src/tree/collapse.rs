@@ -0,0 +1,160 @@
+use eightfold_common::ArrayIndex;
+
+use crate::{Arena, Octree, OctreeStorage, ProxyData};
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Whether inserting leaf data through [`Self::set_leaf`] or
+    /// [`NodeMut::leaf_data_or_insert_with`](crate::NodeMut::leaf_data_or_insert_with)
+    /// automatically collapses branches left with eight equal leaf children.
+    #[inline]
+    pub fn collapse_on_insert(&self) -> bool {
+        self.collapse_eq.is_some()
+    }
+
+    /// Enable or disable collapsing uniform branches on insertion.
+    ///
+    /// While enabled, inserting leaf data walks up from the inserted node, replacing each ancestor
+    /// whose children are all leafs with equal data by a single leaf. Enabling this doesn't
+    /// collapse existing branches; see [`Self::collapse_uniform`].
+    #[inline]
+    pub fn set_collapse_on_insert(&mut self, enabled: bool)
+    where
+        T: PartialEq,
+    {
+        self.collapse_eq = enabled.then_some(<T as PartialEq>::eq);
+    }
+
+    /// If all children of a branch are leafs with equal data, get the leaf data index of the
+    /// first child.
+    fn uniform_leaf(&self, children: Idx, eq: fn(&T, &T) -> bool) -> Option<Idx> {
+        let mut leafs = self.branch_data[children.as_()]
+            .iter()
+            .map(|c| self.proxies[c.as_()].leaf());
+        let first = leafs.next()??;
+        for l_idx in leafs {
+            if !eq(&self.leaf_data[first.as_()], &self.leaf_data[l_idx?.as_()]) {
+                return None;
+            }
+        }
+        Some(first)
+    }
+
+    /// Replace a branch of leafs by a single leaf, keeping the leaf data at index `keep` & dropping
+    /// the rest.
+    fn collapse_branch(&mut self, target: Idx, children: Idx, keep: Idx) {
+        if let Some(children) = self.branch_data.remove(children.as_()) {
+            for c in children {
+                if let Some(ProxyData::Leaf(l_idx)) = self.proxies.remove(c.as_()).map(|p| p.data) {
+                    if l_idx != keep {
+                        self.leaf_data.remove(l_idx.as_());
+                    }
+                }
+            }
+        }
+        self.proxies[target.as_()].data = ProxyData::Leaf(keep);
+    }
+
+    /// Replace every branch whose children are all leafs with equal data by a single leaf, working
+    /// bottom-up, and return the number of branches collapsed.
+    ///
+    /// Unlike [`Self::merge_branch`], this never combines unequal data, so no information is lost.
+    pub fn collapse_uniform(&mut self) -> usize
+    where
+        T: PartialEq,
+    {
+        // parents precede their children in `branches`, so walking it backwards is bottom-up
+        let mut branches = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(idx) = stack.pop() {
+            if let ProxyData::Branch(c_idx) = self.proxies[idx.as_()].data {
+                branches.push((idx, c_idx));
+                stack.extend_from_slice(&self.branch_data[c_idx.as_()]);
+            }
+        }
+        let mut res = 0;
+        for (idx, c_idx) in branches.into_iter().rev() {
+            if let Some(keep) = self.uniform_leaf(c_idx, <T as PartialEq>::eq) {
+                self.collapse_branch(idx, c_idx, keep);
+                res += 1;
+            }
+        }
+        res
+    }
+
+    /// With collapse-on-insert enabled, walk up from the leaf `node`, collapsing each uniform
+    /// ancestor, and return the index of the node now holding `node`'s leaf data.
+    pub(crate) fn collapse_ancestors(&mut self, mut node: Idx) -> Idx {
+        let Some(eq) = self.collapse_eq else {
+            return node;
+        };
+        let Some(keep) = self.proxies[node.as_()].leaf() else {
+            return node;
+        };
+        loop {
+            let parent = self.proxies[node.as_()].parent;
+            if parent == node {
+                break;
+            }
+            match self.proxies[parent.as_()].data {
+                ProxyData::Branch(c_idx) if self.uniform_leaf(c_idx, eq).is_some() => {
+                    self.collapse_branch(parent, c_idx, keep);
+                }
+                _ => break,
+            }
+            node = parent;
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NodePoint, Octree, OctreeSlice, ProxyData};
+
+    #[test]
+    fn collapse_uniform() {
+        let mut tree = Octree::<u8, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        for c in root.into_iter().chain(inner).filter(|c| *c != root[3]) {
+            tree.set_leaf(c, 1);
+        }
+        tree.set_leaf(inner[2], 2);
+        assert_eq!(tree.collapse_uniform(), 0);
+        tree.set_leaf(inner[2], 1);
+        assert_eq!(tree.collapse_uniform(), 2);
+        assert!(tree.leaf_dfi().eq([(&1, NodePoint::new(0, 0, 0, 0))]));
+        assert_eq!(tree.proxies().len_init(), 1);
+        assert_eq!(tree.leaf_data().len_init(), 1);
+    }
+
+    #[test]
+    fn collapse_on_insert() {
+        let mut tree = Octree::<u8, u32>::new();
+        tree.set_collapse_on_insert(true);
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        for c in root.into_iter().chain(inner[1..].iter().copied()) {
+            if c != root[3] {
+                tree.set_leaf(c, 1);
+            }
+        }
+        assert_eq!(tree.collapse_uniform(), 0);
+
+        let mut node = tree.node_mut(inner[0]).unwrap();
+        assert_eq!(*node.leaf_data_or_insert_with(|| 1).unwrap(), 1);
+        assert_eq!(node.index(), 0);
+        assert!(matches!(tree.get(0).data, ProxyData::Leaf(_)));
+        assert_eq!(tree.proxies().len_init(), 1);
+        assert_eq!(tree.leaf_data().len_init(), 1);
+
+        // unequal data isn't collapsed
+        tree.remove(0);
+        let root = *tree.split(0).unwrap().0;
+        tree.set_leaf(root[1], 2);
+        for c in root.into_iter().filter(|c| *c != root[1]) {
+            tree.set_leaf(c, 1);
+        }
+        assert!(matches!(tree.get(0).data, ProxyData::Branch(_)));
+    }
+}
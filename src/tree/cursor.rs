@@ -0,0 +1,445 @@
+use std::ops::Range;
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{
+    Error, Node, NodeMut, NodePoint, Octant, Octree, OctreeStorage, Proxy, ProxyData, StableStorage,
+};
+
+/// A position within an [Octree], which can move between nodes; see [`Octree::cursor`].
+#[derive(Debug, Clone, Copy)]
+pub struct OctreeCursor<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    tree: &'tree Octree<T, Idx, S>,
+    index: Idx,
+    point: NodePoint<Idx>,
+}
+
+/// A position within an [Octree], which can move between nodes & edit them in place; see
+/// [`Octree::cursor_mut`].
+#[derive(Debug)]
+pub struct OctreeCursorMut<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    tree: &'tree mut Octree<T, Idx, S>,
+    index: Idx,
+    point: NodePoint<Idx>,
+}
+
+/// `Idx::ONE` if `b` is set, else `Idx::ZERO`.
+#[inline]
+fn bit<Idx: ArrayIndex>(b: u8) -> Idx {
+    if b == 0 {
+        Idx::ZERO
+    } else {
+        Idx::ONE
+    }
+}
+
+/// The [Octant] of the node at `point` within its parent, unless it's the root.
+#[inline]
+fn octant_of<Idx: ArrayIndex>(point: &NodePoint<Idx>) -> Option<Octant> {
+    (point.0.w != Idx::ZERO).then(|| {
+        Octant::new(
+            point.0.x & Idx::ONE == Idx::ONE,
+            point.0.y & Idx::ONE == Idx::ONE,
+            point.0.z & Idx::ONE == Idx::ONE,
+        )
+    })
+}
+
+/// The index & [`NodePoint`] of the parent of a node.
+fn parent_of<T, Idx: ArrayIndex, S: OctreeStorage>(
+    tree: &Octree<T, Idx, S>,
+    index: Idx,
+    point: &NodePoint<Idx>,
+) -> Result<(Idx, NodePoint<Idx>), Error<Idx>> {
+    let parent = tree.proxies[index.as_()].parent;
+    if parent == index {
+        return Err(Error::NoParent);
+    }
+    Ok((
+        parent,
+        NodePoint::new(
+            point.0.x >> Idx::ONE,
+            point.0.y >> Idx::ONE,
+            point.0.z >> Idx::ONE,
+            point.0.w - Idx::ONE,
+        ),
+    ))
+}
+
+/// The index & [`NodePoint`] of a child of a node.
+fn child_of<T, Idx: ArrayIndex, S: OctreeStorage>(
+    tree: &Octree<T, Idx, S>,
+    index: Idx,
+    point: &NodePoint<Idx>,
+    oct: Octant,
+) -> Result<(Idx, NodePoint<Idx>), Error<Idx>> {
+    let ProxyData::Branch(b_idx) = tree.proxies[index.as_()].data else {
+        return Err(Error::NoChildren(index));
+    };
+    Ok((
+        tree.branch_data[b_idx.as_()][usize::from(oct)],
+        NodePoint::new(
+            (point.0.x << Idx::ONE) | bit(oct.i()),
+            (point.0.y << Idx::ONE) | bit(oct.j()),
+            (point.0.z << Idx::ONE) | bit(oct.k()),
+            point.0.w + Idx::ONE,
+        ),
+    ))
+}
+
+/// The index & [`NodePoint`] of a sibling of a node.
+fn sibling_of<T, Idx: ArrayIndex, S: OctreeStorage>(
+    tree: &Octree<T, Idx, S>,
+    index: Idx,
+    point: &NodePoint<Idx>,
+    oct: Octant,
+) -> Result<(Idx, NodePoint<Idx>), Error<Idx>> {
+    let (parent, p_point) = parent_of(tree, index, point)?;
+    child_of(tree, parent, &p_point, oct)
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> OctreeCursor<'tree, T, Idx, S> {
+    /// The tree being traversed.
+    #[inline]
+    pub fn tree(&self) -> &'tree Octree<T, Idx, S> {
+        self.tree
+    }
+
+    /// The index of the current node.
+    #[inline]
+    pub fn index(&self) -> Idx {
+        self.index
+    }
+
+    /// The [`NodePoint`] of the current node.
+    #[inline]
+    pub fn point(&self) -> NodePoint<Idx> {
+        self.point
+    }
+
+    /// The depth of the current node, where the root is at depth 0.
+    #[inline]
+    pub fn depth(&self) -> Idx {
+        self.point.0.w
+    }
+
+    /// The [Octant] of the current node within its parent, unless it's the root.
+    #[inline]
+    pub fn octant(&self) -> Option<Octant> {
+        octant_of(&self.point)
+    }
+
+    /// The [Proxy] of the current node.
+    #[inline]
+    pub fn proxy(&self) -> Proxy<Idx> {
+        self.tree.proxies[self.index.as_()]
+    }
+
+    /// The current node.
+    ///
+    /// This can't panic, as the cursor can only move between existing nodes of a tree it borrows
+    /// immutably.
+    #[inline]
+    pub fn node(&self) -> Node<'tree, T, Idx, S> {
+        self.tree
+            .node(self.index)
+            .expect("the cursor should be at an existing node")
+    }
+
+    /// The leaf data of the current node, if it's a leaf.
+    #[inline]
+    pub fn leaf_data(&self) -> Option<&'tree T> {
+        self.proxy()
+            .leaf()
+            .map(|l_idx| &self.tree.leaf_data[l_idx.as_()])
+    }
+
+    /// Move to the parent of the current node.
+    ///
+    /// # Errors
+    ///
+    /// * [`NoParent`](Error::NoParent) if the current node is the root.
+    pub fn move_to_parent(&mut self) -> Result<&mut Self, Error<Idx>> {
+        (self.index, self.point) = parent_of(self.tree, self.index, &self.point)?;
+        Ok(self)
+    }
+
+    /// Move to a child of the current node.
+    ///
+    /// # Errors
+    ///
+    /// * [`NoChildren`](Error::NoChildren) if the current node isn't a branch.
+    pub fn move_to_child(&mut self, oct: Octant) -> Result<&mut Self, Error<Idx>> {
+        (self.index, self.point) = child_of(self.tree, self.index, &self.point, oct)?;
+        Ok(self)
+    }
+
+    /// Move to a sibling of the current node, by its [Octant] within their parent.
+    ///
+    /// # Errors
+    ///
+    /// * [`NoParent`](Error::NoParent) if the current node is the root.
+    pub fn move_to_sibling(&mut self, oct: Octant) -> Result<&mut Self, Error<Idx>> {
+        (self.index, self.point) = sibling_of(self.tree, self.index, &self.point, oct)?;
+        Ok(self)
+    }
+
+    /// Move to the root of the tree.
+    #[inline]
+    pub fn move_to_root(&mut self) -> &mut Self {
+        *self = self.tree.cursor();
+        self
+    }
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> OctreeCursorMut<'tree, T, Idx, S> {
+    /// A read-only view of the current position.
+    #[inline]
+    pub fn as_cursor(&self) -> OctreeCursor<'_, T, Idx, S> {
+        OctreeCursor {
+            tree: self.tree,
+            index: self.index,
+            point: self.point,
+        }
+    }
+
+    /// Convert `self` into a mutable reference to the current node.
+    ///
+    /// This can't panic, as the cursor can only move between existing nodes, and moves to an
+    /// existing ancestor after any edit which removes the current node.
+    #[inline]
+    pub fn into_node_mut(self) -> NodeMut<'tree, T, Idx, S> {
+        self.tree
+            .node_mut(self.index)
+            .expect("the cursor should be at an existing node")
+    }
+
+    /// The index of the current node.
+    #[inline]
+    pub fn index(&self) -> Idx {
+        self.index
+    }
+
+    /// The [`NodePoint`] of the current node.
+    #[inline]
+    pub fn point(&self) -> NodePoint<Idx> {
+        self.point
+    }
+
+    /// The depth of the current node, where the root is at depth 0.
+    #[inline]
+    pub fn depth(&self) -> Idx {
+        self.point.0.w
+    }
+
+    /// The [Octant] of the current node within its parent, unless it's the root.
+    #[inline]
+    pub fn octant(&self) -> Option<Octant> {
+        octant_of(&self.point)
+    }
+
+    /// The [Proxy] of the current node.
+    #[inline]
+    pub fn proxy(&self) -> Proxy<Idx> {
+        self.tree.proxies[self.index.as_()]
+    }
+
+    /// The leaf data of the current node, if it's a leaf.
+    #[inline]
+    pub fn leaf_data(&self) -> Option<&T> {
+        self.proxy()
+            .leaf()
+            .map(|l_idx| &self.tree.leaf_data[l_idx.as_()])
+    }
+
+    /// The leaf data of the current node, if it's a leaf.
+    #[inline]
+    pub fn leaf_data_mut(&mut self) -> Option<&mut T> {
+        self.proxy()
+            .leaf()
+            .map(|l_idx| &mut self.tree.leaf_data[l_idx.as_()])
+    }
+
+    /// Move to the parent of the current node.
+    ///
+    /// # Errors
+    ///
+    /// * [`NoParent`](Error::NoParent) if the current node is the root.
+    pub fn move_to_parent(&mut self) -> Result<&mut Self, Error<Idx>> {
+        (self.index, self.point) = parent_of(self.tree, self.index, &self.point)?;
+        Ok(self)
+    }
+
+    /// Move to a child of the current node.
+    ///
+    /// # Errors
+    ///
+    /// * [`NoChildren`](Error::NoChildren) if the current node isn't a branch.
+    pub fn move_to_child(&mut self, oct: Octant) -> Result<&mut Self, Error<Idx>> {
+        (self.index, self.point) = child_of(self.tree, self.index, &self.point, oct)?;
+        Ok(self)
+    }
+
+    /// Move to a sibling of the current node, by its [Octant] within their parent.
+    ///
+    /// # Errors
+    ///
+    /// * [`NoParent`](Error::NoParent) if the current node is the root.
+    pub fn move_to_sibling(&mut self, oct: Octant) -> Result<&mut Self, Error<Idx>> {
+        (self.index, self.point) = sibling_of(self.tree, self.index, &self.point, oct)?;
+        Ok(self)
+    }
+
+    /// Move to the root of the tree.
+    #[inline]
+    pub fn move_to_root(&mut self) -> &mut Self {
+        self.index = self.tree.root;
+        self.point = NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO);
+        self
+    }
+
+    /// Move to the deepest node still encompassing the current position, after an edit which
+    /// may have removed the current node.
+    fn resync(&mut self) {
+        let p = self.point.0;
+        self.index = self.tree.internal_node_at(&p.xyz(), p.w);
+        let up = p.w - self.tree.depth_of_unchecked(self.index);
+        self.point = NodePoint::new(p.x >> up, p.y >> up, p.z >> up, p.w - up);
+    }
+
+    /// [Split](Octree::split) the current node, if it isn't already a branch.
+    ///
+    /// # Errors
+    ///
+    /// * [`CannotSplitLeaf`](Error::CannotSplitLeaf) if the current node is a leaf.
+    pub fn split(&mut self) -> Result<&mut Self, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        self.tree.split(self.index)?;
+        Ok(self)
+    }
+
+    /// [Set the leaf data](Octree::set_leaf) of the current node, and return its previous data.
+    ///
+    /// With [collapse-on-insert](Octree::set_collapse_on_insert) enabled, the current node may be
+    /// collapsed into an ancestor, in which case the cursor moves to that ancestor.
+    pub fn set_leaf(&mut self, data: T) -> Vec<T>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        let res = self.tree.set_leaf(self.index, data);
+        self.resync();
+        res
+    }
+
+    /// [Remove](Octree::remove) the current node, and return its data.
+    ///
+    /// With [auto-pruning](Octree::set_auto_prune) enabled, ancestors left without any non-void
+    /// children are voided, in which case the cursor moves to the highest of them.
+    pub fn remove(&mut self) -> Vec<T> {
+        let res = self.tree.remove(self.index);
+        self.resync();
+        res
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Get an [`OctreeCursor`] at the root of this tree.
+    #[inline]
+    pub fn cursor(&self) -> OctreeCursor<'_, T, Idx, S> {
+        OctreeCursor {
+            tree: self,
+            index: self.root,
+            point: NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
+        }
+    }
+
+    /// Get an [`OctreeCursorMut`] at the root of this tree.
+    #[inline]
+    pub fn cursor_mut(&mut self) -> OctreeCursorMut<'_, T, Idx, S> {
+        OctreeCursorMut {
+            index: self.root,
+            tree: self,
+            point: NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, NodePoint, Octant, Octree, OctreeSlice};
+
+    #[test]
+    fn cursor() {
+        let mut tree = Octree::<u8, u32>::new();
+        let mut cursor = tree.cursor_mut();
+        assert!(matches!(cursor.move_to_parent(), Err(Error::NoParent)));
+        assert!(matches!(
+            cursor.move_to_child(Octant(3)),
+            Err(Error::NoChildren(0))
+        ));
+
+        cursor
+            .split()
+            .unwrap()
+            .move_to_child(Octant(3))
+            .unwrap()
+            .split()
+            .unwrap()
+            .move_to_child(Octant(5))
+            .unwrap();
+        assert_eq!(cursor.point(), NodePoint::new(1, 2, 3, 2));
+        assert_eq!(cursor.octant(), Some(Octant(5)));
+        assert!(cursor.set_leaf(1).is_empty());
+        assert_eq!(cursor.leaf_data(), Some(&1));
+        *cursor.leaf_data_mut().unwrap() += 1;
+
+        cursor.move_to_sibling(Octant(0)).unwrap();
+        assert_eq!(cursor.point(), NodePoint::new(0, 2, 2, 2));
+        assert!(cursor.as_cursor().proxy().is_void());
+        cursor.move_to_parent().unwrap();
+        assert_eq!(cursor.point(), NodePoint::new(0, 1, 1, 1));
+        assert_eq!(cursor.octant(), Some(Octant(3)));
+        cursor.move_to_root();
+        assert_eq!(cursor.index(), tree.root_idx());
+
+        assert!(tree.leaf_dfi().eq([(&2, NodePoint::new(1, 2, 3, 2))]));
+        let mut cursor = tree.cursor();
+        cursor.move_to_child(Octant(3)).unwrap();
+        assert_eq!(cursor.node().parent().unwrap().index(), tree.root_idx());
+        assert!(tree.node(tree.root_idx()).unwrap().parent().is_none());
+    }
+
+    #[test]
+    fn edit_moves_cursor() {
+        let mut tree = Octree::<u8, u32>::new();
+        tree.set_collapse_on_insert(true);
+        let mut cursor = tree.cursor_mut();
+        cursor.split().unwrap();
+        for oct in Octant::ALL {
+            cursor.move_to_root().move_to_child(oct).unwrap();
+            cursor.set_leaf(1);
+        }
+        // the last leaf collapsed the root
+        assert_eq!(cursor.point(), NodePoint::new(0, 0, 0, 0));
+        assert_eq!(cursor.leaf_data(), Some(&1));
+
+        cursor.set_leaf(2);
+        assert_eq!(cursor.remove(), [2]);
+        tree.set_auto_prune(true);
+        let mut cursor = tree.cursor_mut();
+        cursor
+            .split()
+            .unwrap()
+            .move_to_child(Octant(6))
+            .unwrap()
+            .set_leaf(3);
+        // removing the only leaf prunes its parent
+        cursor.remove();
+        assert_eq!(cursor.index(), tree.root_idx());
+        assert_eq!(tree.proxies().len_init(), 1);
+    }
+}
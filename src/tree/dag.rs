@@ -0,0 +1,320 @@
+use std::{collections::HashMap, hash::Hash, iter::FusedIterator, ops::Range};
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{
+    Arena, Error, NodePoint, Octant, Octree, OctreeSlice, OctreeStorage, ProxyData, VoxelPoint,
+};
+
+/// A read-only sparse voxel DAG: an [Octree] in which structurally identical subtrees, including
+/// equal leaf data, are stored only once.
+///
+/// Nodes are referred to by [`ProxyData`], where leaf & branch indices point into the deduplicated
+/// leaf & branch stores, respectively. A node may be shared by any number of parents, so nodes
+/// have no parent links & no unique [`NodePoint`].
+#[derive(Debug, Clone)]
+pub struct OctreeDag<T, Idx: ArrayIndex> {
+    branches: Vec<[ProxyData<Idx>; 8]>,
+    leafs: Vec<T>,
+    root: ProxyData<Idx>,
+    height: Idx,
+    /// The number of non-void nodes in the source tree.
+    source_len: usize,
+}
+
+/// Interns nodes of a source tree, bottom-up.
+struct DagBuilder<'tree, T, Idx: ArrayIndex, S: OctreeStorage> {
+    tree: &'tree Octree<T, Idx, S>,
+    branches: Vec<[ProxyData<Idx>; 8]>,
+    branch_ids: HashMap<[ProxyData<Idx>; 8], Idx>,
+    leafs: Vec<&'tree T>,
+    leaf_ids: HashMap<&'tree T, Idx>,
+}
+
+impl<'tree, T: Hash + Eq, Idx: ArrayIndex + Hash, S: OctreeStorage> DagBuilder<'tree, T, Idx, S>
+where
+    usize: AsPrimitive<Idx>,
+{
+    fn intern(&mut self, node: Idx) -> ProxyData<Idx> {
+        match self.tree.get(node).data {
+            ProxyData::Void => ProxyData::Void,
+            ProxyData::Leaf(l_idx) => {
+                let data = &self.tree.leaf_data()[l_idx.as_()];
+                let next = self.leafs.len().as_();
+                let id = *self.leaf_ids.entry(data).or_insert(next);
+                if id == next {
+                    self.leafs.push(data);
+                }
+                ProxyData::Leaf(id)
+            }
+            ProxyData::Branch(b_idx) => {
+                let children = self.tree.branch_data()[b_idx.as_()].map(|c| self.intern(c));
+                let next = self.branches.len().as_();
+                let id = *self.branch_ids.entry(children).or_insert(next);
+                if id == next {
+                    self.branches.push(children);
+                }
+                ProxyData::Branch(id)
+            }
+        }
+    }
+}
+
+impl<T, Idx: ArrayIndex> OctreeDag<T, Idx> {
+    /// Build a DAG from an [Octree], deduplicating identical subtrees.
+    pub fn new<S: OctreeStorage>(tree: &Octree<T, Idx, S>) -> Self
+    where
+        T: Hash + Eq + Clone,
+        Idx: Hash,
+        usize: AsPrimitive<Idx>,
+    {
+        let mut builder = DagBuilder {
+            tree,
+            branches: Vec::new(),
+            branch_ids: HashMap::new(),
+            leafs: Vec::new(),
+            leaf_ids: HashMap::new(),
+        };
+        let root = builder.intern(tree.root_idx());
+        Self {
+            branches: builder.branches,
+            leafs: builder.leafs.into_iter().cloned().collect(),
+            root,
+            height: tree.height(),
+            source_len: tree.branch_data().len_init() + tree.leaf_data().len_init(),
+        }
+    }
+
+    /// The root node of `self`.
+    #[inline]
+    pub fn root(&self) -> ProxyData<Idx> {
+        self.root
+    }
+
+    /// The height of the source tree.
+    #[inline]
+    pub fn height(&self) -> Idx {
+        self.height
+    }
+
+    /// The dimensions of the cubical voxel grid represented by this tree.
+    #[inline]
+    pub fn grid_size(&self) -> Idx {
+        Idx::ONE << self.height
+    }
+
+    /// The number of unique branches in `self`.
+    #[inline]
+    pub fn len_branches(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// The number of unique leafs in `self`.
+    #[inline]
+    pub fn len_leafs(&self) -> usize {
+        self.leafs.len()
+    }
+
+    /// The number of non-void nodes in the tree from which `self` was built.
+    #[inline]
+    pub fn source_len(&self) -> usize {
+        self.source_len
+    }
+
+    /// The ratio of non-void nodes in the source tree to unique nodes in `self`.
+    ///
+    /// An empty tree has a ratio of `1`.
+    pub fn compression_ratio(&self) -> f64 {
+        let len = self.len_branches() + self.len_leafs();
+        if len == 0 {
+            return 1.0;
+        }
+        self.source_len as f64 / len as f64
+    }
+
+    /// Get the children of a unique branch.
+    ///
+    /// # Panics
+    ///
+    /// * `branch` ≥ `self.len_branches()`
+    #[inline]
+    pub fn children(&self, branch: Idx) -> &[ProxyData<Idx>; 8] {
+        &self.branches[AsPrimitive::<usize>::as_(branch)]
+    }
+
+    /// Get the data of a unique leaf.
+    ///
+    /// # Panics
+    ///
+    /// * `leaf` ≥ `self.len_leafs()`
+    #[inline]
+    pub fn leaf(&self, leaf: Idx) -> &T {
+        &self.leafs[AsPrimitive::<usize>::as_(leaf)]
+    }
+
+    /// Get the deepest node encompassing a specific [`NodePoint`], and its depth.
+    pub fn node_at(&self, p: &NodePoint<Idx>) -> (ProxyData<Idx>, Idx) {
+        let mut node = self.root;
+        let mut depth = p.0.w;
+        while let ProxyData::Branch(b_idx) = node {
+            if depth == Idx::ZERO {
+                break;
+            }
+            depth -= Idx::ONE;
+            let oct = Octant::new(
+                (p.0.x >> depth) & Idx::ONE == Idx::ONE,
+                (p.0.y >> depth) & Idx::ONE == Idx::ONE,
+                (p.0.z >> depth) & Idx::ONE == Idx::ONE,
+            );
+            node = self.branches[AsPrimitive::<usize>::as_(b_idx)][usize::from(oct)];
+        }
+        (node, p.0.w - depth)
+    }
+
+    /// Get the deepest node containing a specific [`VoxelPoint`].
+    ///
+    /// # Errors
+    ///
+    /// * [`VoxelOutOfGrid`](Error::VoxelOutOfGrid) if `p` ∉ 0..`self.grid_size()`
+    pub fn voxel_at(&self, p: &VoxelPoint<Idx>) -> Result<ProxyData<Idx>, Error<Idx>> {
+        let size = self.grid_size();
+        if p.x >= size || p.y >= size || p.z >= size {
+            return Err(Error::VoxelOutOfGrid(size, *p));
+        }
+        Ok(self.node_at(&NodePoint::new(p.x, p.y, p.z, self.height)).0)
+    }
+
+    /// Depth-first iterator through all leafs, by [Octant] ordering.
+    ///
+    /// Shared subtrees are visited once per occurrence.
+    pub fn leaf_dfi(&self) -> DagLeafIter<'_, T, Idx> {
+        DagLeafIter {
+            dag: self,
+            node_stack: vec![(
+                self.root,
+                NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
+            )],
+        }
+    }
+
+    /// Expand `self` back into an [Octree].
+    pub fn to_octree(&self) -> Octree<T, Idx>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        let mut res = Octree::new();
+        let mut stack = vec![(self.root, res.root_idx())];
+        while let Some((node, idx)) = stack.pop() {
+            match node {
+                ProxyData::Void => {}
+                ProxyData::Leaf(l_idx) => {
+                    res.set_leaf(idx, self.leafs[AsPrimitive::<usize>::as_(l_idx)].clone());
+                }
+                ProxyData::Branch(b_idx) => {
+                    // `idx` is always a new void, and a DAG can only be built from an `Octree`
+                    // with the same index type, whose nodes this recreates one-for-one
+                    let children = *res
+                        .split(idx)
+                        .expect("the expanded tree should be no larger than its source")
+                        .0;
+                    stack.extend(
+                        self.branches[AsPrimitive::<usize>::as_(b_idx)]
+                            .iter()
+                            .copied()
+                            .zip(children),
+                    );
+                }
+            }
+        }
+        res
+    }
+}
+
+/// A depth-first iterator over leafs in an [`OctreeDag`].
+pub struct DagLeafIter<'dag, T, Idx: ArrayIndex> {
+    dag: &'dag OctreeDag<T, Idx>,
+    node_stack: Vec<(ProxyData<Idx>, NodePoint<Idx>)>,
+}
+
+impl<'dag, T, Idx: ArrayIndex> FusedIterator for DagLeafIter<'dag, T, Idx> where u8: AsPrimitive<Idx>
+{}
+
+impl<'dag, T, Idx: ArrayIndex> Iterator for DagLeafIter<'dag, T, Idx>
+where
+    u8: AsPrimitive<Idx>,
+{
+    type Item = (&'dag T, NodePoint<Idx>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, np)) = self.node_stack.pop() {
+            match node {
+                ProxyData::Void => {}
+                ProxyData::Leaf(l_idx) => {
+                    return Some((&self.dag.leafs[AsPrimitive::<usize>::as_(l_idx)], np))
+                }
+                ProxyData::Branch(b_idx) => {
+                    self.node_stack.extend(
+                        self.dag.branches[AsPrimitive::<usize>::as_(b_idx)]
+                            .iter()
+                            .zip(Octant::ALL)
+                            .rev()
+                            .map(|(c, oct)| (*c, np + oct)),
+                    );
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<T: Hash + Eq + Clone, Idx: ArrayIndex + Hash, S: OctreeStorage> From<&Octree<T, Idx, S>>
+    for OctreeDag<T, Idx>
+where
+    usize: AsPrimitive<Idx>,
+{
+    /// See [`OctreeDag::new`].
+    #[inline]
+    fn from(tree: &Octree<T, Idx, S>) -> Self {
+        Self::new(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NodePoint, Octree, OctreeDag, OctreeSlice, ProxyData, VoxelPoint};
+
+    #[test]
+    fn dedup() {
+        let mut tree = Octree::<u8, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        // two identical subtrees & one subtree with different data
+        for (b, data) in [(root[1], 1), (root[4], 1), (root[6], 2)] {
+            let children = *tree.split(b).unwrap().0;
+            tree.set_leaf(children[0], data);
+            tree.set_leaf(children[7], 3);
+        }
+        let dag = OctreeDag::from(&tree);
+        assert_eq!(dag.source_len(), 4 + 6);
+        assert_eq!(dag.len_branches(), 3);
+        assert_eq!(dag.len_leafs(), 3);
+        assert!((dag.compression_ratio() - 10.0 / 6.0).abs() < f64::EPSILON);
+
+        assert!(dag.leaf_dfi().eq(tree.leaf_dfi()));
+        assert!(dag.to_octree().leaf_dfi().eq(tree.leaf_dfi()));
+
+        let (ProxyData::Leaf(l), depth) = dag.node_at(&NodePoint::new(3, 3, 1, 2)) else {
+            panic!("expected a leaf");
+        };
+        assert_eq!((*dag.leaf(l), depth), (3, 2));
+        assert_eq!(dag.node_at(&NodePoint::new(3, 3, 0, 2)).1, 2);
+        assert_eq!(dag.node_at(&NodePoint::new(0, 0, 0, 2)).1, 1);
+        assert_eq!(
+            dag.voxel_at(&VoxelPoint::new(2, 0, 0)).unwrap(),
+            ProxyData::Leaf(0)
+        );
+        assert!(dag.voxel_at(&VoxelPoint::new(4, 0, 0)).is_err());
+    }
+}
@@ -1,16 +1,49 @@
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 
 use eightfold_common::ArrayIndex;
 use num_traits::AsPrimitive;
 
+use crate::Arena;
 use crate::Octant;
 use crate::Octree;
 
 use crate::OctreeSlice;
+use crate::OctreeStorage;
 use crate::Proxy;
 use crate::ProxyData;
 
-impl<T: std::fmt::Debug, Idx: ArrayIndex> Display for Octree<T, Idx>
+/// Formats the occupied entries of an [Arena] as a map from index to value.
+struct ArenaEntries<'a, V, A: Arena<V>>(&'a A, std::marker::PhantomData<V>);
+
+impl<'a, V: Debug, A: Arena<V>> Debug for ArenaEntries<'a, V, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.0.enumerate()).finish()
+    }
+}
+
+impl<T: Debug, Idx: ArrayIndex, S: OctreeStorage> Debug for Octree<T, Idx, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Octree")
+            .field(
+                "proxies",
+                &ArenaEntries(&self.proxies, std::marker::PhantomData),
+            )
+            .field(
+                "branch_data",
+                &ArenaEntries(&self.branch_data, std::marker::PhantomData),
+            )
+            .field(
+                "leaf_data",
+                &ArenaEntries(&self.leaf_data, std::marker::PhantomData),
+            )
+            .field("root", &self.root)
+            .field("auto_prune", &self.auto_prune)
+            .field("collapse_eq", &self.collapse_eq)
+            .finish()
+    }
+}
+
+impl<T: std::fmt::Debug, Idx: ArrayIndex, S: OctreeStorage> Display for Octree<T, Idx, S>
 where
     u8: AsPrimitive<Idx>,
 {
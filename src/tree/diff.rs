@@ -0,0 +1,299 @@
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use super::boolean::Operand;
+use crate::{Arena, Error, NodePoint, Octant, Octree, OctreeStorage, ProxyData};
+
+/// A single change within an [`OctreeDiff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeChange<T, Idx: ArrayIndex> {
+    /// A void node becomes a branch with eight void children.
+    Split(NodePoint<Idx>),
+    /// A branch & all of its descendants are removed, leaving a void node.
+    Merge(NodePoint<Idx>),
+    /// A void node becomes a leaf.
+    Insert(NodePoint<Idx>, T),
+    /// A leaf becomes void.
+    Remove(NodePoint<Idx>),
+    /// The data of a leaf is replaced.
+    Modify(NodePoint<Idx>, T),
+}
+
+impl<T, Idx: ArrayIndex> NodeChange<T, Idx> {
+    /// The [`NodePoint`] of the changed node.
+    #[inline]
+    pub fn point(&self) -> &NodePoint<Idx> {
+        match self {
+            Self::Split(p)
+            | Self::Merge(p)
+            | Self::Remove(p)
+            | Self::Insert(p, _)
+            | Self::Modify(p, _) => p,
+        }
+    }
+}
+
+/// The changes turning one [Octree] into another, as given by [`Octree::diff`].
+///
+/// Nodes are addressed by [`NodePoint`] rather than by index, so a diff only depends on the
+/// structure of each tree & can be applied after storage has been
+/// [defragmented](Octree::defragment), or to a copy of the source tree in another process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OctreeDiff<T, Idx: ArrayIndex> {
+    changes: Vec<NodeChange<T, Idx>>,
+}
+
+impl<T, Idx: ArrayIndex> OctreeDiff<T, Idx> {
+    /// The changes, in depth-first order by [Octant]; every change to a node precedes the changes
+    /// to its descendants.
+    #[inline]
+    pub fn changes(&self) -> &[NodeChange<T, Idx>] {
+        &self.changes
+    }
+
+    /// The number of changes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Whether the source & target trees were equal.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl<T, Idx: ArrayIndex> IntoIterator for OctreeDiff<T, Idx> {
+    type Item = NodeChange<T, Idx>;
+    type IntoIter = std::vec::IntoIter<NodeChange<T, Idx>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Record the changes turning the node `a` of `self` into the node `b` of `other`.
+    fn diff_from(
+        &self,
+        a: Operand<'_, T, Idx>,
+        other: &Self,
+        b: Operand<'_, T, Idx>,
+        point: NodePoint<Idx>,
+        changes: &mut Vec<NodeChange<T, Idx>>,
+    ) where
+        T: PartialEq + Clone,
+        u8: AsPrimitive<Idx>,
+    {
+        use Operand::{Branch, Leaf, Void};
+        match (a, b) {
+            (Void, Void) => {}
+            (Leaf(a), Leaf(b)) => {
+                if a != b {
+                    changes.push(NodeChange::Modify(point, b.clone()));
+                }
+            }
+            (Void, Leaf(b)) => changes.push(NodeChange::Insert(point, b.clone())),
+            (Leaf(_), Void) => changes.push(NodeChange::Remove(point)),
+            (Branch(a), Branch(b)) => {
+                for oct in Octant::ALL {
+                    let i = usize::from(oct);
+                    self.diff_from(
+                        self.operand(a[i]),
+                        other,
+                        other.operand(b[i]),
+                        point + oct,
+                        changes,
+                    );
+                }
+            }
+            (a, Branch(b)) => {
+                if let Leaf(_) = a {
+                    changes.push(NodeChange::Remove(point));
+                }
+                changes.push(NodeChange::Split(point));
+                for oct in Octant::ALL {
+                    let b = other.operand(b[usize::from(oct)]);
+                    self.diff_from(Void, other, b, point + oct, changes);
+                }
+            }
+            (Branch(_), b) => {
+                changes.push(NodeChange::Merge(point));
+                if let Leaf(b) = b {
+                    changes.push(NodeChange::Insert(point, b.clone()));
+                }
+            }
+        }
+    }
+
+    /// Find the changes turning `self` into `other`: inserted, removed & modified leafs, and
+    /// branches split or merged, by [`NodePoint`] relative to the root of each tree.
+    ///
+    /// Subtrees which are equal in both trees produce no changes; applying the result to `self`
+    /// through [`Self::apply_patch`] reproduces `other`.
+    pub fn diff(&self, other: &Self) -> OctreeDiff<T, Idx>
+    where
+        T: PartialEq + Clone,
+        u8: AsPrimitive<Idx>,
+    {
+        let mut changes = Vec::new();
+        self.diff_from(
+            self.operand(self.root),
+            other,
+            other.operand(other.root),
+            NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
+            &mut changes,
+        );
+        OctreeDiff { changes }
+    }
+
+    /// Get the index of the node at exactly a specific [`NodePoint`].
+    fn node_at_exact(&self, p: &NodePoint<Idx>) -> Result<Idx, Error<Idx>> {
+        let index = self.internal_node_at(&p.0.xyz(), p.0.w);
+        if self.depth_of_unchecked(index) == p.0.w {
+            Ok(index)
+        } else {
+            Err(Error::NodeNotFound(*p))
+        }
+    }
+
+    /// Apply the changes in a [diff](Self::diff) to `self`, in order.
+    ///
+    /// The result matches the diff's target tree exactly;
+    /// [auto-pruning](Self::set_auto_prune) & [collapse-on-insert](Self::set_collapse_on_insert)
+    /// aren't applied while patching.
+    ///
+    /// # Errors
+    ///
+    /// If `self` doesn't have the structure of the diff's source tree; changes preceding the
+    /// failed one remain applied.
+    ///
+    /// * [`NodeNotFound`](Error::NodeNotFound) if a changed node doesn't exist.
+    /// * [`NotAVoid`](Error::NotAVoid), [`NotALeaf`](Error::NotALeaf) or
+    ///   [`NotABranch`](Error::NotABranch) if a changed node isn't of the expected kind.
+    /// * [`IndexExhausted`](Error::IndexExhausted) if a split or inserted node wouldn't be
+    ///   addressable by `Idx`.
+    pub fn apply_patch(&mut self, patch: OctreeDiff<T, Idx>) -> Result<(), Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+    {
+        for change in patch {
+            let index = self.node_at_exact(change.point())?;
+            let data = self.proxies[index.as_()].data;
+            match (change, data) {
+                (NodeChange::Split(_), ProxyData::Void) => {
+                    self.split(index)?;
+                }
+                (NodeChange::Merge(_), ProxyData::Branch(children)) => {
+                    self.flatten_branch(index, children, ProxyData::Void);
+                }
+                (NodeChange::Insert(_, leaf), ProxyData::Void) => {
+                    self.try_reserve(0, 0, 1)?;
+                    self.proxies[index.as_()].data =
+                        ProxyData::Leaf(self.leaf_data.push(leaf).as_());
+                }
+                (NodeChange::Remove(_), ProxyData::Leaf(l_idx)) => {
+                    self.proxies[index.as_()].data = ProxyData::Void;
+                    self.leaf_data.remove(l_idx.as_());
+                }
+                (NodeChange::Modify(_, leaf), ProxyData::Leaf(l_idx)) => {
+                    self.leaf_data[l_idx.as_()] = leaf;
+                }
+                (NodeChange::Split(_) | NodeChange::Insert(..), _) => {
+                    return Err(Error::NotAVoid(index))
+                }
+                (NodeChange::Merge(_), _) => return Err(Error::NotABranch(index)),
+                (NodeChange::Remove(_) | NodeChange::Modify(..), _) => {
+                    return Err(Error::NotALeaf(index))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, NodeChange, NodePoint, Octree, OctreeSlice, VecStorage};
+
+    /// Leafs at `root[0]`, `root[3][5]` & `root[7]`.
+    fn source() -> Octree<u8, u32> {
+        let mut tree = Octree::new();
+        let root = *tree.split(0).unwrap().0;
+        tree.set_leaf(root[0], 1);
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.set_leaf(inner[5], 2);
+        tree.set_leaf(root[7], 3);
+        tree
+    }
+
+    /// Leafs at `root[0]` (modified), `root[3]` (merged) & `root[7][1]` (split).
+    fn target() -> Octree<u8, u32> {
+        let mut tree = Octree::new();
+        let root = *tree.split(0).unwrap().0;
+        tree.set_leaf(root[0], 4);
+        tree.set_leaf(root[3], 5);
+        let inner = *tree.split(root[7]).unwrap().0;
+        tree.set_leaf(inner[1], 6);
+        tree
+    }
+
+    #[test]
+    fn diff() {
+        let (source, target) = (source(), target());
+        assert!(source.diff(&source).is_empty());
+
+        let diff = source.diff(&target);
+        assert_eq!(
+            diff.changes(),
+            [
+                NodeChange::Modify(NodePoint::new(0, 0, 0, 1), 4),
+                NodeChange::Merge(NodePoint::new(0, 1, 1, 1)),
+                NodeChange::Insert(NodePoint::new(0, 1, 1, 1), 5),
+                NodeChange::Remove(NodePoint::new(1, 1, 1, 1)),
+                NodeChange::Split(NodePoint::new(1, 1, 1, 1)),
+                NodeChange::Insert(NodePoint::new(2, 2, 3, 2), 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_patch() {
+        let target = target();
+        let mut tree = source();
+        let diff = tree.diff(&target);
+        // reshuffle storage, which the diff doesn't depend on
+        tree.remove(tree.node_at(&NodePoint::new(0, 0, 0, 1)));
+        tree.set_leaf(tree.node_at(&NodePoint::new(0, 0, 0, 1)), 1);
+        tree.compress();
+        tree.apply_patch(diff.clone()).unwrap();
+        assert!(tree.leaf_dfi().eq(target.leaf_dfi()));
+        assert!(tree.diff(&target).is_empty());
+
+        // the patch no longer matches its source
+        assert!(tree.apply_patch(diff).is_err());
+    }
+
+    #[test]
+    fn index_exhausted() {
+        let mut leaf = Octree::<u8, u8, VecStorage>::new();
+        leaf.set_leaf(0, 1);
+        let void = Octree::new();
+        let (insert, remove) = (void.diff(&leaf), leaf.diff(&void));
+        // `VecStorage` doesn't reuse removed slots, so every insertion takes a new index
+        let mut tree = Octree::<u8, u8, VecStorage>::new();
+        for _ in 0..256 {
+            tree.apply_patch(insert.clone()).unwrap();
+            tree.apply_patch(remove.clone()).unwrap();
+        }
+        assert!(matches!(
+            tree.apply_patch(insert),
+            Err(Error::IndexExhausted(256))
+        ));
+        assert!(tree.leaf_dfi().next().is_none());
+    }
+}
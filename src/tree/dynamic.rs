@@ -0,0 +1,249 @@
+use std::ops::Range;
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{Entry, Error, NodePoint, Octree, ProxyData};
+
+/// An [Octree] which starts out with the narrowest index type, and is
+/// [upcast](Octree::upcast) to the next wider one whenever an insertion would exhaust its
+/// current one.
+///
+/// Since upcasting reassigns node indices, nodes are addressed by [`NodePoint`] instead of by
+/// index.
+#[derive(Debug)]
+pub enum DynOctree<T> {
+    /// Up to 256 nodes; every tree starts here.
+    U8(Octree<T, u8>),
+    /// Up to 65536 nodes.
+    U16(Octree<T, u16>),
+    /// Up to 2³² nodes.
+    U32(Octree<T, u32>),
+    /// The widest index type, which is never upcast.
+    Usize(Octree<T, usize>),
+}
+
+impl<T> Default for DynOctree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Evaluate `$e` with `$tree` bound to the [Octree] within a [`DynOctree`].
+macro_rules! with_tree {
+    ($dyn:expr, $tree:ident => $e:expr) => {
+        match $dyn {
+            DynOctree::U8($tree) => $e,
+            DynOctree::U16($tree) => $e,
+            DynOctree::U32($tree) => $e,
+            DynOctree::Usize($tree) => $e,
+        }
+    };
+}
+
+/// Convert `p` to a [`NodePoint`] within a tree indexed by `Idx`, if its depth is small enough for
+/// its grid to be addressable by `Idx`.
+///
+/// `p` must already lie within its grid.
+fn narrow<Idx: ArrayIndex>(p: &NodePoint<usize>) -> Option<NodePoint<Idx>>
+where
+    usize: AsPrimitive<Idx>,
+{
+    (p.0.w < std::mem::size_of::<Idx>() * 8).then(|| NodePoint(p.0.map(|c| c.as_())))
+}
+
+/// Whether leaf data could be set at `p` without exhausting `Idx`.
+fn has_room<T, Idx: ArrayIndex>(tree: &mut Octree<T, Idx>, p: &NodePoint<usize>) -> bool
+where
+    usize: AsPrimitive<Idx>,
+{
+    let Some(p) = narrow(p) else {
+        return false;
+    };
+    match tree.entry(p) {
+        Ok(Entry::Vacant(mut e)) => e.try_reserve().is_ok(),
+        // errors other than exhaustion are reported by `set_in`
+        _ => true,
+    }
+}
+
+fn set_in<T, Idx: ArrayIndex + AsPrimitive<usize>>(
+    tree: &mut Octree<T, Idx>,
+    p: &NodePoint<usize>,
+    data: T,
+) -> Result<Option<T>, Error<usize>>
+where
+    usize: AsPrimitive<Idx>,
+    Range<Idx>: Iterator,
+{
+    let p = narrow(p).ok_or(Error::IndexExhausted(p.0.w))?;
+    Ok(match tree.entry(p).map_err(Error::upcast)? {
+        Entry::Occupied(mut e) => Some(e.insert(data)),
+        Entry::Vacant(e) => {
+            e.insert(data);
+            None
+        }
+    })
+}
+
+fn get_in<'tree, T, Idx: ArrayIndex>(
+    tree: &'tree Octree<T, Idx>,
+    p: &NodePoint<usize>,
+) -> Option<&'tree T>
+where
+    usize: AsPrimitive<Idx>,
+{
+    let p = narrow::<Idx>(p)?;
+    let index = tree.internal_node_at(&p.0.xyz(), p.0.w);
+    match tree.proxies[index.as_()].data {
+        ProxyData::Leaf(l_idx) if tree.depth_of_unchecked(index) == p.0.w => {
+            Some(&tree.leaf_data[l_idx.as_()])
+        }
+        _ => None,
+    }
+}
+
+fn remove_in<T, Idx: ArrayIndex + AsPrimitive<usize>>(
+    tree: &mut Octree<T, Idx>,
+    p: &NodePoint<usize>,
+) -> Result<Option<T>, Error<usize>>
+where
+    usize: AsPrimitive<Idx>,
+{
+    match narrow(p) {
+        Some(p) => Ok(tree.entry(p).map_err(Error::upcast)?.remove()),
+        // nothing is stored deeper than `Idx` can address
+        None => Ok(None),
+    }
+}
+
+impl<T> DynOctree<T> {
+    /// Construct a new tree with a void root, indexed by `u8`.
+    #[inline]
+    pub fn new() -> Self {
+        Self::U8(Octree::new())
+    }
+
+    /// The size, in bytes, of the current index type.
+    #[inline]
+    pub fn index_width(&self) -> usize {
+        match self {
+            Self::U8(_) => std::mem::size_of::<u8>(),
+            Self::U16(_) => std::mem::size_of::<u16>(),
+            Self::U32(_) => std::mem::size_of::<u32>(),
+            Self::Usize(_) => std::mem::size_of::<usize>(),
+        }
+    }
+
+    /// The number of leafs in this tree.
+    #[inline]
+    pub fn len_leafs(&self) -> usize {
+        with_tree!(self, tree => tree.leaf_data.len_init())
+    }
+
+    /// Upcast to the next wider index type.
+    fn widen(&mut self) {
+        *self = match std::mem::take(self) {
+            Self::U8(tree) => Self::U16(tree.upcast()),
+            Self::U16(tree) => Self::U32(tree.upcast()),
+            Self::U32(tree) => Self::Usize(tree.upcast()),
+            Self::Usize(_) => unreachable!("usize indices cannot be exhausted"),
+        }
+    }
+
+    /// Convert this tree to one indexed by `usize`.
+    pub fn into_octree(self) -> Octree<T, usize> {
+        match self {
+            Self::U8(tree) => tree.upcast(),
+            Self::U16(tree) => tree.upcast(),
+            Self::U32(tree) => tree.upcast(),
+            Self::Usize(tree) => tree,
+        }
+    }
+
+    /// Get the leaf data of the node at a specific [`NodePoint`], if it's a leaf.
+    pub fn get(&self, p: &NodePoint<usize>) -> Option<&T> {
+        with_tree!(self, tree => get_in(tree, p))
+    }
+
+    /// Set the leaf data of the node at a specific [`NodePoint`], creating it if necessary, and
+    /// return its previous leaf data.
+    ///
+    /// The index type is widened first if the new nodes wouldn't be addressable by it.
+    ///
+    /// # Errors
+    ///
+    /// * See [`Octree::entry`].
+    pub fn set(&mut self, p: NodePoint<usize>, data: T) -> Result<Option<T>, Error<usize>> {
+        let size = u32::try_from(p.0.w)
+            .ok()
+            .and_then(|w| 1usize.checked_shl(w));
+        match size {
+            Some(size) if p.0.x < size && p.0.y < size && p.0.z < size => {}
+            _ => return Err(Error::VoxelOutOfGrid(size.unwrap_or(usize::MAX), p.0.xyz())),
+        }
+        while !with_tree!(self, tree => has_room(tree, &p)) {
+            self.widen();
+        }
+        with_tree!(self, tree => set_in(tree, &p, data))
+    }
+
+    /// Remove the leaf data of the node at a specific [`NodePoint`], if it's a leaf.
+    ///
+    /// # Errors
+    ///
+    /// * See [`Octree::entry`].
+    pub fn remove(&mut self, p: &NodePoint<usize>) -> Result<Option<T>, Error<usize>> {
+        with_tree!(self, tree => remove_in(tree, p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NodePoint, OctreeSlice};
+
+    use super::DynOctree;
+
+    #[test]
+    fn widen() {
+        let mut tree = DynOctree::new();
+        assert_eq!(tree.index_width(), 1);
+        let points = (0..64usize).map(|i| NodePoint::new(i % 4, (i / 4) % 4, i / 16, 3));
+        for (i, p) in points.clone().enumerate() {
+            assert_eq!(tree.set(p, i).unwrap(), None);
+        }
+        assert_eq!(tree.index_width(), 1);
+
+        // deeper than `u8` can address
+        let deep = NodePoint::new(300, 0, 0, 9);
+        assert_eq!(tree.set(deep, 64).unwrap(), None);
+        assert_eq!(tree.index_width(), 2);
+        assert_eq!(tree.get(&deep), Some(&64));
+
+        for (i, p) in points.enumerate() {
+            assert_eq!(tree.get(&p), Some(&i));
+        }
+        assert_eq!(tree.remove(&deep).unwrap(), Some(64));
+        assert_eq!(tree.get(&deep), None);
+        assert_eq!(tree.len_leafs(), 64);
+        assert!(tree.set(NodePoint::new(0, 0, 0, 2), 0).is_err());
+        assert!(tree.set(NodePoint::new(8, 0, 0, 3), 0).is_err());
+    }
+
+    #[test]
+    fn widen_full() {
+        // more nodes than `u8` can address
+        let mut tree = DynOctree::new();
+        let points = (0..512usize).map(|i| NodePoint::new(i % 8, (i / 8) % 8, i / 64, 3));
+        for (i, p) in points.clone().enumerate() {
+            tree.set(p, i).unwrap();
+        }
+        assert_eq!(tree.index_width(), 2);
+        for (i, p) in points.enumerate() {
+            assert_eq!(tree.get(&p), Some(&i));
+        }
+
+        let tree = tree.into_octree();
+        assert_eq!(tree.leaf_dfi().count(), 512);
+    }
+}
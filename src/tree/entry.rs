@@ -0,0 +1,376 @@
+use std::ops::Range;
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{
+    Arena, Error, NodePoint, Octant, Octree, OctreeSlice, OctreeStorage, ProxyData, StableStorage,
+    VoxelPoint,
+};
+
+/// A view into a single node of an [Octree], which may or may not hold leaf data; see
+/// [`Octree::entry`].
+#[derive(Debug)]
+pub enum Entry<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    Occupied(OccupiedEntry<'tree, T, Idx, S>),
+    Vacant(VacantEntry<'tree, T, Idx, S>),
+}
+
+/// A view into a leaf of an [Octree].
+#[derive(Debug)]
+pub struct OccupiedEntry<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    tree: &'tree mut Octree<T, Idx, S>,
+    point: NodePoint<Idx>,
+    index: Idx,
+    leaf: Idx,
+}
+
+/// A view into a node of an [Octree] which is void or doesn't exist yet.
+#[derive(Debug)]
+pub struct VacantEntry<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    tree: &'tree mut Octree<T, Idx, S>,
+    point: NodePoint<Idx>,
+    /// The deepest existing node encompassing `point`, which is void.
+    index: Idx,
+    depth: Idx,
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> Entry<'tree, T, Idx, S> {
+    /// The [`NodePoint`] of this entry.
+    #[inline]
+    pub fn point(&self) -> &NodePoint<Idx> {
+        match self {
+            Self::Occupied(e) => &e.point,
+            Self::Vacant(e) => &e.point,
+        }
+    }
+
+    /// Get the leaf data of this entry, inserting `default` if it's vacant.
+    ///
+    /// # Panics
+    ///
+    /// * See [`VacantEntry::insert`].
+    #[inline]
+    pub fn or_insert(self, default: T) -> &'tree mut T
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        self.or_insert_with(|| default)
+    }
+
+    /// Get the leaf data of this entry, inserting the result of `f` if it's vacant.
+    ///
+    /// # Panics
+    ///
+    /// * See [`VacantEntry::insert`].
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'tree mut T
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        match self {
+            Self::Occupied(e) => e.into_mut(),
+            Self::Vacant(e) => e.insert(f()),
+        }
+    }
+
+    /// Modify the leaf data of this entry with `f`, if it's occupied.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut T)) -> Self {
+        if let Self::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+
+    /// Remove & return the leaf data of this entry, if it's occupied.
+    #[inline]
+    pub fn remove(self) -> Option<T> {
+        match self {
+            Self::Occupied(e) => Some(e.remove()),
+            Self::Vacant(_) => None,
+        }
+    }
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> OccupiedEntry<'tree, T, Idx, S> {
+    #[inline]
+    pub fn point(&self) -> &NodePoint<Idx> {
+        &self.point
+    }
+
+    /// The index of the leaf node.
+    #[inline]
+    pub fn index(&self) -> Idx {
+        self.index
+    }
+
+    #[inline]
+    pub fn get(&self) -> &T {
+        &self.tree.leaf_data[self.leaf.as_()]
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.tree.leaf_data[self.leaf.as_()]
+    }
+
+    #[inline]
+    pub fn into_mut(self) -> &'tree mut T {
+        &mut self.tree.leaf_data[self.leaf.as_()]
+    }
+
+    /// Replace the leaf data, and return the previous leaf data.
+    #[inline]
+    pub fn insert(&mut self, data: T) -> T {
+        std::mem::replace(self.get_mut(), data)
+    }
+
+    /// Remove the leaf, and return its data.
+    ///
+    /// See [`Octree::remove`].
+    #[inline]
+    pub fn remove(self) -> T {
+        // the node is a leaf, so exactly one datum is removed
+        self.tree.remove(self.index).pop().unwrap()
+    }
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> VacantEntry<'tree, T, Idx, S> {
+    #[inline]
+    pub fn point(&self) -> &NodePoint<Idx> {
+        &self.point
+    }
+
+    /// Reserve room for the nodes needed to [insert](Self::insert) leaf data at this entry.
+    ///
+    /// # Errors
+    ///
+    /// * See [`Octree::try_reserve`].
+    pub fn try_reserve(&mut self) -> Result<(), Error<Idx>> {
+        let splits: usize = (self.point.0.w - self.depth).as_();
+        self.tree.try_reserve(splits * 8, splits, 1)
+    }
+
+    /// [Insert](Self::insert) leaf data at this entry, unless any of the new nodes wouldn't be
+    /// addressable by `Idx`.
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if any of the new nodes would exceed
+    ///   `Idx::MAX`, in which case the tree is unchanged.
+    pub fn try_insert(mut self, data: T) -> Result<&'tree mut T, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        self.try_reserve()?;
+        Ok(self.insert(data))
+    }
+
+    /// Insert leaf data at this entry, splitting any void ancestors, and return a reference to it.
+    ///
+    /// With [collapse-on-insert](Octree::set_collapse_on_insert) enabled, the new leaf may be
+    /// collapsed into an ancestor.
+    ///
+    /// # Panics
+    ///
+    /// * any of the new nodes wouldn't be addressable by `Idx`; see [`Self::try_insert`].
+    pub fn insert(self, data: T) -> &'tree mut T
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        let Self {
+            tree,
+            point,
+            mut index,
+            mut depth,
+        } = self;
+        while depth < point.0.w {
+            // every node from here down is a new void child, so only running out of indices
+            // can fail
+            index = tree
+                .split(index)
+                .expect("the new nodes should be addressable by the index type")
+                .0[usize::from(octant_at(&point, depth))];
+            depth += Idx::ONE;
+        }
+        let data_idx = tree.leaf_data.push(data);
+        tree.proxies[index.as_()].data = ProxyData::Leaf(data_idx.as_());
+        // the new leaf data keeps its slot if its node is collapsed into an ancestor
+        tree.collapse_ancestors(index);
+        &mut tree.leaf_data[data_idx]
+    }
+}
+
+/// The [Octant] containing a [`NodePoint`] within its ancestor at depth `depth`.
+#[inline]
+fn octant_at<Idx: ArrayIndex>(p: &NodePoint<Idx>, depth: Idx) -> Octant {
+    let shift = p.0.w - Idx::ONE - depth;
+    Octant::new(
+        (p.0.x >> shift) & Idx::ONE == Idx::ONE,
+        (p.0.y >> shift) & Idx::ONE == Idx::ONE,
+        (p.0.z >> shift) & Idx::ONE == Idx::ONE,
+    )
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Get the [Entry] of the node at a specific [`NodePoint`], which may not exist yet.
+    ///
+    /// Inserting into a vacant entry creates any missing ancestors by [splitting](Self::split)
+    /// void nodes.
+    ///
+    /// # Errors
+    ///
+    /// * [`VoxelOutOfGrid`](Error::VoxelOutOfGrid) if the coordinates of `point` lie outside of
+    ///   the `2ᵈᵉᵖᵗʰ` grid at its depth, or if that grid is too large for its size to be
+    ///   representable by `Idx`.
+    /// * [`CannotSplitLeaf`](Error::CannotSplitLeaf) if an ancestor of the node is a leaf.
+    /// * [`CannotInsertIntoBranch`](Error::CannotInsertIntoBranch) if the node is a branch.
+    pub fn entry(&mut self, point: NodePoint<Idx>) -> Result<Entry<'_, T, Idx, S>, Error<Idx>> {
+        if AsPrimitive::<usize>::as_(point.0.w) >= Idx::ZERO.count_zeros() as usize {
+            return Err(Error::VoxelOutOfGrid(Idx::max_value(), point.0.xyz()));
+        }
+        let size = Idx::ONE << point.0.w;
+        if point.0.x >= size || point.0.y >= size || point.0.z >= size {
+            return Err(Error::VoxelOutOfGrid(size, point.0.xyz()));
+        }
+        let mut index = self.root;
+        let mut depth = Idx::ZERO;
+        loop {
+            match self.proxies[index.as_()].data {
+                ProxyData::Leaf(leaf) if depth == point.0.w => {
+                    return Ok(Entry::Occupied(OccupiedEntry {
+                        tree: self,
+                        point,
+                        index,
+                        leaf,
+                    }))
+                }
+                ProxyData::Leaf(_) => return Err(Error::CannotSplitLeaf),
+                ProxyData::Void => {
+                    return Ok(Entry::Vacant(VacantEntry {
+                        tree: self,
+                        point,
+                        index,
+                        depth,
+                    }))
+                }
+                ProxyData::Branch(_) if depth == point.0.w => {
+                    return Err(Error::CannotInsertIntoBranch)
+                }
+                ProxyData::Branch(b_idx) => {
+                    index = self.branch_data[b_idx.as_()][usize::from(octant_at(&point, depth))];
+                    depth += Idx::ONE;
+                }
+            }
+        }
+    }
+
+    /// The [`NodePoint`] of a voxel at the depth of the current voxel grid.
+    fn grid_point(&self, p: &VoxelPoint<Idx>) -> NodePoint<Idx> {
+        NodePoint::new(p.x, p.y, p.z, self.height())
+    }
+
+    /// Set the leaf data of the voxel at `p` within the current voxel grid, creating it if
+    /// necessary, and return its previous leaf data.
+    ///
+    /// *Warning*: this requires knowing the height of the tree, which can be an expensive
+    /// calculation.
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::entry`] & [`VacantEntry::try_insert`].
+    pub fn insert_at(&mut self, p: &VoxelPoint<Idx>, data: T) -> Result<Option<T>, Error<Idx>>
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        Ok(match self.entry(self.grid_point(p))? {
+            Entry::Occupied(mut e) => Some(e.insert(data)),
+            Entry::Vacant(e) => {
+                e.try_insert(data)?;
+                None
+            }
+        })
+    }
+
+    /// Remove the leaf data of the voxel at `p` within the current voxel grid, if extant.
+    ///
+    /// *Warning*: this requires knowing the height of the tree, which can be an expensive
+    /// calculation.
+    ///
+    /// # Errors
+    ///
+    /// * See [`Self::entry`].
+    pub fn remove_at(&mut self, p: &VoxelPoint<Idx>) -> Result<Option<T>, Error<Idx>> {
+        Ok(self.entry(self.grid_point(p))?.remove())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entry, Error, NodePoint, Octree, OctreeSlice, VoxelPoint};
+
+    #[test]
+    fn entry() {
+        let mut tree = Octree::<u8, u32>::new();
+        let p = NodePoint::new(3, 1, 2, 2);
+        assert!(matches!(tree.entry(p).unwrap(), Entry::Vacant(_)));
+        assert_eq!(tree.proxies().len_init(), 1);
+
+        assert_eq!(*tree.entry(p).unwrap().or_insert(1), 1);
+        assert_eq!(tree.height(), 2);
+        assert_eq!(*tree.entry(p).unwrap().or_insert(2), 1);
+        *tree
+            .entry(p)
+            .unwrap()
+            .and_modify(|l| *l += 10)
+            .or_insert_with(|| unreachable!()) += 1;
+        assert!(tree.leaf_dfi().eq([(&12, p)]));
+
+        assert!(matches!(
+            tree.entry(NodePoint::new(1, 0, 1, 1)),
+            Err(Error::CannotInsertIntoBranch)
+        ));
+        assert!(matches!(
+            tree.entry(NodePoint::new(6, 2, 4, 3)),
+            Err(Error::CannotSplitLeaf)
+        ));
+        assert!(matches!(
+            tree.entry(NodePoint::new(4, 0, 0, 2)),
+            Err(Error::VoxelOutOfGrid(4, _))
+        ));
+        assert!(matches!(
+            tree.entry(NodePoint::new(0, 0, 0, 32)),
+            Err(Error::VoxelOutOfGrid(u32::MAX, _))
+        ));
+        assert!(matches!(
+            tree.entry(NodePoint::new(0, 0, 0, u32::MAX)),
+            Err(Error::VoxelOutOfGrid(u32::MAX, _))
+        ));
+
+        assert_eq!(tree.entry(p).unwrap().remove(), Some(12));
+        assert_eq!(tree.entry(p).unwrap().remove(), None);
+    }
+
+    #[test]
+    fn insert_at() {
+        let mut tree = Octree::<u8, u32>::new();
+        assert_eq!(tree.insert_at(&VoxelPoint::new(0, 0, 0), 1).unwrap(), None);
+        assert!(tree.insert_at(&VoxelPoint::new(1, 0, 0), 1).is_err());
+
+        tree.remove_at(&VoxelPoint::new(0, 0, 0)).unwrap();
+        tree.entry(NodePoint::new(0, 0, 0, 2)).unwrap().or_insert(1);
+        assert_eq!(tree.insert_at(&VoxelPoint::new(3, 2, 1), 2).unwrap(), None);
+        assert_eq!(
+            tree.insert_at(&VoxelPoint::new(3, 2, 1), 3).unwrap(),
+            Some(2)
+        );
+        assert_eq!(tree.leaf_dfi().count(), 2);
+        assert_eq!(tree.remove_at(&VoxelPoint::new(3, 2, 1)).unwrap(), Some(3));
+        assert_eq!(tree.remove_at(&VoxelPoint::new(3, 2, 1)).unwrap(), None);
+        assert!(tree.leaf_dfi().eq([(&1, NodePoint::new(0, 0, 0, 2))]));
+    }
+}
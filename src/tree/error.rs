@@ -1,4 +1,5 @@
 use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
 
 /// Errors related to [Octrees](crate::Octree).
 #[derive(Debug, thiserror::Error)]
@@ -27,4 +28,71 @@ pub enum Error<Idx: ArrayIndex> {
     CannotSplitLeaf,
     #[error("Attempted to set leaf data of branch")]
     CannotInsertIntoBranch,
+    #[error("No node exists at {0:?}")]
+    NodeNotFound(crate::NodePoint<Idx>),
+    #[error(
+        "Attempted to store data at index {0}, which is not representable by the tree's index type"
+    )]
+    IndexExhausted(usize),
+}
+
+impl<Idx: ArrayIndex> Error<Idx> {
+    /// Convert this error to one with a wider index type.
+    pub fn upcast<NIdx: ArrayIndex>(self) -> Error<NIdx>
+    where
+        Idx: AsPrimitive<NIdx>,
+    {
+        match self {
+            Self::NoParent => Error::NoParent,
+            Self::ChildOutOfRange(c) => Error::ChildOutOfRange(c),
+            Self::InvalidIndex(i) => Error::InvalidIndex(i.as_()),
+            Self::NotABranch(i) => Error::NotABranch(i.as_()),
+            Self::NotALeaf(i) => Error::NotALeaf(i.as_()),
+            Self::NotAVoid(i) => Error::NotAVoid(i.as_()),
+            Self::NoChildren(i) => Error::NoChildren(i.as_()),
+            Self::NoLeafs(i) => Error::NoLeafs(i.as_()),
+            Self::VoxelOutOfGrid(size, p) => Error::VoxelOutOfGrid(size.as_(), p.map(|c| c.as_())),
+            Self::ChildCollision(i) => Error::ChildCollision(i.as_()),
+            Self::CannotSplitLeaf => Error::CannotSplitLeaf,
+            Self::CannotInsertIntoBranch => Error::CannotInsertIntoBranch,
+            Self::NodeNotFound(p) => Error::NodeNotFound(crate::NodePoint(p.0.map(|c| c.as_()))),
+            Self::IndexExhausted(i) => Error::IndexExhausted(i),
+        }
+    }
+}
+
+/// Violations of the structural invariants of an [Octree](crate::Octree); see
+/// [`Octree::validate`](crate::Octree::validate).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvariantError<Idx: ArrayIndex> {
+    #[error("The {0} arena's count of occupied entries disagrees with its contents")]
+    InconsistentArena(&'static str),
+    #[error("Root index {0:?} is unoccupied")]
+    InvalidRoot(Idx),
+    #[error("Root node {root:?} has a parent: {parent:?}")]
+    RootHasParent { root: Idx, parent: Idx },
+    #[error("Branch {node:?} refers to unoccupied child {child:?}")]
+    MissingChild { node: Idx, child: Idx },
+    #[error("Node {node:?} is a child of {expected:?}, but its parent is {found:?}")]
+    ParentMismatch {
+        node: Idx,
+        expected: Idx,
+        found: Idx,
+    },
+    #[error("Branch {node:?} refers to unoccupied branch data {branch:?}")]
+    InvalidBranchData { node: Idx, branch: Idx },
+    #[error("Leaf {node:?} refers to unoccupied leaf data {leaf:?}")]
+    InvalidLeafData { node: Idx, leaf: Idx },
+    #[error("Node {0:?} is reachable by more than one path")]
+    SharedNode(Idx),
+    #[error("Branch data {0:?} is used by more than one node")]
+    SharedBranchData(Idx),
+    #[error("Leaf data {0:?} is used by more than one node")]
+    SharedLeafData(Idx),
+    #[error("{0} nodes are unreachable from the root")]
+    OrphanedNodes(usize),
+    #[error("{0} branch data entries are unused")]
+    OrphanedBranchData(usize),
+    #[error("{0} leaf data entries are unused")]
+    OrphanedLeafData(usize),
 }
@@ -0,0 +1,407 @@
+use std::{
+    io::{self, Read, Write},
+    mem,
+    ops::Range,
+};
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{Arena, Error, Octree, OctreeStorage, ProxyData};
+
+/// Magic bytes at the start of every file in the eightfold binary format.
+pub const FORMAT_MAGIC: [u8; 4] = *b"8fld";
+/// The current version of the eightfold binary format.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Header flag indicating that a spatial header follows the base header.
+pub(crate) const FLAG_SPATIAL: u8 = 0b1;
+
+/// Node kinds within the structure bitstream.
+const NODE_VOID: u8 = 0b00;
+const NODE_LEAF: u8 = 0b01;
+const NODE_BRANCH: u8 = 0b10;
+
+/// Errors related to reading & writing the eightfold binary format.
+#[derive(Debug, thiserror::Error)]
+pub enum FormatError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Not an eightfold octree (magic bytes: {0:?})")]
+    BadMagic([u8; 4]),
+    #[error("Unsupported format version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Index width mismatch: expected {expected} bytes, found {found}")]
+    IndexWidth { expected: u8, found: u8 },
+    #[error("Real width mismatch: expected {expected} bytes, found {found}")]
+    RealWidth { expected: u8, found: u8 },
+    #[error("Attempted to read a spatial tree from a file without a spatial header")]
+    NotSpatial,
+    #[error("Invalid node kind in structure: {0:#04b}")]
+    InvalidNode(u8),
+    #[error("Tree has too many nodes for its index type")]
+    IndexOverflow,
+    #[error("Invalid spatial header: {0}")]
+    InvalidSpatialHeader(&'static str),
+    #[error("Leaf size mismatch: expected {expected} bytes, found {found}")]
+    LeafSize { expected: usize, found: usize },
+    #[error("Data truncated: expected {expected} bytes, found {found}")]
+    Truncated { expected: usize, found: usize },
+    #[error("Invalid tree height: {0}")]
+    InvalidHeight(u32),
+}
+
+/// An encoding of leaf data for the eightfold binary format.
+///
+/// See [`Octree::write_to`].
+pub trait LeafCodec<T> {
+    /// Write a single leaf.
+    ///
+    /// # Errors
+    ///
+    /// * Any error produced by `w`, or by `self` while encoding.
+    fn encode<W: Write>(&mut self, leaf: &T, w: &mut W) -> io::Result<()>;
+    /// Read a single leaf, as written by [`Self::encode`].
+    ///
+    /// # Errors
+    ///
+    /// * Any error produced by `r`, or by `self` while decoding.
+    fn decode<R: Read>(&mut self, r: &mut R) -> io::Result<T>;
+}
+
+/// A [`LeafCodec`] for primitive numbers, using their little-endian byte representation.
+///
+/// `()` is encoded as zero bytes, for trees which only store occupancy.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LittleEndian;
+
+macro_rules! le_codec_impl {
+    ($($t:ty),*) => {$(
+        impl LeafCodec<$t> for LittleEndian {
+            #[inline]
+            fn encode<W: Write>(&mut self, leaf: &$t, w: &mut W) -> io::Result<()> {
+                w.write_all(&leaf.to_le_bytes())
+            }
+            #[inline]
+            fn decode<R: Read>(&mut self, r: &mut R) -> io::Result<$t> {
+                let mut buf = [0; mem::size_of::<$t>()];
+                r.read_exact(&mut buf)?;
+                Ok(<$t>::from_le_bytes(buf))
+            }
+        }
+    )*};
+}
+
+le_codec_impl!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl LeafCodec<()> for LittleEndian {
+    #[inline]
+    fn encode<W: Write>(&mut self, _: &(), _: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+    #[inline]
+    fn decode<R: Read>(&mut self, _: &mut R) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write the base header.
+pub(crate) fn write_header<Idx: ArrayIndex, W: Write>(w: &mut W, flags: u8) -> io::Result<()> {
+    w.write_all(&FORMAT_MAGIC)?;
+    w.write_all(&[FORMAT_VERSION, mem::size_of::<Idx>() as u8, flags])
+}
+
+/// Read & check the base header, returning its flags.
+pub(crate) fn read_header<Idx: ArrayIndex, R: Read>(r: &mut R) -> Result<u8, FormatError> {
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if magic != FORMAT_MAGIC {
+        return Err(FormatError::BadMagic(magic));
+    }
+    let mut buf = [0; 3];
+    r.read_exact(&mut buf)?;
+    let [version, idx_width, flags] = buf;
+    if version != FORMAT_VERSION {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+    let expected = mem::size_of::<Idx>() as u8;
+    if idx_width != expected {
+        return Err(FormatError::IndexWidth {
+            expected,
+            found: idx_width,
+        });
+    }
+    Ok(flags)
+}
+
+/// Writes 2-bit node kinds, packed from least to most significant bit.
+struct KindWriter<'w, W: Write> {
+    w: &'w mut W,
+    byte: u8,
+    shift: u8,
+}
+
+impl<'w, W: Write> KindWriter<'w, W> {
+    fn push(&mut self, kind: u8) -> io::Result<()> {
+        self.byte |= kind << self.shift;
+        self.shift += 2;
+        if self.shift == 8 {
+            self.w.write_all(&[self.byte])?;
+            (self.byte, self.shift) = (0, 0);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        if self.shift == 0 {
+            return Ok(());
+        }
+        self.w.write_all(&[self.byte])
+    }
+}
+
+/// Reads 2-bit node kinds, as written by [`KindWriter`].
+struct KindReader<'r, R: Read> {
+    r: &'r mut R,
+    byte: u8,
+    shift: u8,
+}
+
+impl<'r, R: Read> KindReader<'r, R> {
+    fn next(&mut self) -> io::Result<u8> {
+        if self.shift == 0 {
+            let mut buf = [0];
+            self.r.read_exact(&mut buf)?;
+            self.byte = buf[0];
+        }
+        let res = (self.byte >> self.shift) & 0b11;
+        self.shift = (self.shift + 2) % 8;
+        Ok(res)
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Write the structure & leaf data of `self`, without any header.
+    pub(crate) fn write_body<W: Write, C: LeafCodec<T>>(
+        &self,
+        w: &mut W,
+        codec: &mut C,
+    ) -> io::Result<()> {
+        let mut kinds = KindWriter {
+            w: &mut *w,
+            byte: 0,
+            shift: 0,
+        };
+        let mut leafs = Vec::with_capacity(self.leaf_data.len_init());
+        let mut stack = vec![self.root];
+        while let Some(idx) = stack.pop() {
+            match self.proxies[idx.as_()].data {
+                ProxyData::Void => kinds.push(NODE_VOID)?,
+                ProxyData::Leaf(l_idx) => {
+                    kinds.push(NODE_LEAF)?;
+                    leafs.push(l_idx);
+                }
+                ProxyData::Branch(b_idx) => {
+                    kinds.push(NODE_BRANCH)?;
+                    stack.extend(self.branch_data[b_idx.as_()].iter().rev());
+                }
+            }
+        }
+        kinds.finish()?;
+        for l_idx in leafs {
+            codec.encode(&self.leaf_data[l_idx.as_()], w)?;
+        }
+        Ok(())
+    }
+
+    /// Read a tree written by [`Self::write_body`].
+    pub(crate) fn read_body<R: Read, C: LeafCodec<T>>(
+        r: &mut R,
+        codec: &mut C,
+    ) -> Result<Self, FormatError>
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        let mut res = Self::new();
+        let mut kinds = KindReader {
+            r: &mut *r,
+            byte: 0,
+            shift: 0,
+        };
+        let mut leafs = Vec::new();
+        let mut stack = vec![res.root];
+        while let Some(idx) = stack.pop() {
+            match kinds.next()? {
+                NODE_VOID => {}
+                NODE_LEAF => leafs.push(idx),
+                NODE_BRANCH => {
+                    let (children, _) = res.split(idx).map_err(|e| match e {
+                        Error::IndexExhausted(_) => FormatError::IndexOverflow,
+                        // `idx` is always a new void, so it can't be a leaf
+                        e => unreachable!("{e:?}"),
+                    })?;
+                    stack.extend(children.iter().rev());
+                }
+                kind => return Err(FormatError::InvalidNode(kind)),
+            }
+        }
+        for idx in leafs {
+            res.set_leaf(idx, codec.decode(r)?);
+        }
+        Ok(res)
+    }
+
+    /// Write `self` in the eightfold binary format, using `codec` to encode leaf data.
+    ///
+    /// # Layout
+    ///
+    /// All integers are little-endian.
+    ///
+    /// | Field          | Size           | Contents                                              |
+    /// |----------------|----------------|-------------------------------------------------------|
+    /// | magic          | 4              | [`FORMAT_MAGIC`]                                      |
+    /// | version        | 1              | [`FORMAT_VERSION`]                                    |
+    /// | index width    | 1              | `size_of::<Idx>()`                                    |
+    /// | flags          | 1              | bit 0: a spatial header follows                       |
+    /// | spatial header | `9 + 9 × real` | see [`VoxelOctree::write_to`](crate::spatial::VoxelOctree::write_to) |
+    /// | structure      | `⌈nodes / 4⌉`  | 2 bits per node: `0` void, `1` leaf, `2` branch       |
+    /// | leafs          | …              | leaf data, as written by `codec`                      |
+    ///
+    /// Nodes are ordered depth-first by [Octant](crate::Octant) ordering, and packed from least to
+    /// most significant bit; leafs are given in the same order. No indices are stored, so the
+    /// layout of the tree in memory isn't preserved.
+    ///
+    /// # Errors
+    ///
+    /// * Any error produced by `w` or `codec`.
+    pub fn write_to<W: Write, C: LeafCodec<T>>(
+        &self,
+        mut w: W,
+        codec: &mut C,
+    ) -> Result<(), FormatError> {
+        write_header::<Idx, W>(&mut w, 0)?;
+        self.write_body(&mut w, codec)?;
+        Ok(())
+    }
+
+    /// Read a tree written by [`Self::write_to`], using `codec` to decode leaf data.
+    ///
+    /// The spatial header of a file written by a
+    /// [`VoxelOctree`](crate::spatial::VoxelOctree) is skipped.
+    ///
+    /// # Errors
+    ///
+    /// * [`Io`](FormatError::Io) on errors produced by `r` or `codec`, including unexpected EOF.
+    /// * [`BadMagic`](FormatError::BadMagic), [`UnsupportedVersion`](FormatError::UnsupportedVersion)
+    ///   or [`IndexWidth`](FormatError::IndexWidth) if the header doesn't match.
+    /// * [`InvalidNode`](FormatError::InvalidNode) if the structure is corrupt.
+    /// * [`IndexOverflow`](FormatError::IndexOverflow) if the tree doesn't fit in `Idx`.
+    pub fn read_from<R: Read, C: LeafCodec<T>>(mut r: R, codec: &mut C) -> Result<Self, FormatError>
+    where
+        usize: AsPrimitive<Idx>,
+        Range<Idx>: Iterator,
+    {
+        if read_header::<Idx, R>(&mut r)? & FLAG_SPATIAL != 0 {
+            let mut real_width = [0];
+            r.read_exact(&mut real_width)?;
+            let len = 8 + 9 * u64::from(real_width[0]);
+            io::copy(&mut r.by_ref().take(len), &mut io::sink())?;
+        }
+        Self::read_body(&mut r, codec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FormatError, LittleEndian, Octree, OctreeSlice, FORMAT_MAGIC, FORMAT_VERSION};
+
+    /// Build a tree of height 2, with leafs at `root[6]`, `root[3][0]` & `root[3][5]`.
+    fn tree() -> Octree<u32, u16> {
+        let mut tree = Octree::<u32, u16>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.set_leaf(root[6], 6);
+        tree.set_leaf(inner[0], 30);
+        tree.set_leaf(inner[5], 35);
+        tree
+    }
+
+    fn write(tree: &Octree<u32, u16>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        tree.write_to(&mut buf, &mut LittleEndian).unwrap();
+        buf
+    }
+
+    #[test]
+    fn round_trip() {
+        let tree = tree();
+        let buf = write(&tree);
+        // header + 17 nodes + 3 leafs
+        assert_eq!(buf.len(), 7 + 5 + 3 * 4);
+        let res = Octree::<u32, u16>::read_from(buf.as_slice(), &mut LittleEndian).unwrap();
+        assert!(res.leaf_dfi().eq(tree.leaf_dfi()));
+        assert_eq!(res.node_dfi().count(), tree.node_dfi().count());
+    }
+
+    #[test]
+    fn header() {
+        let mut buf = write(&tree());
+        assert!(matches!(
+            Octree::<u32, u8>::read_from(buf.as_slice(), &mut LittleEndian),
+            Err(FormatError::IndexWidth {
+                expected: 1,
+                found: 2
+            })
+        ));
+        buf[4] = 0;
+        assert!(matches!(
+            Octree::<u32, u16>::read_from(buf.as_slice(), &mut LittleEndian),
+            Err(FormatError::UnsupportedVersion(0))
+        ));
+        buf[0] = 0;
+        assert!(matches!(
+            Octree::<u32, u16>::read_from(buf.as_slice(), &mut LittleEndian),
+            Err(FormatError::BadMagic(_))
+        ));
+    }
+
+    #[test]
+    fn corrupt() {
+        let buf = write(&tree());
+        // truncated leaf data
+        assert!(matches!(
+            Octree::<u32, u16>::read_from(&buf[..buf.len() - 1], &mut LittleEndian),
+            Err(FormatError::Io(_))
+        ));
+        // invalid node kind at the root
+        let mut bad = buf.clone();
+        bad[7] |= 0b11;
+        assert!(matches!(
+            Octree::<u32, u16>::read_from(bad.as_slice(), &mut LittleEndian),
+            Err(FormatError::InvalidNode(0b11))
+        ));
+        // more nodes than fit in the index type
+        let mut deep = FORMAT_MAGIC.to_vec();
+        deep.extend([FORMAT_VERSION, 1, 0]);
+        deep.extend([0b1010_1010; 16]);
+        assert!(matches!(
+            Octree::<(), u8>::read_from(deep.as_slice(), &mut LittleEndian),
+            Err(FormatError::IndexOverflow)
+        ));
+    }
+
+    #[test]
+    fn full_index_range() {
+        // a chain of as many branches as fit in the index type
+        let mut tree = Octree::<(), u8>::new();
+        let mut target = tree.root_idx();
+        while let Ok((children, _)) = tree.split(target) {
+            target = children[0];
+        }
+        let mut buf = Vec::new();
+        tree.write_to(&mut buf, &mut LittleEndian).unwrap();
+        let res = Octree::<(), u8>::read_from(buf.as_slice(), &mut LittleEndian).unwrap();
+        assert_eq!(res.proxies().len_init(), tree.proxies().len_init());
+    }
+}
@@ -1,46 +1,69 @@
-use std::iter::FusedIterator;
+use std::{
+    collections::{HashSet, VecDeque},
+    iter::FusedIterator,
+    marker::PhantomData,
+};
 
 use eightfold_common::ArrayIndex;
 use num_traits::AsPrimitive;
 
-use crate::{NodePoint, Octant, Octree, Proxy, ProxyData};
+use crate::{
+    Arena, NodePoint, Octant, Octree, OctreeSlice, OctreeStorage, Proxy, ProxyData, StableStorage,
+};
 
-pub struct NodeIter<'tree, T, Idx: ArrayIndex> {
-    pub(crate) tree: &'tree Octree<T, Idx>,
-    pub(crate) node_stack: Vec<(&'tree Proxy<Idx>, Octant, NodePoint<Idx>)>,
-    pub(crate) curr_node: Option<(&'tree Proxy<Idx>, Octant, NodePoint<Idx>)>,
+/// A depth-first iterator over nodes in an [`OctreeSlice`], yielding each node & its [`NodePoint`]
+/// by [Octant] ordering.
+pub struct NodeIter<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized = Octree<T, Idx>> {
+    pub(crate) slice: &'a Sl,
+    pub(crate) node_stack: Vec<(Sl::Node<'a>, Octant, NodePoint<Idx>)>,
+    pub(crate) curr_node: Option<(Sl::Node<'a>, Octant, NodePoint<Idx>)>,
+    pub(crate) _data: PhantomData<&'a T>,
 }
 
-impl<'tree, T, Idx: ArrayIndex> FusedIterator for NodeIter<'tree, T, Idx> where u8: AsPrimitive<Idx> {}
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> NodeIter<'a, T, Idx, Sl> {
+    pub(crate) fn new(slice: &'a Sl) -> Self {
+        Self {
+            slice,
+            node_stack: Vec::default(),
+            curr_node: Some((slice.root_node(), Octant(0), slice.root_point())),
+            _data: PhantomData,
+        }
+    }
+}
 
-impl<'tree, T, Idx: ArrayIndex> Iterator for NodeIter<'tree, T, Idx>
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> FusedIterator
+    for NodeIter<'a, T, Idx, Sl>
 where
     u8: AsPrimitive<Idx>,
 {
-    type Item = (Proxy<Idx>, NodePoint<Idx>);
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> Iterator for NodeIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+    type Item = (Sl::Node<'a>, NodePoint<Idx>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        #[warn(clippy::never_loop)]
-        while let Some((prox, oct, np)) = self.curr_node {
-            match prox.data {
-                ProxyData::Void | ProxyData::Leaf(_) => {
+        while let Some((node, oct, np)) = self.curr_node {
+            match self.slice.children_of(node) {
+                None => {
                     self.curr_node = self.node_stack.pop();
-                    return Some((*prox, np));
+                    return Some((node, np));
                 }
-                ProxyData::Branch(ch_idx) => {
-                    let children: &[Idx; 8] = &self.tree.branch_data[ch_idx.as_()];
+                Some(children) => {
                     // move the cursor to the next child node (ordered by `oct`)
-                    self.curr_node = Some((
-                        &self.tree.proxies[children[usize::from(oct)].as_()],
-                        Octant(0),
-                        np + oct,
-                    ));
+                    self.curr_node = Some((children[usize::from(oct)], Octant(0), np + oct));
                     if oct < Octant::MAX {
                         // if we haven't checked all children of this node,
                         // put it on the top of the node stack
-                        self.node_stack.push((prox, Octant(oct.0 + 1), np));
+                        self.node_stack.push((node, Octant(oct.0 + 1), np));
+                    }
+                    // output each branch before its first child, but not again when returning
+                    // to it for the rest
+                    if oct == Octant(0) {
+                        return Some((node, np));
                     }
-                    return Some((*prox, np));
                 }
             }
         }
@@ -48,48 +71,386 @@ where
     }
 }
 
-/// A depth-first iterator over leafs in an [Octree].
-pub struct LeafIter<'tree, T, Idx: ArrayIndex> {
-    pub(crate) tree: &'tree Octree<T, Idx>,
-    pub(crate) node_stack: Vec<(&'tree Proxy<Idx>, Octant, NodePoint<Idx>)>,
-    pub(crate) curr_node: Option<(&'tree Proxy<Idx>, Octant, NodePoint<Idx>)>,
+/// A depth-first iterator over leafs in an [`OctreeSlice`].
+pub struct LeafIter<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized = Octree<T, Idx>> {
+    pub(crate) nodes: NodeIter<'a, T, Idx, Sl>,
 }
 
-impl<'tree, T, Idx: ArrayIndex> FusedIterator for LeafIter<'tree, T, Idx> where u8: AsPrimitive<Idx> {}
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> FusedIterator
+    for LeafIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+}
 
-impl<'tree, T, Idx: ArrayIndex> Iterator for LeafIter<'tree, T, Idx>
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> Iterator for LeafIter<'a, T, Idx, Sl>
 where
     u8: AsPrimitive<Idx>,
 {
-    type Item = (&'tree T, NodePoint<Idx>);
+    type Item = (&'a T, NodePoint<Idx>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some((prox, oct, np)) = self.curr_node {
-            match prox.data {
-                ProxyData::Void => self.curr_node = self.node_stack.pop(), // not a leaf & not a
-                // branch; skip
-                ProxyData::Leaf(leaf_idx) => {
-                    // move the cursor back to this node's parent (for the next iteration),
-                    // then output this node's data
-                    self.curr_node = self.node_stack.pop();
-                    return Some((&self.tree.leaf_data[leaf_idx.as_()], np));
-                }
-                ProxyData::Branch(ch_idx) => {
-                    let children: &[Idx; 8] = &self.tree.branch_data[ch_idx.as_()];
-                    // move the cursor to the next child node (ordered by `oct`)
-                    self.curr_node = Some((
-                        &self.tree.proxies[children[oct.0 as usize].as_()],
-                        Octant(0),
-                        np + oct,
-                    ));
-                    if oct < Octant::MAX {
-                        // if we haven't checked all children of this node,
-                        // put it on the top of the node stack
-                        self.node_stack.push((prox, Octant(oct.0 + 1), np));
-                    }
+        let slice = self.nodes.slice;
+        self.nodes
+            .find_map(|(node, np)| slice.leaf_of(node).map(|l| (l, np)))
+    }
+}
+
+/// A depth-first iterator over mutable references to leafs in an [Octree], by [Octant] ordering.
+pub struct LeafIterMut<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    pub(crate) proxies: &'tree S::Arena<Proxy<Idx>>,
+    pub(crate) branch_data: &'tree S::Arena<[Idx; 8]>,
+    /// The tree's leaf data, borrowed mutably for `'tree`; each value is borrowed once its leaf is
+    /// reached.
+    pub(crate) leaf_data: *mut S::Arena<T>,
+    /// The indices of the leaf data borrowed so far.
+    pub(crate) reached: HashSet<usize>,
+    pub(crate) node_stack: Vec<(Idx, NodePoint<Idx>)>,
+    pub(crate) _leaf_data: PhantomData<&'tree mut T>,
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> LeafIterMut<'tree, T, Idx, S> {
+    pub(crate) fn new(
+        tree: &'tree mut Octree<T, Idx, S>,
+        root: Idx,
+        root_point: NodePoint<Idx>,
+    ) -> Self {
+        Self {
+            proxies: &tree.proxies,
+            branch_data: &tree.branch_data,
+            leaf_data: &mut tree.leaf_data,
+            reached: HashSet::new(),
+            node_stack: vec![(root, root_point)],
+            _leaf_data: PhantomData,
+        }
+    }
+}
+
+// SAFETY: `leaf_data` is an exclusive borrow, so this is `Send` wherever the borrows it stands for
+// would be
+#[allow(unsafe_code)]
+unsafe impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> Send for LeafIterMut<'tree, T, Idx, S>
+where
+    S::Arena<Proxy<Idx>>: Sync,
+    S::Arena<[Idx; 8]>: Sync,
+    S::Arena<T>: Send,
+{
+}
+
+// SAFETY: as above, for `Sync`
+#[allow(unsafe_code)]
+unsafe impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> Sync for LeafIterMut<'tree, T, Idx, S>
+where
+    S::Arena<Proxy<Idx>>: Sync,
+    S::Arena<[Idx; 8]>: Sync,
+    S::Arena<T>: Sync,
+{
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> FusedIterator for LeafIterMut<'tree, T, Idx, S> where
+    u8: AsPrimitive<Idx>
+{
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> Iterator for LeafIterMut<'tree, T, Idx, S>
+where
+    u8: AsPrimitive<Idx>,
+{
+    type Item = (&'tree mut T, NodePoint<Idx>);
+
+    #[allow(unsafe_code)]
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((idx, np)) = self.node_stack.pop() {
+            match self.proxies[idx.as_()].data {
+                ProxyData::Void => {}
+                ProxyData::Leaf(l_idx) => {
+                    let l_idx = AsPrimitive::<usize>::as_(l_idx);
+                    // a well-formed tree never reaches the same leaf data twice, but nodes can be
+                    // edited into any shape
+                    assert!(
+                        self.reached.insert(l_idx),
+                        "each leaf's data should be reached only once"
+                    );
+                    // SAFETY: `leaf_data` is borrowed mutably for `'tree`, and only accessed here,
+                    // with each index at most once
+                    let leaf = unsafe { Arena::get_disjoint_mut(self.leaf_data, l_idx) }
+                        .expect("leaf proxies should refer to extant leaf data");
+                    return Some((leaf, np));
                 }
+                ProxyData::Branch(ch_idx) => self.node_stack.extend(
+                    self.branch_data[ch_idx.as_()]
+                        .into_iter()
+                        .zip(Octant::ALL)
+                        .rev()
+                        .map(|(c, oct)| (c, np + oct)),
+                ),
+            }
+        }
+        None
+    }
+}
+
+/// A breadth-first iterator over nodes in an [`OctreeSlice`], yielding each node & its
+/// [`NodePoint`].
+///
+/// Nodes at the same depth are given in [Octant] order.
+pub struct NodeBfIter<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized = Octree<T, Idx>> {
+    pub(crate) slice: &'a Sl,
+    pub(crate) queue: VecDeque<(Sl::Node<'a>, NodePoint<Idx>)>,
+    pub(crate) _data: PhantomData<&'a T>,
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> NodeBfIter<'a, T, Idx, Sl> {
+    pub(crate) fn new(slice: &'a Sl) -> Self {
+        Self {
+            slice,
+            queue: VecDeque::from([(slice.root_node(), slice.root_point())]),
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> FusedIterator
+    for NodeBfIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> Iterator
+    for NodeBfIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+    type Item = (Sl::Node<'a>, NodePoint<Idx>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, np) = self.queue.pop_front()?;
+        if let Some(children) = self.slice.children_of(node) {
+            self.queue.extend(
+                children
+                    .into_iter()
+                    .zip(Octant::ALL)
+                    .map(|(c, oct)| (c, np + oct)),
+            );
+        }
+        Some((node, np))
+    }
+}
+
+/// A breadth-first iterator over leafs in an [`OctreeSlice`], from shallowest to deepest.
+///
+/// Leafs at the same depth are given in [Octant] order.
+pub struct LeafBfIter<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized = Octree<T, Idx>> {
+    pub(crate) nodes: NodeBfIter<'a, T, Idx, Sl>,
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> FusedIterator
+    for LeafBfIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> Iterator
+    for LeafBfIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+    type Item = (&'a T, NodePoint<Idx>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slice = self.nodes.slice;
+        self.nodes
+            .find_map(|(node, np)| slice.leaf_of(node).map(|l| (l, np)))
+    }
+}
+
+/// An iterator over every node at a specific depth of an [`OctreeSlice`], yielding each node &
+/// its [`NodePoint`] in [Octant] order.
+///
+/// Leafs and voids shallower than the target depth have no descendants, so nothing is given for
+/// the space they occupy.
+pub struct LevelIter<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized = Octree<T, Idx>> {
+    pub(crate) slice: &'a Sl,
+    /// Absolute depth of the nodes to output.
+    pub(crate) depth: Idx,
+    /// Nodes yet to be visited, shallower than or at `depth`; the next node is on top.
+    pub(crate) node_stack: Vec<(Sl::Node<'a>, NodePoint<Idx>)>,
+    pub(crate) _data: PhantomData<&'a T>,
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> LevelIter<'a, T, Idx, Sl> {
+    /// Construct an iterator over the nodes at `depth` below the root of `slice`.
+    pub(crate) fn new(slice: &'a Sl, depth: Idx) -> Self {
+        let root_point = slice.root_point();
+        Self {
+            slice,
+            depth: root_point.0.w + depth,
+            node_stack: vec![(slice.root_node(), root_point)],
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> FusedIterator
+    for LevelIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+}
+
+impl<'a, T, Idx: ArrayIndex, Sl: OctreeSlice<T, Idx> + ?Sized> Iterator
+    for LevelIter<'a, T, Idx, Sl>
+where
+    u8: AsPrimitive<Idx>,
+{
+    type Item = (Sl::Node<'a>, NodePoint<Idx>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // depth-first, but pruned at `self.depth`, which gives the same order as breadth-first
+        // without having to store an entire level at once
+        while let Some((node, np)) = self.node_stack.pop() {
+            if np.0.w == self.depth {
+                return Some((node, np));
+            }
+            if let Some(children) = self.slice.children_of(node) {
+                self.node_stack.extend(
+                    children
+                        .into_iter()
+                        .zip(Octant::ALL)
+                        .rev()
+                        .map(|(c, oct)| (c, np + oct)),
+                );
             }
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{NodePoint, Octant, Octree, OctreeSlice};
+
+    /// Build a tree of height 2, with leafs at `root[6]` & `root[3][5]`.
+    fn tree() -> (Octree<u8, u32>, [u32; 8], [u32; 8]) {
+        let mut tree = Octree::<u8, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.set_leaf(root[6], 6);
+        tree.set_leaf(inner[5], 35);
+        (tree, root, inner)
+    }
+
+    #[test]
+    fn node_dfi() {
+        let (tree, root, inner) = tree();
+        let nodes = tree.node_dfi().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 17);
+        assert_eq!(nodes[0], 0);
+        assert_eq!(&nodes[1..5], &root[..4]);
+        assert_eq!(&nodes[5..13], &inner);
+        assert_eq!(&nodes[13..], &root[4..]);
+        for (i, np) in tree.node_dfi() {
+            assert_eq!(tree.node_point_of_unchecked(i), np);
+        }
+    }
+
+    #[test]
+    fn node_bfi() {
+        let (tree, root, inner) = tree();
+        let nodes = tree.node_bfi().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 17);
+        assert_eq!(nodes[0], 0);
+        assert_eq!(&nodes[1..9], &root);
+        assert_eq!(&nodes[9..], &inner);
+        for (i, np) in tree.node_bfi() {
+            assert_eq!(tree.node_point_of_unchecked(i), np);
+            assert_eq!(tree.node_at(&np), i);
+        }
+    }
+
+    #[test]
+    fn leaf_bfi() {
+        let (tree, _, _) = tree();
+        assert_eq!(
+            tree.leaf_bfi().collect::<Vec<_>>(),
+            vec![
+                (&6, NodePoint::new(1, 1, 0, 1)),
+                (&35, NodePoint::new(1, 2, 3, 2))
+            ]
+        );
+    }
+
+    #[test]
+    fn level() {
+        let (tree, root, inner) = tree();
+        assert_eq!(tree.level(0).map(|(i, _)| i).collect::<Vec<_>>(), [0]);
+        let lvl = tree.level(1).collect::<Vec<_>>();
+        assert_eq!(lvl.iter().map(|(i, _)| *i).collect::<Vec<_>>(), root);
+        for ((_, np), oct) in lvl.into_iter().zip(Octant::ALL) {
+            assert_eq!(np, NodePoint::default() + oct);
+        }
+        assert_eq!(tree.level(2).map(|(i, _)| i).collect::<Vec<_>>(), inner);
+        assert_eq!(tree.level(3).count(), 0);
+
+        let slice = tree.slice(root[3]).unwrap();
+        assert_eq!(slice.level(1).map(|(i, _)| i).collect::<Vec<_>>(), inner);
+        assert_eq!(slice.leaf_bfi().count(), 1);
+    }
+
+    /// An implementor outside of this module, which only provides the required methods.
+    struct Wrapper(Octree<u8, u32>);
+
+    impl OctreeSlice<u8, u32> for Wrapper {
+        type Node<'a> = u32;
+
+        fn root_node(&self) -> u32 {
+            self.0.root_idx()
+        }
+        fn children_of(&self, node: u32) -> Option<[u32; 8]> {
+            self.0.children_of(node)
+        }
+        fn leaf_of(&self, node: u32) -> Option<&u8> {
+            self.0.leaf_of(node)
+        }
+    }
+
+    #[test]
+    fn provided_methods() {
+        let (tree, _, inner) = tree();
+        let wrapper = Wrapper(tree.clone());
+        assert_eq!(wrapper.height(), tree.height());
+        assert_eq!(wrapper.node_at(&NodePoint::new(1, 2, 3, 2)), inner[5]);
+        assert!(wrapper.node_dfi().eq(tree.node_dfi()));
+        assert!(wrapper.leaf_dfi().eq(tree.leaf_dfi()));
+        assert!(wrapper.node_bfi().eq(tree.node_bfi()));
+        assert!(wrapper.leaf_bfi().eq(tree.leaf_bfi()));
+        for depth in 0..3 {
+            assert!(wrapper.level(depth).eq(tree.level(depth)));
+        }
+    }
+
+    #[test]
+    fn leaf_dfi_mut() {
+        let (mut tree, root, _) = tree();
+        tree.set_leaf(root[0], 0);
+        for (leaf, np) in tree.leaf_dfi_mut() {
+            *leaf += np.0.w as u8 * 100;
+        }
+        assert_eq!(
+            tree.leaf_dfi().map(|(l, _)| *l).collect::<Vec<_>>(),
+            [100, 235, 106]
+        );
+        for leaf in tree.slice_mut(root[3]).unwrap().leaf_dfi_mut() {
+            *leaf.0 = 0;
+        }
+        for leaf in tree.leaf_unordered_mut() {
+            *leaf += 1;
+        }
+        assert_eq!(
+            tree.leaf_dfi().map(|(l, _)| *l).collect::<Vec<_>>(),
+            [101, 1, 107]
+        );
+    }
+}
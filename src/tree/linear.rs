@@ -0,0 +1,291 @@
+use std::ops::Range;
+
+use eightfold_common::{morton, ArrayIndex};
+use num_traits::AsPrimitive;
+
+use crate::{Arena, Error, NodePoint, Octree, OctreeSlice, OctreeStorage, ProxyData, VoxelPoint};
+
+/// A read-only octree stored as an array of leafs sorted by Morton code, for cache-friendly
+/// traversal.
+///
+/// Each leaf is stored as `(code, depth, data)`, where `code` is the Morton code of the leaf's
+/// first voxel within the voxel grid of the tree (that is, at depth `height`). Because leafs never
+/// overlap, this orders leafs identically to [`OctreeSlice::leaf_dfi`], and every leaf spans the
+/// contiguous range of codes given by [`Self::range_of`].
+#[derive(Debug, Clone)]
+pub struct LinearOctree<T, Idx: ArrayIndex> {
+    /// Height of the voxel grid in which codes are given.
+    height: Idx,
+    leafs: Vec<(u128, Idx, T)>,
+}
+
+impl<T, Idx: ArrayIndex> Default for LinearOctree<T, Idx> {
+    fn default() -> Self {
+        Self {
+            height: Idx::ZERO,
+            leafs: Vec::new(),
+        }
+    }
+}
+
+impl<T, Idx: ArrayIndex> LinearOctree<T, Idx> {
+    /// The height of the voxel grid indexed by `self`.
+    #[inline]
+    pub fn height(&self) -> Idx {
+        self.height
+    }
+
+    /// The number of leafs in `self`.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.leafs.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.leafs.is_empty()
+    }
+
+    /// All leafs, as `(code, depth, data)`, sorted by code.
+    #[inline]
+    pub fn as_slice(&self) -> &[(u128, Idx, T)] {
+        &self.leafs
+    }
+
+    /// The number of voxel-grid codes spanned by a node at `depth`.
+    #[inline]
+    fn span(&self, depth: Idx) -> u128 {
+        1 << (3 * AsPrimitive::<usize>::as_(self.height - depth))
+    }
+
+    /// The code of the first voxel within a node at `p`.
+    #[inline]
+    fn code_of(&self, p: &NodePoint<Idx>) -> u128 {
+        p.morton() << (3 * AsPrimitive::<usize>::as_(self.height - p.0.w))
+    }
+
+    /// If `p` is deeper than the voxel grid, get the voxel containing it instead.
+    #[inline]
+    fn clamp(&self, p: &NodePoint<Idx>) -> NodePoint<Idx> {
+        if p.0.w <= self.height {
+            return *p;
+        }
+        let s: usize = (p.0.w - self.height).as_();
+        NodePoint::new(p.0.x >> s, p.0.y >> s, p.0.z >> s, self.height)
+    }
+
+    /// The range of codes of the voxels within the node at `p`.
+    ///
+    /// # Panics
+    ///
+    /// * `p` is deeper than `self.height()`
+    pub fn range_of(&self, p: &NodePoint<Idx>) -> Range<u128> {
+        let start = self.code_of(p);
+        start..(start + self.span(p.0.w))
+    }
+
+    /// The [`NodePoint`] of a leaf with a specific code & depth.
+    pub fn node_point_of(&self, code: u128, depth: Idx) -> NodePoint<Idx> {
+        NodePoint::from_morton(
+            code >> (3 * AsPrimitive::<usize>::as_(self.height - depth)),
+            depth,
+        )
+    }
+
+    /// Iterate through all leafs, by [Octant](crate::Octant) ordering.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, NodePoint<Idx>)> {
+        self.leafs
+            .iter()
+            .map(|(code, depth, data)| (data, self.node_point_of(*code, *depth)))
+    }
+
+    /// Get the leaf containing the voxel with a specific code, if any.
+    pub fn leaf_containing_code(&self, code: u128) -> Option<(&T, NodePoint<Idx>)> {
+        // the last leaf starting at or before `code`
+        let i = self.leafs.partition_point(|(c, _, _)| *c <= code);
+        let (c, depth, data) = self.leafs.get(i.checked_sub(1)?)?;
+        (code < c + self.span(*depth)).then(|| (data, self.node_point_of(*c, *depth)))
+    }
+
+    /// Get the leaf containing a specific [`VoxelPoint`], if any.
+    pub fn leaf_at(&self, p: &VoxelPoint<Idx>) -> Option<(&T, NodePoint<Idx>)> {
+        let size = Idx::ONE << self.height;
+        if p.x >= size || p.y >= size || p.z >= size {
+            return None;
+        }
+        self.leaf_containing_code(morton::encode((*p).into()))
+    }
+
+    /// Get the leaf containing a node at a specific [`NodePoint`], if any.
+    ///
+    /// A leaf deeper than `p` only covers part of the node, so it doesn't contain it.
+    pub fn leaf_containing(&self, p: &NodePoint<Idx>) -> Option<(&T, NodePoint<Idx>)> {
+        let p = self.clamp(p);
+        self.leaf_containing_code(self.code_of(&p))
+            .filter(|(_, np)| np.0.w <= p.0.w)
+    }
+
+    /// All leafs which overlap a range of codes, sorted by code.
+    pub fn range(&self, codes: Range<u128>) -> &[(u128, Idx, T)] {
+        // leafs don't overlap, so their ends are sorted as well as their starts
+        let start = self
+            .leafs
+            .partition_point(|(c, depth, _)| c + self.span(*depth) <= codes.start);
+        let end = self.leafs.partition_point(|(c, _, _)| *c < codes.end);
+        &self.leafs[start..end.max(start)]
+    }
+
+    /// All leafs which overlap the node at a specific [`NodePoint`], sorted by code.
+    pub fn leafs_within(&self, p: &NodePoint<Idx>) -> &[(u128, Idx, T)] {
+        self.range(self.range_of(&self.clamp(p)))
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> From<Octree<T, Idx, S>> for LinearOctree<T, Idx>
+where
+    u8: AsPrimitive<Idx>,
+{
+    /// Convert an [Octree] to a [`LinearOctree`]; void-only branches are discarded.
+    fn from(mut tree: Octree<T, Idx, S>) -> Self {
+        let height = tree.height();
+        debug_assert!(AsPrimitive::<usize>::as_(height) <= morton::MAX_BITS as usize);
+        let shift = |depth: Idx| 3 * AsPrimitive::<usize>::as_(height - depth);
+        let leafs = tree
+            .node_dfi()
+            .filter_map(|(idx, np)| match tree.get(idx).data {
+                ProxyData::Leaf(l_idx) => Some((np.morton() << shift(np.0.w), np.0.w, l_idx)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        Self {
+            height,
+            leafs: leafs
+                .into_iter()
+                .map(|(code, depth, l_idx)| {
+                    (code, depth, tree.leaf_data.remove(l_idx.as_()).unwrap())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> TryFrom<LinearOctree<T, Idx>> for Octree<T, Idx, S>
+where
+    usize: AsPrimitive<Idx>,
+{
+    type Error = Error<Idx>;
+
+    /// Convert a [`LinearOctree`] back into an [Octree].
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the nodes or leafs of the tree wouldn't be
+    ///   addressable by `Idx`.
+    fn try_from(linear: LinearOctree<T, Idx>) -> Result<Self, Self::Error> {
+        let height: usize = linear.height.as_();
+        let mut tree = Octree::new();
+        for (code, depth, data) in linear.leafs {
+            let mut idx = tree.root;
+            for level in 0..AsPrimitive::<usize>::as_(depth) {
+                let oct = (code >> (3 * (height - 1 - level))) & 0b111;
+                // leafs never overlap, so this is always a void or a branch
+                idx = tree.split(idx)?.0[oct as usize];
+            }
+            tree.try_set_leaf(idx, data)?;
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, LinearOctree, NodePoint, Octree, OctreeSlice, VoxelPoint};
+
+    /// Build a tree of height 2, with leafs at `root[6]`, `root[3][0]` & `root[3][5]`.
+    fn tree() -> Octree<u8, u16> {
+        let mut tree = Octree::<u8, u16>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.set_leaf(root[6], 6);
+        tree.set_leaf(inner[0], 30);
+        tree.set_leaf(inner[5], 35);
+        tree
+    }
+
+    #[test]
+    fn round_trip() {
+        let expected = tree()
+            .leaf_dfi()
+            .map(|(l, np)| (*l, np))
+            .collect::<Vec<_>>();
+        let linear = LinearOctree::from(tree());
+        assert_eq!(linear.height(), 2);
+        assert_eq!(
+            linear.iter().map(|(l, np)| (*l, np)).collect::<Vec<_>>(),
+            expected
+        );
+        let tree = Octree::<u8, u16>::try_from(linear).unwrap();
+        assert_eq!(
+            tree.leaf_dfi().map(|(l, np)| (*l, np)).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn index_exhausted() {
+        // one leaf in each of the 64 nodes at depth 2, which takes 585 nodes to represent
+        let linear = LinearOctree {
+            height: 3,
+            leafs: (0..64).map(|i| (i << 3, 3, i as u8)).collect(),
+        };
+        assert!(matches!(
+            Octree::<u8, u8>::try_from(linear),
+            Err(Error::IndexExhausted(_))
+        ));
+    }
+
+    #[test]
+    fn point_location() {
+        let linear = LinearOctree::from(tree());
+        // root[6] is (1, 1, 0) at depth 1, so it covers voxels (2..4, 2..4, 0..2)
+        assert_eq!(
+            linear.leaf_at(&VoxelPoint::new(3, 2, 1)),
+            Some((&6, NodePoint::new(1, 1, 0, 1)))
+        );
+        assert_eq!(
+            linear.leaf_at(&VoxelPoint::new(1, 2, 3)),
+            Some((&35, NodePoint::new(1, 2, 3, 2)))
+        );
+        assert_eq!(linear.leaf_at(&VoxelPoint::new(0, 0, 0)), None);
+        assert_eq!(linear.leaf_at(&VoxelPoint::new(4, 0, 0)), None);
+        assert_eq!(
+            linear.leaf_containing(&NodePoint::new(7, 4, 1, 3)),
+            Some((&6, NodePoint::new(1, 1, 0, 1)))
+        );
+        // root[3] only partially holds data, and the root holds none itself
+        assert_eq!(linear.leaf_containing(&NodePoint::new(0, 1, 1, 1)), None);
+        assert_eq!(linear.leaf_containing(&NodePoint::new(0, 0, 0, 0)), None);
+        assert_eq!(
+            linear.leaf_containing(&NodePoint::new(1, 2, 3, 2)),
+            Some((&35, NodePoint::new(1, 2, 3, 2)))
+        );
+    }
+
+    #[test]
+    fn range() {
+        let linear = LinearOctree::from(tree());
+        let leafs = |np| {
+            linear
+                .leafs_within(&np)
+                .iter()
+                .map(|(_, _, l)| *l)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(leafs(NodePoint::new(0, 0, 0, 0)), [30, 35, 6]);
+        assert_eq!(leafs(NodePoint::new(0, 1, 1, 1)), [30, 35]);
+        assert_eq!(leafs(NodePoint::new(1, 1, 0, 1)), [6]);
+        assert_eq!(leafs(NodePoint::new(3, 3, 1, 2)), [6]);
+        assert_eq!(leafs(NodePoint::new(13, 9, 3, 4)), [6]);
+        assert!(leafs(NodePoint::new(0, 0, 0, 1)).is_empty());
+    }
+}
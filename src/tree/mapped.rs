@@ -0,0 +1,529 @@
+use std::{collections::VecDeque, io::Write, iter::FusedIterator, marker::PhantomData, mem};
+
+use eightfold_common::ArrayIndex;
+
+use crate::{
+    Arena, Error, FormatError, LeafSample, NodePoint, Octant, Octree, OctreeSlice, OctreeStorage,
+    ProxyData, VoxelPoint,
+};
+
+/// Magic bytes at the start of every [`MappedOctree`].
+pub const MAPPED_MAGIC: [u8; 4] = *b"8fmo";
+/// The current version of the [`MappedOctree`] layout.
+pub const MAPPED_VERSION: u8 = 1;
+
+/// Size of the [`MappedOctree`] header, in bytes.
+const HEADER_LEN: usize = 24;
+/// Mask of the kind bits within a node word.
+const KIND_MASK: u32 = 0b11 << 30;
+const KIND_LEAF: u32 = 0b01 << 30;
+const KIND_BRANCH: u32 = 0b10 << 30;
+/// The largest number of nodes or leafs which can be addressed by a node word.
+const MAX_INDEX: u32 = !KIND_MASK;
+/// The height of the tallest tree whose voxel grid can be addressed by `u32` coordinates.
+const MAX_HEIGHT: u32 = u32::BITS - 1;
+
+/// Leaf data with a fixed-size byte representation, for storage within a [`MappedOctree`].
+pub trait MappedLeaf: Sized {
+    /// The number of bytes used to store each leaf.
+    const SIZE: usize;
+    /// Write `self` into `out`, which is exactly [`Self::SIZE`] bytes long.
+    fn to_bytes(&self, out: &mut [u8]);
+    /// Read a leaf from `bytes`, which is exactly [`Self::SIZE`] bytes long.
+    fn from_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! mapped_leaf_impl {
+    ($($t:ty),*) => {$(
+        impl MappedLeaf for $t {
+            const SIZE: usize = mem::size_of::<$t>();
+            #[inline]
+            fn to_bytes(&self, out: &mut [u8]) {
+                out.copy_from_slice(&self.to_le_bytes());
+            }
+            #[inline]
+            fn from_bytes(bytes: &[u8]) -> Self {
+                let mut buf = [0; mem::size_of::<$t>()];
+                buf.copy_from_slice(bytes);
+                <$t>::from_le_bytes(buf)
+            }
+        }
+    )*};
+}
+
+mapped_leaf_impl!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl MappedLeaf for () {
+    const SIZE: usize = 0;
+    #[inline]
+    fn to_bytes(&self, _: &mut [u8]) {}
+    #[inline]
+    fn from_bytes(_: &[u8]) -> Self {}
+}
+
+/// A read-only octree, queried directly from a byte slice (such as a memory-mapped file) in the
+/// layout written by [`Octree::write_mapped`].
+///
+/// # Layout
+///
+/// All integers are little-endian.
+///
+/// | Field      | Size            | Contents                       |
+/// |------------|-----------------|--------------------------------|
+/// | magic      | 4               | [`MAPPED_MAGIC`]               |
+/// | version    | 1               | [`MAPPED_VERSION`]             |
+/// | padding    | 3               |                                |
+/// | leaf size  | 4               | [`MappedLeaf::SIZE`]           |
+/// | height     | 4               | height of the tree, at most 31 |
+/// | node count | 4               |                                |
+/// | leaf count | 4               |                                |
+/// | nodes      | `4 × nodes`     | one `u32` word per node        |
+/// | leafs      | `SIZE × leafs`  | [`MappedLeaf`] data            |
+///
+/// The top two bits of a node word give its kind: `0` void, `1` leaf, `2` branch. The remaining
+/// bits give the index of a leaf's data, or the index of the first of a branch's 8 children, which
+/// are stored consecutively in [Octant] order. Nodes are stored breadth-first, with the root first,
+/// so every child is stored after its parent.
+///
+/// # Panics
+///
+/// Construction only checks the header, so queries against corrupt data may panic (or give
+/// nonsensical results), though they can't read outside of the slice or loop forever.
+#[derive(Debug, Clone, Copy)]
+pub struct MappedOctree<'data, T> {
+    height: u32,
+    nodes: &'data [u8],
+    leafs: &'data [u8],
+    _leaf: PhantomData<fn() -> T>,
+}
+
+impl<'data, T: MappedLeaf> MappedOctree<'data, T> {
+    /// View a byte slice as a [`MappedOctree`], checking only its header; this is *O(1)*.
+    ///
+    /// # Errors
+    ///
+    /// * [`BadMagic`](FormatError::BadMagic) or
+    ///   [`UnsupportedVersion`](FormatError::UnsupportedVersion) if the header doesn't match.
+    /// * [`LeafSize`](FormatError::LeafSize) if `data` stores leafs of a different size than `T`.
+    /// * [`InvalidHeight`](FormatError::InvalidHeight) if the tree is too tall for its voxel grid
+    ///   to be addressed by `u32`.
+    /// * [`Truncated`](FormatError::Truncated) if `data` is shorter than its header claims.
+    pub fn new(data: &'data [u8]) -> Result<Self, FormatError> {
+        if data.len() < HEADER_LEN {
+            return Err(FormatError::Truncated {
+                expected: HEADER_LEN,
+                found: data.len(),
+            });
+        }
+        let word = |i: usize| u32::from_le_bytes(data[i..(i + 4)].try_into().unwrap());
+        let magic: [u8; 4] = data[0..4].try_into().unwrap();
+        if magic != MAPPED_MAGIC {
+            return Err(FormatError::BadMagic(magic));
+        }
+        if data[4] != MAPPED_VERSION {
+            return Err(FormatError::UnsupportedVersion(data[4]));
+        }
+        let leaf_size = word(8) as usize;
+        if leaf_size != T::SIZE {
+            return Err(FormatError::LeafSize {
+                expected: T::SIZE,
+                found: leaf_size,
+            });
+        }
+        let (height, node_count, leaf_count) = (word(12), word(16) as usize, word(20) as usize);
+        if height > MAX_HEIGHT {
+            return Err(FormatError::InvalidHeight(height));
+        }
+        let nodes_end = HEADER_LEN + 4 * node_count;
+        let leafs_end = nodes_end + T::SIZE * leaf_count;
+        if node_count == 0 || data.len() < leafs_end {
+            return Err(FormatError::Truncated {
+                expected: leafs_end.max(HEADER_LEN + 4),
+                found: data.len(),
+            });
+        }
+        Ok(Self {
+            height,
+            nodes: &data[HEADER_LEN..nodes_end],
+            leafs: &data[nodes_end..leafs_end],
+            _leaf: PhantomData,
+        })
+    }
+
+    /// The height of the tree, as recorded when it was written.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The dimensions of the cubical voxel grid represented by this tree.
+    #[inline]
+    pub fn grid_size(&self) -> u32 {
+        1 << self.height
+    }
+
+    /// The number of nodes in the tree.
+    #[inline]
+    pub fn len_nodes(&self) -> usize {
+        self.nodes.len() / 4
+    }
+
+    /// The number of leafs in the tree.
+    #[inline]
+    pub fn len_leafs(&self) -> usize {
+        self.leafs.len().checked_div(T::SIZE).unwrap_or(0)
+    }
+
+    /// Get the [`ProxyData`] of the node at a specific index.
+    ///
+    /// For branches, this is the index of the first of its 8 consecutive children, rather than an
+    /// index into branch data.
+    ///
+    /// # Panics
+    ///
+    /// * `node` ≥ `self.len_nodes()`
+    /// * The node's kind is invalid
+    pub fn data(&self, node: u32) -> ProxyData<u32> {
+        let i = 4 * node as usize;
+        let word = u32::from_le_bytes(self.nodes[i..(i + 4)].try_into().unwrap());
+        match word & KIND_MASK {
+            0 => ProxyData::Void,
+            KIND_LEAF => ProxyData::Leaf(word & MAX_INDEX),
+            KIND_BRANCH => {
+                let first = word & MAX_INDEX;
+                assert!(
+                    first > node,
+                    "corrupt mapped octree: child stored before parent"
+                );
+                ProxyData::Branch(first)
+            }
+            _ => panic!("corrupt mapped octree: invalid node kind"),
+        }
+    }
+
+    /// Get the index of a child of a branch.
+    ///
+    /// Returns `None` if `node` is not a branch.
+    #[inline]
+    pub fn child(&self, node: u32, oct: Octant) -> Option<u32> {
+        match self.data(node) {
+            ProxyData::Branch(first) => Some(first + u32::from(oct.0)),
+            _ => None,
+        }
+    }
+
+    /// Read the leaf data at a specific index.
+    ///
+    /// # Panics
+    ///
+    /// * `leaf` ≥ `self.len_leafs()`
+    pub fn leaf(&self, leaf: u32) -> T {
+        let i = T::SIZE * leaf as usize;
+        T::from_bytes(&self.leafs[i..(i + T::SIZE)])
+    }
+
+    /// Descend from the root towards a point at `depth`, returning the path taken, from the root.
+    fn path_to(&self, p: &VoxelPoint<u32>, depth: u32) -> Vec<u32> {
+        // no node is deeper than the height of the tree, so finer coordinates can be discarded
+        let excess = depth.saturating_sub(self.height);
+        let p = p.map(|c| c.checked_shr(excess).unwrap_or(0));
+        let mut depth = depth.min(self.height);
+        let mut path = vec![0];
+        let mut idx = 0;
+        while let ProxyData::Branch(first) = self.data(idx) {
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+            let oct = Octant::new(
+                (p.x >> depth) & 1 == 1,
+                (p.y >> depth) & 1 == 1,
+                (p.z >> depth) & 1 == 1,
+            );
+            idx = first + u32::from(oct.0);
+            path.push(idx);
+        }
+        path
+    }
+
+    /// Get the index of the deepest node encompassing a specific [`NodePoint`].
+    pub fn node_at(&self, p: &NodePoint<u32>) -> u32 {
+        *self.path_to(&p.0.xyz(), p.0.w).last().unwrap()
+    }
+
+    /// Get the index of the deepest voxel containing a specific [`VoxelPoint`].
+    ///
+    /// # Errors
+    ///
+    /// * [`VoxelOutOfGrid`](Error::VoxelOutOfGrid) if `p` ∉ 0..`self.grid_size()`
+    pub fn voxel_at(&self, p: &VoxelPoint<u32>) -> Result<u32, Error<u32>> {
+        let size = self.grid_size();
+        if p.x >= size || p.y >= size || p.z >= size {
+            return Err(Error::VoxelOutOfGrid(size, *p));
+        }
+        Ok(self.node_at(&NodePoint::new(p.x, p.y, p.z, self.height)))
+    }
+
+    /// Depth-first iterator through all leafs, by [Octant] ordering.
+    pub fn leaf_dfi(&self) -> MappedLeafIter<'_, 'data, T> {
+        MappedLeafIter {
+            tree: self,
+            node_stack: vec![(0, NodePoint::new(0, 0, 0, 0))],
+        }
+    }
+
+    fn internal_sample_branch(&self, first: u32) -> Option<T>
+    where
+        T: LeafSample,
+    {
+        let mut res: Option<T> = None;
+        for c in first..(first + 8) {
+            let data = match self.data(c) {
+                ProxyData::Void => continue,
+                ProxyData::Leaf(l) => self.leaf(l),
+                ProxyData::Branch(f) => match self.internal_sample_branch(f) {
+                    Some(d) => d,
+                    None => continue,
+                },
+            };
+            res = Some(match res {
+                Some(r) => T::leaf_sample(&r, &data),
+                None => data,
+            });
+        }
+        res
+    }
+
+    /// Return the merged leaf data at a specific point.
+    ///
+    /// See [`Octree::sample_at`].
+    pub fn sample_at(&self, point: &NodePoint<u32>) -> Option<T>
+    where
+        T: LeafSample,
+    {
+        let path = self.path_to(&point.0.xyz(), point.0.w);
+        path.into_iter()
+            .rev()
+            .find_map(|node| match self.data(node) {
+                ProxyData::Void => None,
+                ProxyData::Leaf(l) => Some(self.leaf(l)),
+                ProxyData::Branch(first) => self.internal_sample_branch(first),
+            })
+    }
+}
+
+/// A depth-first iterator over leafs in a [`MappedOctree`].
+pub struct MappedLeafIter<'tree, 'data, T> {
+    tree: &'tree MappedOctree<'data, T>,
+    node_stack: Vec<(u32, NodePoint<u32>)>,
+}
+
+impl<'tree, 'data, T: MappedLeaf> FusedIterator for MappedLeafIter<'tree, 'data, T> {}
+
+impl<'tree, 'data, T: MappedLeaf> Iterator for MappedLeafIter<'tree, 'data, T> {
+    type Item = (T, NodePoint<u32>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, np)) = self.node_stack.pop() {
+            match self.tree.data(node) {
+                ProxyData::Void => {}
+                ProxyData::Leaf(l) => return Some((self.tree.leaf(l), np)),
+                ProxyData::Branch(first) => {
+                    self.node_stack.extend(
+                        Octant::ALL
+                            .into_iter()
+                            .rev()
+                            .map(|oct| (first + u32::from(oct.0), np + oct)),
+                    );
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Write `self` in the fixed layout read by [`MappedOctree`].
+    ///
+    /// # Errors
+    ///
+    /// * [`Io`](FormatError::Io) on errors produced by `w`.
+    /// * [`IndexOverflow`](FormatError::IndexOverflow) if `self` has more than `2³⁰ - 1` nodes.
+    /// * [`InvalidHeight`](FormatError::InvalidHeight) if `self` is taller than 31.
+    pub fn write_mapped<W: Write>(&self, mut w: W) -> Result<(), FormatError>
+    where
+        T: MappedLeaf,
+    {
+        let mut nodes = Vec::with_capacity(self.proxies.len_init());
+        let mut leafs = Vec::with_capacity(self.leaf_data.len_init());
+        // nodes are assigned positions in the order they're queued
+        let mut queue = VecDeque::from([self.root]);
+        let mut next = 1u32;
+        while let Some(idx) = queue.pop_front() {
+            let word = match self.proxies[idx.as_()].data {
+                ProxyData::Void => 0,
+                ProxyData::Leaf(l_idx) => {
+                    leafs.push(l_idx);
+                    KIND_LEAF | (leafs.len() as u32 - 1)
+                }
+                ProxyData::Branch(b_idx) => {
+                    queue.extend(self.branch_data[b_idx.as_()]);
+                    next += 8;
+                    KIND_BRANCH | (next - 8)
+                }
+            };
+            if next > MAX_INDEX {
+                return Err(FormatError::IndexOverflow);
+            }
+            nodes.push(word);
+        }
+
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(&MAPPED_MAGIC);
+        header[4] = MAPPED_VERSION;
+        let height = self.height().to_u32().unwrap_or(u32::MAX);
+        if height > MAX_HEIGHT {
+            return Err(FormatError::InvalidHeight(height));
+        }
+        header[8..12].copy_from_slice(&(T::SIZE as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&height.to_le_bytes());
+        header[16..20].copy_from_slice(&(nodes.len() as u32).to_le_bytes());
+        header[20..24].copy_from_slice(&(leafs.len() as u32).to_le_bytes());
+        w.write_all(&header)?;
+        for word in nodes {
+            w.write_all(&word.to_le_bytes())?;
+        }
+        let mut buf = vec![0; T::SIZE];
+        for l_idx in leafs {
+            self.leaf_data[l_idx.as_()].to_bytes(&mut buf);
+            w.write_all(&buf)?;
+        }
+        Ok(())
+    }
+
+    /// Write `self` into a new buffer, in the fixed layout read by [`MappedOctree`].
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexOverflow`](FormatError::IndexOverflow) if `self` has more than `2³⁰ - 1` nodes.
+    /// * [`InvalidHeight`](FormatError::InvalidHeight) if `self` is taller than 31.
+    pub fn to_mapped_bytes(&self) -> Result<Vec<u8>, FormatError>
+    where
+        T: MappedLeaf,
+    {
+        let mut res = Vec::new();
+        self.write_mapped(&mut res)?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        FormatError, LeafSample, MappedLeaf, MappedOctree, NodePoint, Octree, OctreeSlice,
+        VoxelPoint,
+    };
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Max(u16);
+
+    impl LeafSample for Max {
+        fn leaf_sample(a: &Self, b: &Self) -> Self {
+            Self(a.0.max(b.0))
+        }
+    }
+
+    impl MappedLeaf for Max {
+        const SIZE: usize = 2;
+        fn to_bytes(&self, out: &mut [u8]) {
+            self.0.to_bytes(out);
+        }
+        fn from_bytes(bytes: &[u8]) -> Self {
+            Self(u16::from_bytes(bytes))
+        }
+    }
+
+    /// Build a tree of height 3, with leafs at `root[6]`, `root[3][0]`, `root[3][5][2]`.
+    fn tree() -> Octree<Max, u32> {
+        let mut tree = Octree::<Max, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        let deep = *tree.split(inner[5]).unwrap().0;
+        tree.set_leaf(root[6], Max(6));
+        tree.set_leaf(inner[0], Max(30));
+        tree.set_leaf(deep[2], Max(352));
+        tree
+    }
+
+    #[test]
+    fn queries() {
+        let tree = tree();
+        let bytes = tree.to_mapped_bytes().unwrap();
+        let mapped = MappedOctree::<Max>::new(&bytes).unwrap();
+        assert_eq!(mapped.height(), 3);
+        assert_eq!(mapped.len_nodes(), 25);
+        assert_eq!(mapped.len_leafs(), 3);
+        assert!(mapped
+            .leaf_dfi()
+            .eq(tree.leaf_dfi().map(|(l, np)| (*l, np))));
+        let size = mapped.grid_size();
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let vp = VoxelPoint::new(x, y, z);
+                    let leaf = |i| match tree[i].data {
+                        crate::ProxyData::Leaf(l) => Some(tree.leaf_data[l as usize]),
+                        _ => None,
+                    };
+                    let m_leaf = |i| match mapped.data(i) {
+                        crate::ProxyData::Leaf(l) => Some(mapped.leaf(l)),
+                        _ => None,
+                    };
+                    assert_eq!(
+                        m_leaf(mapped.voxel_at(&vp).unwrap()),
+                        leaf(tree.voxel_at(&vp).unwrap())
+                    );
+                    let np = NodePoint::new(x >> 1, y >> 1, z >> 1, 2);
+                    assert_eq!(mapped.sample_at(&np), tree.sample_at(&np));
+                }
+            }
+        }
+        assert!(mapped.voxel_at(&VoxelPoint::new(8, 0, 0)).is_err());
+        // points deeper than the tree are located by their ancestor at the tree's height
+        let (_, deep) = mapped.leaf_dfi().find(|(l, _)| l.0 == 352).unwrap();
+        let finer = deep.0.xyz().map(|c| (c << 28) | (1 << 27));
+        assert_eq!(
+            mapped.node_at(&NodePoint::new(finer.x, finer.y, finer.z, 31)),
+            mapped.node_at(&deep)
+        );
+        assert_eq!(mapped.node_at(&NodePoint::new(1, 1, 1, u32::MAX)), 1);
+    }
+
+    #[test]
+    fn header() {
+        let bytes = tree().to_mapped_bytes().unwrap();
+        assert!(matches!(
+            MappedOctree::<Max>::new(&bytes[..bytes.len() - 1]),
+            Err(FormatError::Truncated { .. })
+        ));
+        assert!(matches!(
+            MappedOctree::<u8>::new(&bytes),
+            Err(FormatError::LeafSize {
+                expected: 1,
+                found: 2
+            })
+        ));
+        let mut bad = bytes.clone();
+        bad[0] = 0;
+        assert!(matches!(
+            MappedOctree::<Max>::new(&bad),
+            Err(FormatError::BadMagic(_))
+        ));
+        let mut tall = bytes.clone();
+        tall[12..16].copy_from_slice(&32u32.to_le_bytes());
+        assert!(matches!(
+            MappedOctree::<Max>::new(&tall),
+            Err(FormatError::InvalidHeight(32))
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 use eightfold_common::ArrayIndex;
 use num_traits::AsPrimitive;
 
-use crate::{Error, Octree, ProxyData};
+use crate::{Arena, Error, Octree, OctreeStorage, ProxyData};
 
 /// Define how to merge two leaves at the same depth into a single instance, to allow collapsing [Octree] branches.
 pub trait LeafMerge: Sized {
@@ -9,27 +9,21 @@ pub trait LeafMerge: Sized {
     fn leaf_merge(a: Self, b: Self) -> Self;
 }
 
-impl<T: LeafMerge, Idx: ArrayIndex> Octree<T, Idx> {
-    #[allow(unsafe_code)]
+impl<T: LeafMerge, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
     fn internal_merge_branch(&mut self, children_idx: Idx) -> Option<T> {
         debug_assert!(self.branch_data.is_init(children_idx.as_()));
         let mut res: Option<T> = None;
-        for c in unsafe { self.branch_data.remove_unchecked(children_idx.as_()) }.unwrap() {
-            match unsafe { self.proxies.remove_unchecked(c.as_()) }
-                .unwrap()
-                .data
-            {
+        for c in self.branch_data.remove(children_idx.as_()).unwrap() {
+            match self.proxies.remove(c.as_()).unwrap().data {
                 ProxyData::Void => {}
                 ProxyData::Leaf(l_idx) => match res {
                     Some(r) => {
                         res = Some(T::leaf_merge(
                             r,
-                            unsafe { self.leaf_data.remove_unchecked(l_idx.as_()) }.unwrap(),
+                            self.leaf_data.remove(l_idx.as_()).unwrap(),
                         ))
                     }
-                    None => {
-                        res = Some(unsafe { self.leaf_data.remove_unchecked(l_idx.as_()) }.unwrap())
-                    }
+                    None => res = Some(self.leaf_data.remove(l_idx.as_()).unwrap()),
                 },
                 ProxyData::Branch(b_idx) => {
                     let data = match self.internal_merge_branch(b_idx) {
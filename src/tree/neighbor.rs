@@ -0,0 +1,142 @@
+use eightfold_common::ArrayIndex;
+
+use crate::{Arena, Direction, Error, Octant, Octree, OctreeStorage, ProxyData};
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// The [Octant] a node occupies within its parent, or `None` if the node is the root.
+    ///
+    /// # Panics
+    ///
+    /// * `index` ∉ `self.proxies`
+    pub fn octant_of_unchecked(&self, index: Idx) -> Option<Octant> {
+        let parent = self.proxies[index.as_()].parent;
+        if parent == index {
+            return None;
+        }
+        match self.proxies[parent.as_()].data {
+            ProxyData::Branch(b_idx) => self.branch_data[b_idx.as_()]
+                .iter()
+                .position(|&c| c == index)
+                .map(|o| Octant(o as u8)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Find the neighbor of a node in a given [Direction].
+    ///
+    /// The result is the node at the same depth as `node` which lies across the face, edge, or
+    /// corner given by `dir`; if that node doesn't exist, its deepest existing ancestor is given
+    /// instead. Returns `None` if the neighbor would lie outside of the tree.
+    ///
+    /// This only follows parent links upwards until the neighbor shares an ancestor with `node`,
+    /// then descends from that ancestor, so it never has to start from the root.
+    ///
+    /// # Panics
+    ///
+    /// * `node` ∉ `self.proxies`
+    pub fn neighbor_of_unchecked(&self, node: Idx, dir: Direction) -> Option<Idx> {
+        // octants of the neighbor's ancestors, from deepest to shallowest
+        let mut path = Vec::new();
+        let mut carry = Some(dir);
+        let mut current = node;
+        while let Some(d) = carry {
+            let oct = self.octant_of_unchecked(current)?;
+            let (n_oct, n_carry) = d.step(oct);
+            path.push(n_oct);
+            carry = n_carry;
+            current = self.proxies[current.as_()].parent;
+        }
+        // `current` is now the deepest common ancestor of `node` and its neighbor
+        while let Some(oct) = path.pop() {
+            match self.proxies[current.as_()].data {
+                ProxyData::Branch(b_idx) => {
+                    current = self.branch_data[b_idx.as_()][usize::from(oct)];
+                }
+                ProxyData::Void | ProxyData::Leaf(_) => break,
+            }
+        }
+        Some(current)
+    }
+
+    /// Find the neighbor of a node in a given [Direction].
+    ///
+    /// See [`Self::neighbor_of_unchecked`].
+    ///
+    /// # Errors
+    ///
+    /// * [`InvalidIndex`](Error::InvalidIndex) if `node` is not a valid index into `self.proxies`.
+    pub fn neighbor_of(&self, node: Idx, dir: Direction) -> Result<Option<Idx>, Error<Idx>> {
+        if !self.proxies.is_init(node.as_()) {
+            return Err(Error::InvalidIndex(node));
+        }
+        Ok(self.neighbor_of_unchecked(node, dir))
+    }
+
+    /// Find the neighbors of a node in all 26 [directions](Direction::all).
+    ///
+    /// # Panics
+    ///
+    /// * `node` ∉ `self.proxies`
+    pub fn neighbors_of_unchecked(&self, node: Idx) -> impl Iterator<Item = (Direction, Idx)> + '_ {
+        Direction::all().filter_map(move |d| Some((d, self.neighbor_of_unchecked(node, d)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, Octant, Octree};
+
+    /// Build a tree of height 2 where the root's [Octant] 0 is split.
+    fn tree() -> (Octree<u8, u32>, [u32; 8], [u32; 8]) {
+        let mut tree = Octree::<u8, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[0]).unwrap().0;
+        (tree, root, inner)
+    }
+
+    #[test]
+    fn same_parent() {
+        let (tree, _, inner) = tree();
+        let pos_i = Direction::FACES[1];
+        assert_eq!(tree.neighbor_of_unchecked(inner[0], pos_i), Some(inner[4]));
+        assert_eq!(tree.neighbor_of_unchecked(inner[4], -pos_i), Some(inner[0]));
+        assert_eq!(
+            tree.neighbor_of_unchecked(inner[0], Direction::corner(Octant(7))),
+            Some(inner[7])
+        );
+    }
+
+    #[test]
+    fn across_parents() {
+        let (tree, root, inner) = tree();
+        // same-depth neighbor doesn't exist; its ancestor does
+        assert_eq!(
+            tree.neighbor_of_unchecked(inner[4], Direction::FACES[1]),
+            Some(root[4])
+        );
+        assert_eq!(
+            tree.neighbor_of_unchecked(inner[7], Direction::corner(Octant(7))),
+            Some(root[7])
+        );
+        assert_eq!(
+            tree.neighbor_of_unchecked(root[4], Direction::FACES[0]),
+            Some(root[0])
+        );
+    }
+
+    #[test]
+    fn outside() {
+        let (tree, root, inner) = tree();
+        assert_eq!(tree.neighbor_of_unchecked(0, Direction::FACES[0]), None);
+        assert_eq!(
+            tree.neighbor_of_unchecked(root[0], Direction::FACES[0]),
+            None
+        );
+        assert_eq!(
+            tree.neighbor_of_unchecked(inner[4], Direction::FACES[0]),
+            Some(inner[0])
+        );
+        assert_eq!(tree.neighbors_of_unchecked(inner[0]).count(), 7);
+        assert!(tree.neighbor_of(99, Direction::FACES[0]).is_err());
+    }
+}
@@ -3,7 +3,10 @@ use std::{marker::PhantomData, ops::Range};
 use eightfold_common::ArrayIndex;
 use num_traits::AsPrimitive;
 
-use crate::{Error, Octant, Octree, OctreeSlice, Proxy, ProxyData};
+use crate::{
+    Arena, Direction, Error, Octant, Octree, OctreeSlice, OctreeStorage, Proxy, ProxyData,
+    StableStorage,
+};
 
 #[derive(Debug, Clone, Copy)]
 pub enum NodeData<'tree, T, Idx: ArrayIndex> {
@@ -13,7 +16,7 @@ pub enum NodeData<'tree, T, Idx: ArrayIndex> {
 }
 
 impl<'tree, T, Idx: ArrayIndex> NodeData<'tree, T, Idx> {
-    fn from_tree_proxy(tree: &'tree Octree<T, Idx>, prox: Proxy<Idx>) -> Self {
+    fn from_tree_proxy<S: OctreeStorage>(tree: &'tree Octree<T, Idx, S>, prox: Proxy<Idx>) -> Self {
         match prox.data {
             crate::ProxyData::Void => NodeData::Void,
             crate::ProxyData::Leaf(idx) => NodeData::Leaf(&tree.leaf_data[idx.as_()]),
@@ -23,34 +26,53 @@ impl<'tree, T, Idx: ArrayIndex> NodeData<'tree, T, Idx> {
 }
 
 #[derive(Debug, Clone, Copy)]
-pub struct Node<'tree, T, Idx: ArrayIndex, Data: 'tree = NodeData<'tree, T, Idx>> {
-    tree: &'tree Octree<T, Idx>,
+pub struct Node<
+    'tree,
+    T,
+    Idx: ArrayIndex,
+    S: OctreeStorage = StableStorage,
+    Data: 'tree = NodeData<'tree, T, Idx>,
+> {
+    tree: &'tree Octree<T, Idx, S>,
     proxy: Proxy<Idx>,
     index: Idx,
     data: Data,
 }
 
-pub type Void<'tree, T, Idx> = Node<'tree, T, Idx, ()>;
-pub type Leaf<'tree, T, Idx> = Node<'tree, T, Idx, &'tree T>;
-pub type Branch<'tree, T, Idx> = Node<'tree, T, Idx, &'tree [Idx; 8]>;
+pub type Void<'tree, T, Idx, S = StableStorage> = Node<'tree, T, Idx, S, ()>;
+pub type Leaf<'tree, T, Idx, S = StableStorage> = Node<'tree, T, Idx, S, &'tree T>;
+pub type Branch<'tree, T, Idx, S = StableStorage> = Node<'tree, T, Idx, S, &'tree [Idx; 8]>;
 
-impl<'tree, T, Idx: ArrayIndex, Data: 'tree> Node<'tree, T, Idx, Data> {
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage, Data: 'tree> Node<'tree, T, Idx, S, Data> {
     #[inline(always)]
     pub const fn index(&self) -> Idx {
         self.index
     }
 
-    pub fn parent(&self) -> Option<Branch<'tree, T, Idx>> {
-        todo!()
+    /// Get the parent of this node, unless it's the root.
+    pub fn parent(&self) -> Option<Branch<'tree, T, Idx, S>> {
+        match self.proxy.parent {
+            parent if parent == self.index => None,
+            parent => self.tree.branch(parent),
+        }
+    }
+
+    /// Get the neighbor of this node in a given [Direction], or its deepest existing ancestor.
+    ///
+    /// See [`Octree::neighbor_of_unchecked`].
+    pub fn neighbor(&self, dir: Direction) -> Option<Node<'tree, T, Idx, S>> {
+        self.tree
+            .neighbor_of_unchecked(self.index, dir)
+            .and_then(|i| self.tree.node(i))
     }
 
     #[inline(always)]
-    pub fn into_inner(self) -> (&'tree Octree<T, Idx>, Proxy<Idx>, Idx, Data) {
+    pub fn into_inner(self) -> (&'tree Octree<T, Idx, S>, Proxy<Idx>, Idx, Data) {
         (self.tree, self.proxy, self.index, self.data)
     }
 }
 
-impl<'tree, T, Idx: ArrayIndex> Node<'tree, T, Idx, NodeData<'tree, T, Idx>> {}
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> Node<'tree, T, Idx, S, NodeData<'tree, T, Idx>> {}
 
 #[derive(Debug)]
 pub enum NodeDataMut<'tree, T, Idx: ArrayIndex> {
@@ -60,7 +82,10 @@ pub enum NodeDataMut<'tree, T, Idx: ArrayIndex> {
 }
 
 impl<'tree, T, Idx: ArrayIndex> NodeDataMut<'tree, T, Idx> {
-    fn from_tree_proxy(tree: &'tree mut Octree<T, Idx>, prox: Proxy<Idx>) -> Self {
+    fn from_tree_proxy<S: OctreeStorage>(
+        tree: &'tree mut Octree<T, Idx, S>,
+        prox: Proxy<Idx>,
+    ) -> Self {
         match prox.data {
             crate::ProxyData::Void => Self::Void,
             crate::ProxyData::Leaf(idx) => Self::Leaf(&mut tree.leaf_data[idx.as_()]),
@@ -70,16 +95,39 @@ impl<'tree, T, Idx: ArrayIndex> NodeDataMut<'tree, T, Idx> {
 }
 
 #[derive(Debug)]
-pub struct NodeMut<'tree, T, Idx: ArrayIndex, Data: 'tree = NodeDataMut<'tree, T, Idx>> {
-    tree: &'tree mut Octree<T, Idx>,
+pub struct NodeMut<
+    'tree,
+    T,
+    Idx: ArrayIndex,
+    S: OctreeStorage = StableStorage,
+    Data: 'tree = NodeDataMut<'tree, T, Idx>,
+> {
+    tree: &'tree mut Octree<T, Idx, S>,
     proxy: Proxy<Idx>,
     index: Idx,
     _data: PhantomData<Data>,
 }
 
-impl<'tree, T, Idx: ArrayIndex, Data: 'tree> NodeMut<'tree, T, Idx, Data> {
-    pub fn parent(self) -> Option<BranchMut<'tree, T, Idx>> {
-        todo!()
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage, Data: 'tree> NodeMut<'tree, T, Idx, S, Data> {
+    #[inline(always)]
+    pub const fn index(&self) -> Idx {
+        self.index
+    }
+
+    /// Move to the parent of this node, unless it's the root.
+    pub fn parent(self) -> Option<BranchMut<'tree, T, Idx, S>> {
+        match self.proxy.parent {
+            parent if parent == self.index => None,
+            parent => self.tree.branch_mut(parent),
+        }
+    }
+
+    /// Move to the neighbor of this node in a given [Direction], or its deepest existing ancestor.
+    ///
+    /// See [`Octree::neighbor_of_unchecked`].
+    pub fn neighbor(self, dir: Direction) -> Option<NodeMut<'tree, T, Idx, S>> {
+        let index = self.tree.neighbor_of_unchecked(self.index, dir)?;
+        self.tree.node_mut(index)
     }
 
     pub fn data(&'tree self) -> NodeData<'tree, T, Idx> {
@@ -90,7 +138,7 @@ impl<'tree, T, Idx: ArrayIndex, Data: 'tree> NodeMut<'tree, T, Idx, Data> {
         NodeDataMut::from_tree_proxy(self.tree, self.proxy)
     }
 
-    pub fn split(self) -> Result<BranchMut<'tree, T, Idx>, Error<Idx>>
+    pub fn split(self) -> Result<BranchMut<'tree, T, Idx, S>, Error<Idx>>
     where
         usize: AsPrimitive<Idx>,
         Range<Idx>: Iterator,
@@ -104,6 +152,10 @@ impl<'tree, T, Idx: ArrayIndex, Data: 'tree> NodeMut<'tree, T, Idx, Data> {
         })
     }
 
+    /// Get the leaf data of this node, inserting the result of `f` if this node is void.
+    ///
+    /// With [collapse-on-insert](Octree::set_collapse_on_insert) enabled, a newly inserted leaf
+    /// may be collapsed into an ancestor, in which case `self` moves to that ancestor.
     pub fn leaf_data_or_insert_with<'data>(
         &'data mut self,
         f: impl FnOnce() -> T,
@@ -115,29 +167,29 @@ impl<'tree, T, Idx: ArrayIndex, Data: 'tree> NodeMut<'tree, T, Idx, Data> {
             ProxyData::Leaf(idx) => Ok(&mut self.tree.leaf_data[idx.as_()]),
             ProxyData::Void => {
                 let data_idx = self.tree.leaf_data.push(f());
-                self.proxy = {
-                    let prox = &mut self.tree.proxies[self.index.as_()];
-                    prox.data = ProxyData::Leaf(data_idx.as_());
-                    *prox
-                };
+                self.tree.proxies[self.index.as_()].data = ProxyData::Leaf(data_idx.as_());
+                // the new leaf data keeps its slot if its node is collapsed into an ancestor
+                self.index = self.tree.collapse_ancestors(self.index);
+                self.proxy = self.tree.proxies[self.index.as_()];
                 Ok(&mut self.tree.leaf_data[data_idx])
             }
             ProxyData::Branch(_) => Err(Error::CannotInsertIntoBranch),
         }
     }
 
-    pub fn into_leaf_mut(self) -> Result<LeafMut<'tree, T, Idx>, Error<Idx>> {
+    pub fn into_leaf_mut(self) -> Result<LeafMut<'tree, T, Idx, S>, Error<Idx>> {
         self.tree
             .leaf_mut(self.index)
             .ok_or(Error::NotALeaf(self.index))
     }
 }
 
-pub type VoidMut<'tree, T, Idx> = NodeMut<'tree, T, Idx, ()>;
-pub type LeafMut<'tree, T, Idx> = NodeMut<'tree, T, Idx, &'tree mut T>;
-pub type BranchMut<'tree, T, Idx> = NodeMut<'tree, T, Idx, &'tree mut [Idx; 8]>;
+pub type VoidMut<'tree, T, Idx, S = StableStorage> = NodeMut<'tree, T, Idx, S, ()>;
+pub type LeafMut<'tree, T, Idx, S = StableStorage> = NodeMut<'tree, T, Idx, S, &'tree mut T>;
+pub type BranchMut<'tree, T, Idx, S = StableStorage> =
+    NodeMut<'tree, T, Idx, S, &'tree mut [Idx; 8]>;
 
-impl<'tree, T, Idx: ArrayIndex> LeafMut<'tree, T, Idx> {
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> LeafMut<'tree, T, Idx, S> {
     pub fn leaf_data(&'tree self) -> &'tree T {
         &self.tree.leaf_data[self.proxy.leaf().unwrap().as_()]
     }
@@ -147,7 +199,7 @@ impl<'tree, T, Idx: ArrayIndex> LeafMut<'tree, T, Idx> {
     }
 }
 
-impl<'tree, T, Idx: ArrayIndex> BranchMut<'tree, T, Idx> {
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> BranchMut<'tree, T, Idx, S> {
     pub fn child_indices(&'tree self) -> &'tree [Idx; 8] {
         &self.tree.branch_data[self.proxy.branch().unwrap().as_()]
     }
@@ -156,15 +208,15 @@ impl<'tree, T, Idx: ArrayIndex> BranchMut<'tree, T, Idx> {
         &mut self.tree.branch_data[self.proxy.branch().unwrap().as_()]
     }
 
-    pub fn child(self, oct: Octant) -> NodeMut<'tree, T, Idx> {
+    pub fn child(self, oct: Octant) -> NodeMut<'tree, T, Idx, S> {
         self.tree
             .node_mut(self.child_indices()[usize::from(oct)])
             .unwrap()
     }
 }
 
-impl<T, Idx: ArrayIndex> Octree<T, Idx> {
-    pub fn node<'tree>(&'tree self, index: Idx) -> Option<Node<'tree, T, Idx>> {
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    pub fn node<'tree>(&'tree self, index: Idx) -> Option<Node<'tree, T, Idx, S>> {
         self.proxies.get(index.as_()).copied().map(|proxy| Node {
             tree: self,
             proxy,
@@ -173,7 +225,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         })
     }
 
-    pub fn node_mut<'tree>(&'tree mut self, index: Idx) -> Option<NodeMut<'tree, T, Idx>> {
+    pub fn node_mut<'tree>(&'tree mut self, index: Idx) -> Option<NodeMut<'tree, T, Idx, S>> {
         self.proxies.get(index.as_()).copied().map(|proxy| NodeMut {
             tree: self,
             proxy,
@@ -182,7 +234,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         })
     }
 
-    pub fn void<'tree>(&'tree self, index: Idx) -> Option<Void<'tree, T, Idx>> {
+    pub fn void<'tree>(&'tree self, index: Idx) -> Option<Void<'tree, T, Idx, S>> {
         self.proxies
             .get(index.as_())
             .copied()
@@ -197,7 +249,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
             })
     }
 
-    pub fn leaf<'tree>(&'tree self, index: Idx) -> Option<Leaf<'tree, T, Idx>> {
+    pub fn leaf<'tree>(&'tree self, index: Idx) -> Option<Leaf<'tree, T, Idx, S>> {
         self.proxies.get(index.as_()).copied().and_then(|proxy| {
             proxy.leaf().map(|idx| Leaf {
                 tree: self,
@@ -208,7 +260,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         })
     }
 
-    pub fn leaf_mut<'tree>(&'tree mut self, index: Idx) -> Option<LeafMut<'tree, T, Idx>> {
+    pub fn leaf_mut<'tree>(&'tree mut self, index: Idx) -> Option<LeafMut<'tree, T, Idx, S>> {
         self.proxies.get(index.as_()).copied().and_then(|proxy| {
             proxy.leaf().map(|_| LeafMut {
                 tree: self,
@@ -219,7 +271,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         })
     }
 
-    pub fn branch<'tree>(&'tree self, index: Idx) -> Option<Branch<'tree, T, Idx>> {
+    pub fn branch<'tree>(&'tree self, index: Idx) -> Option<Branch<'tree, T, Idx, S>> {
         self.proxies.get(index.as_()).copied().and_then(|proxy| {
             proxy.branch().map(|idx| Branch {
                 tree: self,
@@ -230,7 +282,7 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         })
     }
 
-    pub fn branch_mut<'tree>(&'tree mut self, index: Idx) -> Option<BranchMut<'tree, T, Idx>> {
+    pub fn branch_mut<'tree>(&'tree mut self, index: Idx) -> Option<BranchMut<'tree, T, Idx, S>> {
         self.proxies.get(index.as_()).copied().and_then(|proxy| {
             proxy.branch().map(|_| BranchMut {
                 tree: self,
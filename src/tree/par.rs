@@ -0,0 +1,328 @@
+//! Parallel traversal using [rayon].
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+use rayon::{
+    iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer},
+    prelude::*,
+};
+
+use crate::{NodePoint, Octant, Octree, OctreeStorage, Proxy, ProxyData, StableStorage, TreeSlice};
+
+/// A parallel depth-first iterator over nodes in an [Octree].
+///
+/// Work is split at branch boundaries, by dividing the children of a branch between threads.
+/// Collecting the iterator yields nodes in the same order as a sequential pre-order traversal by
+/// [Octant] ordering.
+#[derive(Debug)]
+pub struct ParNodeIter<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    tree: &'tree Octree<T, Idx, S>,
+    root: Idx,
+    root_point: NodePoint<Idx>,
+}
+
+/// A pending entry in a [`NodeProducer`]; `expanded` nodes have had their children split off
+/// already, and only yield themselves.
+#[derive(Debug, Clone, Copy)]
+struct Pending<Idx: ArrayIndex> {
+    index: Idx,
+    point: NodePoint<Idx>,
+    expanded: bool,
+}
+
+struct NodeProducer<'tree, T, Idx: ArrayIndex, S: OctreeStorage> {
+    tree: &'tree Octree<T, Idx, S>,
+    /// Pending subtrees, in traversal order.
+    pending: Vec<Pending<Idx>>,
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> NodeProducer<'tree, T, Idx, S>
+where
+    u8: AsPrimitive<Idx>,
+{
+    /// The children of a pending branch which hasn't been expanded yet.
+    fn children(
+        tree: &'tree Octree<T, Idx, S>,
+        p: Pending<Idx>,
+    ) -> Option<impl Iterator<Item = Pending<Idx>> + 'tree> {
+        match (p.expanded, tree.get(p.index).data) {
+            (false, ProxyData::Branch(b_idx)) => Some(
+                tree.branch_data()[b_idx.as_()]
+                    .into_iter()
+                    .zip(Octant::ALL)
+                    .map(move |(index, oct)| Pending {
+                        index,
+                        point: p.point + oct,
+                        expanded: false,
+                    }),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl<'tree, T: Sync, Idx: ArrayIndex + Send + Sync, S: OctreeStorage> UnindexedProducer
+    for NodeProducer<'tree, T, Idx, S>
+where
+    u8: AsPrimitive<Idx>,
+    Octree<T, Idx, S>: Sync,
+{
+    type Item = (Idx, Proxy<Idx>, NodePoint<Idx>);
+
+    fn split(mut self) -> (Self, Option<Self>) {
+        if self.pending.len() == 1 {
+            let root = self.pending[0];
+            let Some(children) = Self::children(self.tree, root) else {
+                return (self, None);
+            };
+            self.pending = vec![Pending {
+                expanded: true,
+                ..root
+            }];
+            self.pending.extend(children);
+        }
+        let right = self.pending.split_off(self.pending.len() / 2);
+        let right = Self {
+            tree: self.tree,
+            pending: right,
+        };
+        (self, Some(right))
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        let Self {
+            tree,
+            pending: mut stack,
+        } = self;
+        stack.reverse();
+        while let Some(p) = stack.pop() {
+            if folder.full() {
+                break;
+            }
+            let len = stack.len();
+            if let Some(children) = Self::children(tree, p) {
+                stack.extend(children);
+                stack[len..].reverse();
+            }
+            folder = folder.consume((p.index, tree.get(p.index), p.point));
+        }
+        folder
+    }
+}
+
+impl<'tree, T: Sync, Idx: ArrayIndex + Send + Sync, S: OctreeStorage> ParallelIterator
+    for ParNodeIter<'tree, T, Idx, S>
+where
+    u8: AsPrimitive<Idx>,
+    Octree<T, Idx, S>: Sync,
+{
+    type Item = (Idx, Proxy<Idx>, NodePoint<Idx>);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge_unindexed(
+            NodeProducer {
+                tree: self.tree,
+                pending: vec![Pending {
+                    index: self.root,
+                    point: self.root_point,
+                    expanded: false,
+                }],
+            },
+            consumer,
+        )
+    }
+}
+
+/// Fold a subtree bottom-up, folding the children of each branch in parallel.
+fn par_fold_from<T, Idx, S, R, L, B>(
+    tree: &Octree<T, Idx, S>,
+    index: Idx,
+    point: NodePoint<Idx>,
+    leaf: &L,
+    branch: &B,
+) -> Option<R>
+where
+    T: Sync,
+    Idx: ArrayIndex + Send + Sync,
+    S: OctreeStorage,
+    Octree<T, Idx, S>: Sync,
+    u8: AsPrimitive<Idx>,
+    R: Send,
+    L: Fn(&T, NodePoint<Idx>) -> R + Sync,
+    B: Fn([Option<R>; 8], NodePoint<Idx>) -> R + Sync,
+{
+    match tree.get(index).data {
+        ProxyData::Void => None,
+        ProxyData::Leaf(l_idx) => Some(leaf(&tree.leaf_data()[l_idx.as_()], point)),
+        ProxyData::Branch(b_idx) => {
+            let indices = &tree.branch_data()[b_idx.as_()];
+            let mut results = Vec::with_capacity(8);
+            (0..8u8)
+                .into_par_iter()
+                .map(|i| {
+                    let oct = Octant(i);
+                    par_fold_from(tree, indices[usize::from(oct)], point + oct, leaf, branch)
+                })
+                .collect_into_vec(&mut results);
+            let mut children: [Option<R>; 8] = Default::default();
+            for (child, res) in children.iter_mut().zip(results) {
+                *child = res;
+            }
+            Some(branch(children, point))
+        }
+    }
+}
+
+impl<'tree, T: Sync, Idx: ArrayIndex + Send + Sync, S: OctreeStorage> TreeSlice<'tree, T, Idx, S>
+where
+    u8: AsPrimitive<Idx>,
+    Octree<T, Idx, S>: Sync,
+{
+    /// Parallel depth-first iterator through all nodes in `self`, as `(index, proxy, point)`.
+    pub fn par_node_iter(&self) -> ParNodeIter<'tree, T, Idx, S> {
+        ParNodeIter {
+            tree: self.base(),
+            root: self.root_idx(),
+            root_point: self.base().node_point_of_unchecked(self.root_idx()),
+        }
+    }
+
+    /// Parallel depth-first iterator through all leafs in `self`.
+    pub fn par_leaf_iter(&self) -> impl ParallelIterator<Item = (&'tree T, NodePoint<Idx>)> {
+        let tree = self.base();
+        self.par_node_iter()
+            .filter_map(move |(_, prox, np)| match prox.data {
+                ProxyData::Leaf(l_idx) => Some((&tree.leaf_data()[l_idx.as_()], np)),
+                _ => None,
+            })
+    }
+
+    /// Fold `self` bottom-up, mapping each leaf with `leaf` & combining the results for the
+    /// children of each branch with `branch`, where void children are `None`.
+    ///
+    /// The children of each branch are folded in parallel. Returns `None` if the root of `self`
+    /// is void.
+    pub fn par_fold<R, L, B>(&self, leaf: L, branch: B) -> Option<R>
+    where
+        R: Send,
+        L: Fn(&T, NodePoint<Idx>) -> R + Sync,
+        B: Fn([Option<R>; 8], NodePoint<Idx>) -> R + Sync,
+    {
+        let root = self.root_idx();
+        par_fold_from(
+            self.base(),
+            root,
+            self.base().node_point_of_unchecked(root),
+            &leaf,
+            &branch,
+        )
+    }
+}
+
+impl<T: Sync, Idx: ArrayIndex + Send + Sync, S: OctreeStorage> Octree<T, Idx, S>
+where
+    u8: AsPrimitive<Idx>,
+    Octree<T, Idx, S>: Sync,
+{
+    /// Parallel depth-first iterator through all nodes, as `(index, proxy, point)`.
+    ///
+    /// See [`TreeSlice::par_node_iter`].
+    #[inline]
+    pub fn par_node_iter(&self) -> ParNodeIter<'_, T, Idx, S> {
+        self.as_slice().par_node_iter()
+    }
+
+    /// Parallel depth-first iterator through all leafs.
+    ///
+    /// See [`TreeSlice::par_leaf_iter`].
+    #[inline]
+    pub fn par_leaf_iter(&self) -> impl ParallelIterator<Item = (&T, NodePoint<Idx>)> {
+        self.as_slice().par_leaf_iter()
+    }
+
+    /// Fold the whole tree bottom-up; see [`TreeSlice::par_fold`].
+    #[inline]
+    pub fn par_fold<R, L, B>(&self, leaf: L, branch: B) -> Option<R>
+    where
+        R: Send,
+        L: Fn(&T, NodePoint<Idx>) -> R + Sync,
+        B: Fn([Option<R>; 8], NodePoint<Idx>) -> R + Sync,
+    {
+        self.as_slice().par_fold(leaf, branch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use crate::{Octree, OctreeSlice, OctreeStorage, StableStorage, VecStorage};
+
+    fn tree<S: OctreeStorage>() -> Octree<u32, u32, S> {
+        let mut tree = Octree::new();
+        let mut stack = vec![(0, 0)];
+        while let Some((idx, depth)) = stack.pop() {
+            if depth == 3 {
+                tree.set_leaf(idx, idx);
+            } else if idx % 3 != 1 {
+                stack.extend(tree.split(idx).unwrap().0.map(|c| (c, depth + 1)));
+            }
+        }
+        tree
+    }
+
+    #[test]
+    fn par_iter() {
+        let tree = tree::<StableStorage>();
+        let leafs = tree.par_leaf_iter().collect::<Vec<_>>();
+        assert!(leafs.iter().copied().eq(tree.leaf_dfi()));
+
+        let nodes = tree.par_node_iter().collect::<Vec<_>>();
+        assert_eq!(nodes.len(), tree.proxies().len_init());
+        let leaf_nodes = nodes.iter().filter_map(|(_, prox, np)| {
+            prox.leaf()
+                .map(|l_idx| (&tree.leaf_data()[l_idx as usize], *np))
+        });
+        assert!(leaf_nodes.eq(tree.leaf_dfi()));
+
+        let slice = tree.slice(tree.branch_data()[0][2]).unwrap();
+        assert!(slice
+            .par_leaf_iter()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .eq(slice.leaf_dfi()));
+    }
+
+    #[test]
+    fn par_fold() {
+        let tree = tree::<StableStorage>();
+        let sum = tree.par_fold(
+            |l, _| u64::from(*l),
+            |children, _| children.into_iter().flatten().sum(),
+        );
+        assert_eq!(sum, Some(tree.leaf_dfi().map(|(l, _)| u64::from(*l)).sum()));
+        assert_eq!(Octree::<u32, u32>::new().par_fold(|_, _| 1, |_, _| 0), None);
+    }
+
+    #[test]
+    fn vec_storage() {
+        let expected = tree::<StableStorage>();
+        let tree = tree::<VecStorage>();
+        assert!(tree
+            .par_leaf_iter()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .eq(expected.leaf_dfi()));
+        let sum = |l: &u32, _| u64::from(*l);
+        assert_eq!(
+            tree.par_fold(sum, |c, _| c.into_iter().flatten().sum()),
+            expected.par_fold(sum, |c, _| c.into_iter().flatten().sum())
+        );
+    }
+}
@@ -0,0 +1,439 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{Error, NodePoint, Octant, Octree, OctreeSlice, OctreeStorage, ProxyData};
+
+/// A node within a [`PersistentOctree`].
+///
+/// Leaf data & branch children are reference-counted, so that they can be shared between
+/// snapshots.
+#[derive(Debug)]
+pub enum PersistentNode<T> {
+    Void,
+    Leaf(Arc<T>),
+    Branch(Arc<[PersistentNode<T>; 8]>),
+}
+
+impl<T> Clone for PersistentNode<T> {
+    /// Shallow copy; leaf data & children are shared.
+    #[inline]
+    fn clone(&self) -> Self {
+        match self {
+            Self::Void => Self::Void,
+            Self::Leaf(l) => Self::Leaf(Arc::clone(l)),
+            Self::Branch(b) => Self::Branch(Arc::clone(b)),
+        }
+    }
+}
+
+impl<T> Default for PersistentNode<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::Void
+    }
+}
+
+impl<T> PersistentNode<T> {
+    /// Whether `self` and `other` are the same node, shared between snapshots.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Void, Self::Void) => true,
+            (Self::Leaf(a), Self::Leaf(b)) => Arc::ptr_eq(a, b),
+            (Self::Branch(a), Self::Branch(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// An octree with structural sharing, for keeping cheap snapshots of previous states.
+///
+/// [`Self::snapshot`] is *O(1)*: the snapshot shares all of its nodes with `self`. Later edits
+/// copy only the branches on the path from the root to the edited node, so each snapshot remains
+/// unchanged & queryable through [`OctreeSlice`]. Nodes are addressed by [`NodePoint`], as there
+/// are no stable indices.
+#[derive(Debug)]
+pub struct PersistentOctree<T, Idx: ArrayIndex> {
+    root: PersistentNode<T>,
+    _idx: PhantomData<Idx>,
+}
+
+impl<T, Idx: ArrayIndex> Clone for PersistentOctree<T, Idx> {
+    /// See [`Self::snapshot`].
+    #[inline]
+    fn clone(&self) -> Self {
+        self.snapshot()
+    }
+}
+
+impl<T, Idx: ArrayIndex> Default for PersistentOctree<T, Idx> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, Idx: ArrayIndex> PersistentOctree<T, Idx> {
+    /// Construct a new tree with a void root.
+    pub const fn new() -> Self {
+        Self {
+            root: PersistentNode::Void,
+            _idx: PhantomData,
+        }
+    }
+
+    /// Take a snapshot of `self`, sharing all of its nodes.
+    #[inline]
+    pub fn snapshot(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            _idx: PhantomData,
+        }
+    }
+
+    /// The root node of `self`.
+    #[inline]
+    pub fn root(&self) -> &PersistentNode<T> {
+        &self.root
+    }
+
+    /// The [Octant] at a specific level of the path to a [`NodePoint`].
+    #[inline]
+    fn octant_at(p: &NodePoint<Idx>, level: Idx) -> Octant {
+        let shift = p.0.w - Idx::ONE - level;
+        Octant::new(
+            (p.0.x >> shift) & Idx::ONE == Idx::ONE,
+            (p.0.y >> shift) & Idx::ONE == Idx::ONE,
+            (p.0.z >> shift) & Idx::ONE == Idx::ONE,
+        )
+    }
+
+    /// Get the deepest node encompassing a specific [`NodePoint`], and its depth.
+    fn find(&self, p: &NodePoint<Idx>) -> (&PersistentNode<T>, Idx) {
+        let mut node = &self.root;
+        let mut depth = Idx::ZERO;
+        while depth < p.0.w {
+            match node {
+                PersistentNode::Branch(children) => {
+                    node = &children[usize::from(Self::octant_at(p, depth))];
+                }
+                _ => break,
+            }
+            depth += Idx::ONE;
+        }
+        (node, depth)
+    }
+
+    /// Get a mutable reference to the node at a specific [`NodePoint`], copying any shared
+    /// branches along the way & splitting voids as necessary.
+    fn node_at_mut(&mut self, p: &NodePoint<Idx>) -> Result<&mut PersistentNode<T>, Error<Idx>> {
+        let mut node = &mut self.root;
+        let mut depth = Idx::ZERO;
+        while depth < p.0.w {
+            if let PersistentNode::Void = node {
+                *node = PersistentNode::Branch(Arc::default());
+            }
+            node = match node {
+                PersistentNode::Branch(children) => {
+                    &mut Arc::make_mut(children)[usize::from(Self::octant_at(p, depth))]
+                }
+                _ => return Err(Error::CannotSplitLeaf),
+            };
+            depth += Idx::ONE;
+        }
+        Ok(node)
+    }
+
+    /// Divide the node at a specific [`NodePoint`] into a branch, creating it & its ancestors if
+    /// they don't exist.
+    ///
+    /// # Errors
+    ///
+    /// * [`CannotSplitLeaf`](Error::CannotSplitLeaf) if the node or one of its ancestors is a leaf.
+    pub fn split(&mut self, p: &NodePoint<Idx>) -> Result<(), Error<Idx>> {
+        if let (PersistentNode::Branch(_), depth) = self.find(p) {
+            if depth == p.0.w {
+                return Ok(());
+            }
+        }
+        let node = self.node_at_mut(p)?;
+        match node {
+            PersistentNode::Void => {
+                *node = PersistentNode::Branch(Arc::default());
+                Ok(())
+            }
+            PersistentNode::Leaf(_) => Err(Error::CannotSplitLeaf),
+            PersistentNode::Branch(_) => Ok(()),
+        }
+    }
+
+    /// Set the leaf data of the node at a specific [`NodePoint`], creating it & its ancestors if
+    /// they don't exist.
+    ///
+    /// If the node is a branch, the branch is replaced.
+    ///
+    /// # Errors
+    ///
+    /// * [`CannotSplitLeaf`](Error::CannotSplitLeaf) if an ancestor of the node is a leaf.
+    pub fn set_leaf(&mut self, p: &NodePoint<Idx>, data: T) -> Result<(), Error<Idx>> {
+        *self.node_at_mut(p)? = PersistentNode::Leaf(Arc::new(data));
+        Ok(())
+    }
+
+    /// Clear the node at a specific [`NodePoint`], and return whether anything was removed.
+    ///
+    /// Nothing is copied if the node doesn't exist or is already void.
+    pub fn remove(&mut self, p: &NodePoint<Idx>) -> bool {
+        match self.find(p) {
+            (PersistentNode::Void, _) => return false,
+            (_, depth) if depth != p.0.w => return false,
+            _ => {}
+        }
+        // the node exists, so every ancestor is a branch
+        if let Ok(node) = self.node_at_mut(p) {
+            *node = PersistentNode::Void;
+        }
+        true
+    }
+
+    /// Get the node at a specific [`NodePoint`], if it exists.
+    pub fn node(&self, p: &NodePoint<Idx>) -> Option<&PersistentNode<T>> {
+        match self.find(p) {
+            (node, depth) if depth == p.0.w => Some(node),
+            _ => None,
+        }
+    }
+
+    /// The [`NodePoint`] of a node borrowed from `self`, or `None` if it isn't part of `self`.
+    ///
+    /// Nodes don't know their parents, so this searches the whole tree.
+    pub fn node_point_of(&self, node: &PersistentNode<T>) -> Option<NodePoint<Idx>>
+    where
+        u8: AsPrimitive<Idx>,
+    {
+        self.node_dfi()
+            .find(|(n, _)| std::ptr::eq(*n, node))
+            .map(|(_, np)| np)
+    }
+
+    /// Copy `self` into an [Octree], e.g. to edit it by index.
+    ///
+    /// # Errors
+    ///
+    /// * [`IndexExhausted`](Error::IndexExhausted) if the nodes or leafs of `self` wouldn't be
+    ///   addressable by `Idx`; snapshots share nodes, so they can hold many more than an [Octree].
+    pub fn to_octree(&self) -> Result<Octree<T, Idx>, Error<Idx>>
+    where
+        T: Clone,
+        usize: AsPrimitive<Idx>,
+    {
+        let mut res = Octree::new();
+        let mut stack = vec![(&self.root, res.root_idx())];
+        while let Some((node, idx)) = stack.pop() {
+            match node {
+                PersistentNode::Void => {}
+                PersistentNode::Leaf(l) => {
+                    res.try_set_leaf(idx, l.as_ref().clone())?;
+                }
+                PersistentNode::Branch(children) => {
+                    let c_idx = *res.split(idx)?.0;
+                    stack.extend(children.iter().zip(c_idx));
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl<T, Idx: ArrayIndex> OctreeSlice<T, Idx> for PersistentOctree<T, Idx> {
+    type Node<'a>
+        = &'a PersistentNode<T>
+    where
+        Self: 'a;
+
+    #[inline]
+    fn root_node(&self) -> &PersistentNode<T> {
+        &self.root
+    }
+
+    #[inline]
+    fn children_of<'a>(
+        &'a self,
+        node: &'a PersistentNode<T>,
+    ) -> Option<[&'a PersistentNode<T>; 8]> {
+        match node {
+            PersistentNode::Branch(children) => Some(children.each_ref()),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn leaf_of<'a>(&'a self, node: &'a PersistentNode<T>) -> Option<&'a T> {
+        match node {
+            PersistentNode::Leaf(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Clone, Idx: ArrayIndex, S: OctreeStorage> From<&Octree<T, Idx, S>>
+    for PersistentOctree<T, Idx>
+{
+    /// Copy an [Octree] into a [`PersistentOctree`].
+    fn from(tree: &Octree<T, Idx, S>) -> Self {
+        fn convert<T: Clone, Idx: ArrayIndex, S: OctreeStorage>(
+            tree: &Octree<T, Idx, S>,
+            idx: Idx,
+        ) -> PersistentNode<T> {
+            match tree.get(idx).data {
+                ProxyData::Void => PersistentNode::Void,
+                ProxyData::Leaf(l_idx) => {
+                    PersistentNode::Leaf(Arc::new(tree.leaf_data()[l_idx.as_()].clone()))
+                }
+                ProxyData::Branch(b_idx) => {
+                    let children = &tree.branch_data()[b_idx.as_()];
+                    PersistentNode::Branch(Arc::new(children.map(|c| convert(tree, c))))
+                }
+            }
+        }
+        Self {
+            root: convert(tree, tree.root_idx()),
+            _idx: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Error, NodePoint, Octree, OctreeSlice, PersistentNode, PersistentOctree, Proxy, ProxyData,
+    };
+
+    fn leafs(tree: &impl OctreeSlice<u8, u32>) -> Vec<(u8, NodePoint<u32>)> {
+        tree.leaf_dfi().map(|(l, np)| (*l, np)).collect()
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut tree = PersistentOctree::<u8, u32>::new();
+        tree.set_leaf(&NodePoint::new(1, 1, 0, 1), 6).unwrap();
+        tree.set_leaf(&NodePoint::new(0, 2, 3, 2), 35).unwrap();
+        let snap = tree.snapshot();
+        assert!(tree.root().ptr_eq(snap.root()));
+
+        tree.set_leaf(&NodePoint::new(1, 1, 1, 1), 7).unwrap();
+        assert!(tree.remove(&NodePoint::new(0, 2, 3, 2)));
+        assert!(!tree.remove(&NodePoint::new(0, 2, 3, 2)));
+        assert_eq!(
+            leafs(&snap),
+            [
+                (35, NodePoint::new(0, 2, 3, 2)),
+                (6, NodePoint::new(1, 1, 0, 1))
+            ]
+        );
+        assert_eq!(
+            leafs(&tree),
+            [
+                (6, NodePoint::new(1, 1, 0, 1)),
+                (7, NodePoint::new(1, 1, 1, 1))
+            ]
+        );
+
+        // only the root was on the path to the edited node
+        let (PersistentNode::Branch(a), PersistentNode::Branch(b)) = (tree.root(), snap.root())
+        else {
+            panic!("root should be a branch");
+        };
+        assert!(!std::sync::Arc::ptr_eq(a, b));
+        assert!(a[6].ptr_eq(&b[6]));
+    }
+
+    #[test]
+    fn octree_conversion() {
+        let mut tree = Octree::<u8, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.set_leaf(root[6], 6);
+        tree.set_leaf(inner[5], 35);
+        let persistent = PersistentOctree::from(&tree);
+        assert!(persistent.leaf_dfi().eq(tree.leaf_dfi()));
+        let copy = persistent.to_octree().unwrap();
+        assert!(copy.leaf_dfi().eq(tree.leaf_dfi()));
+        assert!(persistent
+            .snapshot()
+            .split(&NodePoint::new(2, 2, 0, 2))
+            .is_err());
+    }
+
+    #[test]
+    fn octree_index_exhausted() {
+        // one leaf in each node at depth 2, which needs 585 nodes in an `Octree`
+        let mut tree = PersistentOctree::<u8, u8>::new();
+        for i in 0..64u8 {
+            let p = NodePoint::new((i & 3) * 2, ((i >> 2) & 3) * 2, (i >> 4) * 2, 3);
+            tree.set_leaf(&p, i).unwrap();
+        }
+        assert_eq!(tree.leaf_dfi().count(), 64);
+        assert!(matches!(tree.to_octree(), Err(Error::IndexExhausted(_))));
+    }
+
+    #[test]
+    fn queries() {
+        // the kind of each node, so that persistent nodes can be compared with proxies
+        fn kind<T>(node: &PersistentNode<T>) -> u8 {
+            match node {
+                PersistentNode::Void => 0,
+                PersistentNode::Leaf(_) => 1,
+                PersistentNode::Branch(_) => 2,
+            }
+        }
+        fn proxy_kind(p: &Proxy<u32>) -> u8 {
+            match p.data {
+                ProxyData::Void => 0,
+                ProxyData::Leaf(_) => 1,
+                ProxyData::Branch(_) => 2,
+            }
+        }
+
+        let mut tree = PersistentOctree::<u8, u32>::new();
+        tree.set_leaf(&NodePoint::new(1, 1, 0, 1), 6).unwrap();
+        tree.set_leaf(&NodePoint::new(0, 2, 3, 2), 35).unwrap();
+        tree.set_leaf(&NodePoint::new(1, 3, 6, 3), 136).unwrap();
+        let snap = tree.snapshot();
+        tree.remove(&NodePoint::new(1, 1, 0, 1));
+        let octree = snap.to_octree().unwrap();
+
+        assert_eq!(snap.height(), octree.height());
+        assert_eq!(snap.grid_size(), 8);
+        assert!(snap.leaf_dfi().eq(octree.leaf_dfi()));
+        assert!(snap.leaf_bfi().eq(octree.leaf_bfi()));
+        assert_eq!(leafs(&snap), leafs(&octree));
+        assert!(snap.node_dfi().map(|(n, np)| (kind(n), np)).eq(octree
+            .node_dfi()
+            .map(|(i, np)| (proxy_kind(&octree.get(i)), np))));
+        assert!(snap.node_bfi().map(|(n, np)| (kind(n), np)).eq(octree
+            .node_bfi()
+            .map(|(i, np)| (proxy_kind(&octree.get(i)), np))));
+        for depth in 0..4 {
+            assert!(snap.level(depth).map(|(n, np)| (kind(n), np)).eq(octree
+                .level(depth)
+                .map(|(i, np)| (proxy_kind(&octree.get(i)), np))));
+        }
+
+        let np = NodePoint::new(1, 3, 6, 3);
+        let node = snap.node(&np).unwrap();
+        assert!(matches!(node, PersistentNode::Leaf(l) if **l == 136));
+        assert!(std::ptr::eq(
+            snap.node_at(&NodePoint::new(3, 7, 13, 4)),
+            node
+        ));
+        assert_eq!(snap.node_point_of(node), Some(np));
+        assert_eq!(tree.node_point_of(node), Some(np));
+        assert!(snap.node(&NodePoint::new(2, 6, 12, 4)).is_none());
+        // the edited tree no longer has this leaf, only the snapshot does
+        let leaf = snap.node(&NodePoint::new(1, 1, 0, 1)).unwrap();
+        assert_eq!(snap.node_point_of(leaf), Some(NodePoint::new(1, 1, 0, 1)));
+        assert_eq!(tree.node_point_of(leaf), None);
+    }
+}
@@ -4,13 +4,15 @@ pub trait TreeIndex {}
 
 /// Indices of data within an [Octree](crate::Octree).
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Proxy<Idx: ArrayIndex> {
     pub(crate) parent: Idx,
     pub(crate) data: ProxyData<Idx>,
 }
 
 /// The type of data pointed to by a [Proxy] and the index of that data.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProxyData<Idx: ArrayIndex> {
     /// Empty
     Void,
@@ -0,0 +1,231 @@
+use eightfold_common::ArrayIndex;
+
+use crate::{Arena, Error, Octant, Octree, OctreeStorage, ProxyData};
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Whether [`Self::remove`] automatically prunes branches left with only void children.
+    #[inline]
+    pub fn auto_prune(&self) -> bool {
+        self.auto_prune
+    }
+
+    /// Enable or disable automatic pruning.
+    ///
+    /// While enabled, [`Self::remove`] walks up from the removed node, collapsing each ancestor
+    /// whose children are all void. Enabling this doesn't prune existing branches; see
+    /// [`Self::prune`].
+    #[inline]
+    pub fn set_auto_prune(&mut self, auto_prune: bool) {
+        self.auto_prune = auto_prune;
+    }
+
+    /// Whether all children of a branch are void.
+    #[inline]
+    fn is_void_branch(&self, children: Idx) -> bool {
+        self.branch_data[children.as_()]
+            .iter()
+            .all(|c| matches!(self.proxies[c.as_()].data, ProxyData::Void))
+    }
+
+    /// Collapse every branch whose children are all void, and return the number of branches
+    /// collapsed.
+    ///
+    /// The freed slots in the proxy & branch stores are reused by later insertions.
+    pub fn prune(&mut self) -> usize {
+        self.prune_subtree_unchecked(self.root)
+    }
+
+    /// [`Self::prune_subtree`], without error checks.
+    ///
+    /// # Panics
+    ///
+    /// * `node` ∉ `self.proxies`
+    pub fn prune_subtree_unchecked(&mut self, node: Idx) -> usize {
+        // parents precede their children in `branches`, so walking it backwards is bottom-up
+        let mut branches = Vec::new();
+        let mut stack = vec![node];
+        while let Some(idx) = stack.pop() {
+            if let ProxyData::Branch(c_idx) = self.proxies[idx.as_()].data {
+                branches.push((idx, c_idx));
+                stack.extend_from_slice(&self.branch_data[c_idx.as_()]);
+            }
+        }
+        let mut res = 0;
+        for (idx, c_idx) in branches.into_iter().rev() {
+            if self.is_void_branch(c_idx) {
+                self.flatten_branch(idx, c_idx, ProxyData::Void);
+                res += 1;
+            }
+        }
+        res
+    }
+
+    /// Collapse every branch within the subtree rooted at `node` (inclusive) whose children are all
+    /// void, and return the number of branches collapsed.
+    ///
+    /// # Errors
+    ///
+    /// * [`InvalidIndex`](Error::InvalidIndex) if `node` is not a valid index into `self.proxies`.
+    pub fn prune_subtree(&mut self, node: Idx) -> Result<usize, Error<Idx>> {
+        if !self.proxies.is_init(node.as_()) {
+            return Err(Error::InvalidIndex(node));
+        }
+        Ok(self.prune_subtree_unchecked(node))
+    }
+
+    /// Walk up from `node`, collapsing each ancestor whose children are all void, and return the
+    /// number of branches collapsed.
+    pub(crate) fn prune_ancestors(&mut self, mut node: Idx) -> usize {
+        let mut res = 0;
+        loop {
+            let parent = self.proxies[node.as_()].parent;
+            if parent == node {
+                break;
+            }
+            match self.proxies[parent.as_()].data {
+                ProxyData::Branch(c_idx) if self.is_void_branch(c_idx) => {
+                    self.flatten_branch(parent, c_idx, ProxyData::Void);
+                    res += 1;
+                }
+                _ => break,
+            }
+            node = parent;
+        }
+        res
+    }
+
+    /// If the root is a branch with exactly one non-void child, make that child the new root, and
+    /// return its [Octant] within the old root.
+    ///
+    /// This is the inverse of [`Self::grow`].
+    pub fn shrink_root(&mut self) -> Option<Octant> {
+        let ProxyData::Branch(c_idx) = self.proxies[self.root.as_()].data else {
+            return None;
+        };
+        let children = self.branch_data[c_idx.as_()];
+        let mut non_void = children
+            .iter()
+            .zip(Octant::ALL)
+            .filter(|(c, _)| !matches!(self.proxies[c.as_()].data, ProxyData::Void));
+        let (&new_root, oct) = non_void.next()?;
+        if non_void.next().is_some() {
+            return None;
+        }
+        for c in children {
+            if c != new_root {
+                self.proxies.remove(c.as_());
+            }
+        }
+        self.branch_data.remove(c_idx.as_());
+        self.proxies.remove(self.root.as_());
+        self.proxies[new_root.as_()].parent = new_root;
+        self.root = new_root;
+        Some(oct)
+    }
+
+    /// Remove the root while it's a branch with only one non-void child, promoting that child,
+    /// and return the [Octant] of each promoted child, from the old root down.
+    ///
+    /// Branches with only void children count as non-void; [prune](Self::prune) first to shrink
+    /// past them.
+    pub fn shrink(&mut self) -> Vec<Octant> {
+        std::iter::from_fn(|| self.shrink_root()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Octant, Octree, OctreeSlice, ProxyData};
+
+    #[test]
+    fn prune() {
+        let mut tree = Octree::<u8, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.split(inner[2]).unwrap();
+        tree.set_leaf(root[6], 6);
+        assert_eq!(tree.prune_subtree(inner[5]).unwrap(), 0);
+        assert_eq!(tree.prune(), 2);
+        assert_eq!(tree.get(root[3]).data, ProxyData::Void);
+        assert_eq!(tree.proxies().len_init(), 9);
+        assert_eq!(tree.branch_data().len_init(), 1);
+
+        tree.remove(root[6]);
+        assert_eq!(tree.prune(), 1);
+        assert_eq!(tree.get(0).data, ProxyData::Void);
+    }
+
+    #[test]
+    fn auto_prune() {
+        let mut tree = Octree::<u8, u32>::new();
+        tree.set_auto_prune(true);
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.set_leaf(root[6], 6);
+        tree.set_leaf(inner[5], 35);
+        tree.remove(inner[5]);
+        assert_eq!(tree.get(root[3]).data, ProxyData::Void);
+        assert!(matches!(tree.get(0).data, ProxyData::Branch(_)));
+        tree.remove(root[6]);
+        assert_eq!(tree.get(0).data, ProxyData::Void);
+        assert_eq!(tree.proxies().len_init(), 1);
+        assert_eq!(tree.branch_data().len_init(), 0);
+    }
+
+    #[test]
+    fn shrink_root() {
+        let mut tree = Octree::<u8, u32>::new();
+        let old_root = tree.root_idx();
+        tree.set_leaf(old_root, 1);
+        let new_root = tree.grow(Octant(5));
+        assert_eq!(tree.shrink_root(), Some(Octant(5)));
+        assert_eq!(tree.root_idx(), old_root);
+        assert_eq!(tree.get(old_root).parent, old_root);
+        assert!(!tree.proxies().is_init(new_root as usize));
+        assert_eq!(tree.shrink_root(), None);
+
+        tree.grow(Octant(2));
+        tree.grow(Octant(7));
+        let root = tree.grow(Octant(0));
+        tree.set_leaf(
+            tree.branch_data()[tree.get(root).branch().unwrap() as usize][4],
+            2,
+        );
+        assert!(tree.shrink().is_empty());
+        tree.remove(tree.node_at(&crate::NodePoint::new(1, 0, 0, 1)));
+        tree.prune();
+        assert_eq!(tree.shrink(), [Octant(0), Octant(7), Octant(2)]);
+        assert_eq!(tree.root_idx(), old_root);
+        assert!(tree
+            .leaf_dfi()
+            .eq([(&1, crate::NodePoint::new(0, 0, 0, 0))]));
+    }
+
+    #[cfg(feature = "spatial")]
+    #[test]
+    fn shrink_to_fit() {
+        use nalgebra::{point, vector};
+
+        use crate::spatial::VoxelOctree;
+
+        let mut tree = VoxelOctree::<u8, f32, u32>::new(vector![1.0, 1.0, 1.0]);
+        tree.grow_to_contain(&point![3.5, 0.5, 0.5]);
+        tree.grow_to_contain(&point![-0.5, 0.5, 0.5]);
+        let aabb = *tree.aabb();
+        tree.node_at_mut(&point![0.5, 0.5, 0.5])
+            .unwrap()
+            .leaf_data_or_insert_with(|| 1)
+            .unwrap();
+        let path = tree.shrink_to_fit();
+        assert_eq!(path.len(), 3);
+        assert_eq!(
+            path.into_iter().fold(aabb, |aabb, oct| aabb.child(oct)),
+            *tree.aabb()
+        );
+        assert!(aabb.contains(&tree.aabb().mins));
+        assert_eq!(tree.aabb().maxs - tree.aabb().mins, vector![1.0, 1.0, 1.0]);
+        assert!(tree.contains(&point![0.5, 0.5, 0.5]));
+        assert_eq!(tree.base().height(), 0);
+        assert!(tree.shrink_to_fit().is_empty());
+    }
+}
@@ -3,7 +3,7 @@ use std::ops::{Shl, ShlAssign, Shr, ShrAssign};
 use eightfold_common::ArrayIndex;
 use simba::scalar::ClosedMul;
 
-use crate::{Error, NodePoint, Octree};
+use crate::{Arena, Error, NodePoint, Octree, OctreeStorage};
 
 /// Define a method for merging two leaf references into a single leaf, to allow sampling leaf data
 /// over an entire branch.
@@ -12,7 +12,7 @@ pub trait LeafSample {
     fn leaf_sample(a: &Self, b: &Self) -> Self;
 }
 
-impl<T: LeafSample + Clone, Idx: ArrayIndex> Octree<T, Idx> {
+impl<T: LeafSample + Clone, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
     fn internal_sample_branch(&self, children_idx: Idx) -> Option<T> {
         let mut res = None;
         for c in self.branch_data[children_idx.as_()] {
@@ -38,6 +38,9 @@ impl<T: LeafSample + Clone, Idx: ArrayIndex> Octree<T, Idx> {
     }
 
     /// Return the merged leaf data of the descendants of a specific branch.
+    ///
+    /// This traverses the whole branch; an [`AugmentedOctree`](crate::AugmentedOctree) summarized
+    /// by [`Sample`](crate::Sample) caches the result for every branch instead.
     pub fn sample_branch(&self, branch_idx: Idx) -> Result<T, Error<Idx>> {
         match self
             .proxies
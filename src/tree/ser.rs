@@ -0,0 +1,47 @@
+//! [Serde](https://serde.rs) support for [Octree].
+
+use eightfold_common::ArrayIndex;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use crate::{Octree, OctreeStorage, Proxy};
+
+/// Mirror of [Octree], deserialized before its invariants have been checked.
+#[derive(Deserialize)]
+#[serde(bound(
+    deserialize = "Idx: Deserialize<'de>, S::Arena<Proxy<Idx>>: Deserialize<'de>, \
+    S::Arena<[Idx; 8]>: Deserialize<'de>, S::Arena<T>: Deserialize<'de>"
+))]
+struct RawOctree<T, Idx: ArrayIndex, S: OctreeStorage> {
+    proxies: S::Arena<Proxy<Idx>>,
+    branch_data: S::Arena<[Idx; 8]>,
+    leaf_data: S::Arena<T>,
+    root: Idx,
+}
+
+impl<'de, T, Idx, S: OctreeStorage> Deserialize<'de> for Octree<T, Idx, S>
+where
+    Idx: ArrayIndex + Deserialize<'de>,
+    S::Arena<Proxy<Idx>>: Deserialize<'de>,
+    S::Arena<[Idx; 8]>: Deserialize<'de>,
+    S::Arena<T>: Deserialize<'de>,
+{
+    /// Deserialize an [Octree], rejecting any input which doesn't describe a well-formed tree.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let RawOctree {
+            proxies,
+            branch_data,
+            leaf_data,
+            root,
+        } = RawOctree::<T, Idx, S>::deserialize(deserializer)?;
+        let res = Self {
+            proxies,
+            branch_data,
+            leaf_data,
+            root,
+            auto_prune: false,
+            collapse_eq: None,
+        };
+        res.validate().map_err(D::Error::custom)?;
+        Ok(res)
+    }
+}
@@ -1,18 +1,20 @@
 use eightfold_common::ArrayIndex;
 use num_traits::AsPrimitive;
-use stablevec::StableVec;
 
-use crate::{Error, LeafIter, NodeIter, NodePoint, Octant, Octree, Proxy, ProxyData};
+use crate::{
+    Arena, Error, LeafBfIter, LeafIter, LeafIterMut, LevelIter, NodeBfIter, NodeIter, NodePoint,
+    Octant, Octree, OctreeStorage, Proxy, ProxyData, StableStorage,
+};
 
 /// A slice representing a subset of an [Octree].
 #[derive(Debug, Clone, Copy)]
-pub struct TreeSlice<'tree, T, Idx: ArrayIndex> {
-    tree: &'tree Octree<T, Idx>,
+pub struct TreeSlice<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    tree: &'tree Octree<T, Idx, S>,
     root: Idx,
 }
 
-impl<T, Idx: ArrayIndex> Octree<T, Idx> {
-    pub fn slice(&self, index: Idx) -> Result<TreeSlice<'_, T, Idx>, Error<Idx>> {
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    pub fn slice(&self, index: Idx) -> Result<TreeSlice<'_, T, Idx, S>, Error<Idx>> {
         if !self.proxies.is_init(index.as_()) {
             Err(Error::InvalidIndex(index))
         } else {
@@ -23,175 +25,364 @@ impl<T, Idx: ArrayIndex> Octree<T, Idx> {
         }
     }
 
-    pub fn as_slice(&self) -> TreeSlice<'_, T, Idx> {
+    pub fn as_slice(&self) -> TreeSlice<'_, T, Idx, S> {
         TreeSlice {
             tree: self,
             root: self.root,
         }
     }
-}
 
-impl<'tree, T, Idx: ArrayIndex> TreeSlice<'tree, T, Idx> {
-    pub fn base(&self) -> &'tree Octree<T, Idx> {
-        self.tree
+    /// Index of the root node.
+    #[inline]
+    pub fn root_idx(&self) -> Idx {
+        self.root
+    }
+
+    #[inline]
+    pub fn proxies(&self) -> &S::Arena<Proxy<Idx>> {
+        &self.proxies
+    }
+
+    #[inline]
+    pub fn branch_data(&self) -> &S::Arena<[Idx; 8]> {
+        &self.branch_data
+    }
+
+    #[inline]
+    pub fn leaf_data(&self) -> &S::Arena<T> {
+        &self.leaf_data
     }
-}
 
-/// Trait for [Octree] references.
-pub trait OctreeSlice<T, Idx: ArrayIndex> {
-    /// Index of the root node.
-    fn root_idx(&self) -> Idx;
-    fn proxies(&self) -> &StableVec<Proxy<Idx>>;
-    fn branch_data(&self) -> &StableVec<[Idx; 8]>;
-    fn leaf_data(&self) -> &StableVec<T>;
     /// Get the [Proxy] representing the node at a given index.
     #[inline]
-    fn get(&self, _i: Idx) -> Proxy<Idx> {
-        self.proxies()[self.root_idx().as_()]
+    pub fn get(&self, i: Idx) -> Proxy<Idx> {
+        self.proxies[i.as_()]
     }
-    /// Get the [Proxy] representing the root node of `self`.
+
+    /// Get the [Proxy] representing the root node.
     #[inline]
-    fn root_proxy(&self) -> Proxy<Idx> {
-        self.get(self.root_idx())
+    pub fn root_proxy(&self) -> Proxy<Idx> {
+        self.get(self.root)
     }
+
     /// The height of a subtree, originating at a specific node.
-    fn height_from(&self, index: Idx) -> Idx;
-    /// The height of the tree calculated from the root.
-    #[inline]
-    fn height(&self) -> Idx {
-        self.height_from(self.root_idx())
+    pub fn height_from(&self, index: Idx) -> Idx {
+        let mut max_depth = Idx::ZERO;
+        let mut node_stack = vec![(self.proxies[index.as_()], Idx::ZERO)];
+        while let Some((p, depth)) = node_stack.pop() {
+            if depth > max_depth {
+                max_depth = depth;
+            }
+            if let ProxyData::Branch(b_idx) = p.data {
+                node_stack.extend(
+                    self.branch_data[b_idx.as_()]
+                        .into_iter()
+                        .map(|c| (self.proxies[c.as_()], depth + Idx::ONE)),
+                );
+            }
+        }
+        max_depth
     }
-    /// The dimensions of the cubical voxel grid represented by this tree, as determined by the
-    /// tree's height.
+
+    /// The children of the node at `index` by [Octant], if it's a branch.
     #[inline]
-    fn grid_size(&self) -> Idx {
-        Idx::ONE << self.height()
+    fn children_at(&self, index: Idx) -> Option<[Idx; 8]> {
+        self.proxies[index.as_()]
+            .branch()
+            .map(|b_idx| self.branch_data[b_idx.as_()])
     }
-    /// Depth-first iterator through all leafs, from deepest to shallowest & nearest to farthest
-    /// (by [Octant] ordering).
-    fn leaf_dfi(&self) -> LeafIter<'_, T, Idx>;
 
-    /// Depth-first iterator through all nodes, by [Octant] ordering.
-    fn node_dfi(&self) -> NodeIter<'_, T, Idx>;
+    /// The data of the node at `index`, if it's a leaf.
+    #[inline]
+    fn leaf_at(&self, index: Idx) -> Option<&T> {
+        self.proxies[index.as_()]
+            .leaf()
+            .map(|l_idx| &self.leaf_data[l_idx.as_()])
+    }
 }
 
-impl<T, Idx: ArrayIndex> OctreeSlice<T, Idx> for Octree<T, Idx> {
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> TreeSlice<'tree, T, Idx, S> {
+    pub fn base(&self) -> &'tree Octree<T, Idx, S> {
+        self.tree
+    }
+
+    /// Index of the root node of `self` within its base tree.
     #[inline]
-    fn root_idx(&self) -> Idx {
+    pub fn root_idx(&self) -> Idx {
         self.root
     }
+}
 
-    #[inline]
-    fn get(&self, i: Idx) -> Proxy<Idx> {
-        self.proxies[i.as_()]
+/// A mutable slice representing a subset of an [Octree].
+#[derive(Debug)]
+pub struct TreeSliceMut<'tree, T, Idx: ArrayIndex, S: OctreeStorage = StableStorage> {
+    tree: &'tree mut Octree<T, Idx, S>,
+    root: Idx,
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// A mutable slice of the subtree rooted at `index`, or [`Error::InvalidIndex`] if there's
+    /// no such node.
+    pub fn slice_mut(&mut self, index: Idx) -> Result<TreeSliceMut<'_, T, Idx, S>, Error<Idx>> {
+        if !self.proxies.is_init(index.as_()) {
+            Err(Error::InvalidIndex(index))
+        } else {
+            Ok(TreeSliceMut {
+                tree: self,
+                root: index,
+            })
+        }
     }
 
-    #[inline]
-    fn proxies(&self) -> &StableVec<Proxy<Idx>> {
-        &self.proxies
+    /// A mutable slice of the whole tree.
+    pub fn as_slice_mut(&mut self) -> TreeSliceMut<'_, T, Idx, S> {
+        let root = self.root;
+        TreeSliceMut { tree: self, root }
     }
+}
 
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> TreeSliceMut<'tree, T, Idx, S> {
+    /// The tree `self` is a slice of.
+    pub fn base(&self) -> &Octree<T, Idx, S> {
+        self.tree
+    }
+
+    /// The tree `self` is a slice of, mutably.
+    pub fn base_mut(&mut self) -> &mut Octree<T, Idx, S> {
+        self.tree
+    }
+
+    /// Index of the root node of `self` within its base tree.
     #[inline]
-    fn branch_data(&self) -> &StableVec<[Idx; 8]> {
-        &self.branch_data
+    pub fn root_idx(&self) -> Idx {
+        self.root
     }
 
+    /// Reborrow `self` as an immutable [`TreeSlice`].
+    pub fn as_slice(&self) -> TreeSlice<'_, T, Idx, S> {
+        TreeSlice {
+            tree: self.tree,
+            root: self.root,
+        }
+    }
+
+    /// Depth-first iterator through mutable references to all leafs, by [Octant] ordering.
+    pub fn leaf_dfi_mut(&mut self) -> LeafIterMut<'_, T, Idx, S> {
+        let root_point = self.tree.node_point_of_unchecked(self.root);
+        LeafIterMut::new(self.tree, self.root, root_point)
+    }
+
+    /// Convert `self` into a depth-first iterator through mutable references to all leafs, by
+    /// [Octant] ordering.
+    pub fn into_leaf_dfi_mut(self) -> LeafIterMut<'tree, T, Idx, S> {
+        let root_point = self.tree.node_point_of_unchecked(self.root);
+        LeafIterMut::new(self.tree, self.root, root_point)
+    }
+}
+
+/// Trait for read-only access to an octree, or to a subtree of one.
+///
+/// Implementors only expose their nodes through [`Self::root_node`], [`Self::children_of`] &
+/// [`Self::leaf_of`]; queries & iterators are built on those, so they work the same way on an
+/// [Octree], a [`TreeSlice`] or a [`PersistentOctree`](crate::PersistentOctree).
+pub trait OctreeSlice<T, Idx: ArrayIndex> {
+    /// A handle to a node of `self`, e.g. its index within an [Octree].
+    type Node<'a>: Copy
+    where
+        Self: 'a;
+
+    /// The root node of `self`.
+    fn root_node(&self) -> Self::Node<'_>;
+
+    /// The children of a node by [Octant], or `None` if it isn't a branch.
+    fn children_of<'a>(&'a self, node: Self::Node<'a>) -> Option<[Self::Node<'a>; 8]>;
+
+    /// The data of a node, or `None` if it isn't a leaf.
+    fn leaf_of<'a>(&'a self, node: Self::Node<'a>) -> Option<&'a T>;
+
+    /// The [`NodePoint`] of the root node of `self`, which is only nonzero for subtrees.
     #[inline]
-    fn leaf_data(&self) -> &StableVec<T> {
-        &self.leaf_data
+    fn root_point(&self) -> NodePoint<Idx> {
+        NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO)
     }
 
-    fn height_from(&self, index: Idx) -> Idx {
+    /// The height of the tree calculated from the root.
+    fn height(&self) -> Idx {
         let mut max_depth = Idx::ZERO;
-        let mut node_stack = vec![(self.proxies[index.as_()], Idx::ZERO)];
-        while let Some((p, depth)) = node_stack.pop() {
+        let mut node_stack = vec![(self.root_node(), Idx::ZERO)];
+        while let Some((node, depth)) = node_stack.pop() {
             if depth > max_depth {
                 max_depth = depth;
             }
-            if let ProxyData::Branch(b_idx) = p.data {
-                node_stack.extend(
-                    self.branch_data[b_idx.as_()]
-                        .into_iter()
-                        .map(|c| (self.proxies[c.as_()], depth + Idx::ONE)),
-                );
+            if let Some(children) = self.children_of(node) {
+                node_stack.extend(children.map(|c| (c, depth + Idx::ONE)));
             }
         }
         max_depth
     }
 
-    fn leaf_dfi(&self) -> LeafIter<'_, T, Idx> {
+    /// The dimensions of the cubical voxel grid represented by this tree, as determined by the
+    /// tree's height.
+    #[inline]
+    fn grid_size(&self) -> Idx {
+        Idx::ONE << self.height()
+    }
+
+    /// Get the deepest node encompassing a specific [`NodePoint`], which must be within the root
+    /// node of `self`.
+    fn node_at(&self, p: &NodePoint<Idx>) -> Self::Node<'_> {
+        let mut node = self.root_node();
+        let mut depth = self.root_point().0.w;
+        while depth < p.0.w {
+            let Some(children) = self.children_of(node) else {
+                break;
+            };
+            // the octant at each level is given by the corresponding bit of each coordinate
+            let shift = p.0.w - Idx::ONE - depth;
+            let oct = Octant::new(
+                (p.0.x >> shift) & Idx::ONE == Idx::ONE,
+                (p.0.y >> shift) & Idx::ONE == Idx::ONE,
+                (p.0.z >> shift) & Idx::ONE == Idx::ONE,
+            );
+            node = children[usize::from(oct)];
+            depth += Idx::ONE;
+        }
+        node
+    }
+
+    /// Depth-first iterator through all leafs, from deepest to shallowest & nearest to farthest
+    /// (by [Octant] ordering).
+    #[inline]
+    fn leaf_dfi(&self) -> LeafIter<'_, T, Idx, Self> {
         LeafIter {
-            tree: self,
-            node_stack: Vec::default(),
-            curr_node: Some((
-                &self.proxies[self.root.as_()],
-                Octant(0),
-                NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
-            )),
+            nodes: self.node_dfi(),
         }
     }
 
-    fn node_dfi(&self) -> NodeIter<'_, T, Idx> {
-        NodeIter {
-            tree: self,
-            node_stack: Vec::default(),
-            curr_node: Some((
-                &self.proxies[self.root.as_()],
-                Octant(0),
-                NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
-            )),
+    /// Depth-first iterator through all nodes, by [Octant] ordering.
+    #[inline]
+    fn node_dfi(&self) -> NodeIter<'_, T, Idx, Self> {
+        NodeIter::new(self)
+    }
+
+    /// Breadth-first iterator through all leafs, from shallowest to deepest & nearest to farthest
+    /// (by [Octant] ordering).
+    #[inline]
+    fn leaf_bfi(&self) -> LeafBfIter<'_, T, Idx, Self> {
+        LeafBfIter {
+            nodes: self.node_bfi(),
         }
     }
+
+    /// Breadth-first iterator through all nodes, by [Octant] ordering.
+    #[inline]
+    fn node_bfi(&self) -> NodeBfIter<'_, T, Idx, Self> {
+        NodeBfIter::new(self)
+    }
+
+    /// Iterator through all nodes exactly `depth` levels below the root of `self`, by [Octant]
+    /// ordering.
+    #[inline]
+    fn level(&self, depth: Idx) -> LevelIter<'_, T, Idx, Self> {
+        LevelIter::new(self, depth)
+    }
 }
 
-impl<'tree, T, Idx: ArrayIndex> OctreeSlice<T, Idx> for TreeSlice<'tree, T, Idx>
+impl<T, Idx: ArrayIndex, S: OctreeStorage> OctreeSlice<T, Idx> for Octree<T, Idx, S> {
+    type Node<'a>
+        = Idx
+    where
+        Self: 'a;
+
+    #[inline]
+    fn root_node(&self) -> Idx {
+        self.root
+    }
+
+    #[inline]
+    fn children_of(&self, node: Idx) -> Option<[Idx; 8]> {
+        self.children_at(node)
+    }
+
+    #[inline]
+    fn leaf_of(&self, node: Idx) -> Option<&T> {
+        self.leaf_at(node)
+    }
+
+    #[inline]
+    fn height(&self) -> Idx {
+        self.height_from(self.root)
+    }
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> OctreeSlice<T, Idx>
+    for TreeSlice<'tree, T, Idx, S>
 where
     u8: AsPrimitive<Idx>,
 {
+    type Node<'a>
+        = Idx
+    where
+        Self: 'a;
+
     #[inline]
-    fn root_idx(&self) -> Idx {
+    fn root_node(&self) -> Idx {
         self.root
     }
 
     #[inline]
-    fn proxies(&self) -> &StableVec<Proxy<Idx>> {
-        &self.tree.proxies
+    fn children_of(&self, node: Idx) -> Option<[Idx; 8]> {
+        self.tree.children_at(node)
     }
 
     #[inline]
-    fn branch_data(&self) -> &StableVec<[Idx; 8]> {
-        &self.tree.branch_data
+    fn leaf_of(&self, node: Idx) -> Option<&T> {
+        self.tree.leaf_at(node)
     }
 
     #[inline]
-    fn leaf_data(&self) -> &StableVec<T> {
-        &self.tree.leaf_data
+    fn root_point(&self) -> NodePoint<Idx> {
+        self.tree.node_point_of_unchecked(self.root)
     }
 
-    fn height_from(&self, index: Idx) -> Idx {
-        self.tree.height_from(index)
+    #[inline]
+    fn height(&self) -> Idx {
+        self.tree.height_from(self.root)
     }
-    fn leaf_dfi(&self) -> LeafIter<'_, T, Idx> {
-        LeafIter {
-            tree: self.tree,
-            node_stack: Vec::default(),
-            curr_node: Some((
-                &self.tree.proxies[self.root.as_()],
-                Octant(0),
-                self.tree.node_point_of_unchecked(self.root),
-            )),
-        }
+}
+
+impl<'tree, T, Idx: ArrayIndex, S: OctreeStorage> OctreeSlice<T, Idx>
+    for TreeSliceMut<'tree, T, Idx, S>
+where
+    u8: AsPrimitive<Idx>,
+{
+    type Node<'a>
+        = Idx
+    where
+        Self: 'a;
+
+    #[inline]
+    fn root_node(&self) -> Idx {
+        self.root
     }
 
-    fn node_dfi(&self) -> NodeIter<'_, T, Idx> {
-        NodeIter {
-            tree: self.tree,
-            node_stack: Vec::default(),
-            curr_node: Some((
-                &self.tree.proxies[self.root.as_()],
-                Octant(0),
-                self.tree.node_point_of_unchecked(self.root),
-            )),
-        }
+    #[inline]
+    fn children_of(&self, node: Idx) -> Option<[Idx; 8]> {
+        self.tree.children_at(node)
+    }
+
+    #[inline]
+    fn leaf_of(&self, node: Idx) -> Option<&T> {
+        self.tree.leaf_at(node)
+    }
+
+    #[inline]
+    fn root_point(&self) -> NodePoint<Idx> {
+        self.tree.node_point_of_unchecked(self.root)
+    }
+
+    #[inline]
+    fn height(&self) -> Idx {
+        self.tree.height_from(self.root)
     }
 }
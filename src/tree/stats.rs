@@ -0,0 +1,195 @@
+use eightfold_common::ArrayIndex;
+
+use crate::{Arena, Octree, OctreeStorage, ProxyData};
+
+/// Memory usage & fragmentation of one of the [Arenas](Arena) backing an [Octree].
+///
+/// Byte counts are measured in the arena's [slots](Arena::slot_size), which may be larger than the
+/// values themselves, but don't include any other bookkeeping of the arena.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Number of stored values.
+    pub len: usize,
+    /// Number of values which can be stored without reallocating.
+    pub capacity: usize,
+    /// Number of vacant entries below the highest stored index.
+    pub holes: usize,
+    /// Bytes occupied by the slots of stored values.
+    pub bytes_used: usize,
+    /// Bytes allocated for slots, whether or not they're occupied.
+    pub bytes_reserved: usize,
+}
+
+impl ArenaStats {
+    fn of<V>(arena: &impl Arena<V>) -> Self {
+        let len = arena.len_init();
+        let end = arena.enumerate().last().map_or(0, |(i, _)| i + 1);
+        Self {
+            len,
+            capacity: arena.capacity(),
+            holes: end - len,
+            bytes_used: len * arena.slot_size(),
+            bytes_reserved: arena.capacity() * arena.slot_size(),
+        }
+    }
+
+    /// Number of vacant entries, including holes.
+    #[inline]
+    pub fn spare_capacity(&self) -> usize {
+        self.capacity - self.len
+    }
+}
+
+/// A report of the memory usage & shape of an [Octree]; see [`Octree::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OctreeStats {
+    /// Usage of the arena of [proxies](crate::Proxy), one per node.
+    pub proxies: ArenaStats,
+    /// Usage of the arena of branch data, holding each branch's children.
+    pub branch_data: ArenaStats,
+    /// Usage of the arena of leaf data.
+    pub leaf_data: ArenaStats,
+    /// Number of leafs at each depth, starting from the root.
+    pub leaf_depths: Vec<usize>,
+    /// Number of branches at each depth, starting from the root.
+    pub branch_depths: Vec<usize>,
+    /// Number of voids at each depth, starting from the root.
+    pub void_depths: Vec<usize>,
+}
+
+impl OctreeStats {
+    /// The depth of the deepest node; 0 if no depths were recorded.
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.void_depths.len().saturating_sub(1)
+    }
+
+    /// Bytes occupied by stored values, across all arenas.
+    #[inline]
+    pub fn bytes_used(&self) -> usize {
+        self.proxies.bytes_used + self.branch_data.bytes_used + self.leaf_data.bytes_used
+    }
+
+    /// Bytes allocated for values, across all arenas.
+    #[inline]
+    pub fn bytes_reserved(&self) -> usize {
+        self.proxies.bytes_reserved
+            + self.branch_data.bytes_reserved
+            + self.leaf_data.bytes_reserved
+    }
+
+    /// The fraction of nodes at each depth which are void.
+    pub fn void_ratios(&self) -> Vec<f64> {
+        (0..self.void_depths.len())
+            .map(|d| {
+                let nodes = self.leaf_depths[d] + self.branch_depths[d] + self.void_depths[d];
+                self.void_depths[d] as f64 / nodes as f64
+            })
+            .collect()
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Measure the memory usage & shape of this tree.
+    ///
+    /// This visits every node, so it's best kept out of hot paths.
+    pub fn stats(&self) -> OctreeStats {
+        let mut res = OctreeStats {
+            proxies: ArenaStats::of(&self.proxies),
+            branch_data: ArenaStats::of(&self.branch_data),
+            leaf_data: ArenaStats::of(&self.leaf_data),
+            ..Default::default()
+        };
+        let mut stack = vec![(self.root, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            if res.void_depths.len() <= depth {
+                res.leaf_depths.push(0);
+                res.branch_depths.push(0);
+                res.void_depths.push(0);
+            }
+            match self.proxies[node.as_()].data {
+                ProxyData::Void => res.void_depths[depth] += 1,
+                ProxyData::Leaf(_) => res.leaf_depths[depth] += 1,
+                ProxyData::Branch(b_idx) => {
+                    res.branch_depths[depth] += 1;
+                    stack.extend(
+                        self.branch_data[b_idx.as_()]
+                            .into_iter()
+                            .map(|c| (c, depth + 1)),
+                    );
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ArenaStats, Octree, OctreeStats, Proxy, VecStorage};
+
+    #[test]
+    fn stats() {
+        let mut tree = Octree::<u64, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[2]).unwrap().0;
+        tree.set_leaf(root[0], 1);
+        tree.set_leaf(inner[0], 2);
+        tree.set_leaf(inner[7], 3);
+        tree.remove(root[0]);
+
+        let stats = tree.stats();
+        assert_eq!(stats.height(), 2);
+        assert_eq!(stats.leaf_depths, [0, 0, 2]);
+        assert_eq!(stats.branch_depths, [1, 1, 0]);
+        assert_eq!(stats.void_depths, [0, 7, 6]);
+        assert_eq!(stats.void_ratios(), [0.0, 0.875, 0.75]);
+        assert_eq!(stats.proxies.len, 17);
+        assert_eq!(
+            stats.leaf_data,
+            ArenaStats {
+                len: 2,
+                capacity: stats.leaf_data.capacity,
+                holes: 1,
+                bytes_used: 16,
+                bytes_reserved: stats.leaf_data.capacity * 8,
+            }
+        );
+        assert_eq!(
+            stats.leaf_data.spare_capacity(),
+            stats.leaf_data.capacity - 2
+        );
+
+        tree.compress();
+        let stats = tree.stats();
+        assert_eq!(stats.leaf_data.holes, 0);
+        assert_eq!(stats.leaf_data.spare_capacity(), 0);
+        assert_eq!(
+            stats.bytes_used(),
+            17 * std::mem::size_of::<Proxy<u32>>() + 2 * 32 + 2 * 8
+        );
+        assert_eq!(stats.bytes_used(), stats.bytes_reserved());
+
+        assert_eq!(OctreeStats::default().height(), 0);
+    }
+
+    #[test]
+    fn vec_storage_slots() {
+        let mut tree = Octree::<u64, u32, VecStorage>::new();
+        let root = *tree.split(0).unwrap().0;
+        tree.set_leaf(root[0], 1);
+        tree.set_leaf(root[1], 2);
+
+        // entries are stored as `Option<u64>`, which doesn't fit in 8 bytes
+        let stats = tree.stats();
+        assert_eq!(stats.leaf_data.len, 2);
+        assert_eq!(
+            stats.leaf_data.bytes_used,
+            2 * std::mem::size_of::<Option<u64>>()
+        );
+        assert_eq!(
+            stats.leaf_data.bytes_reserved,
+            stats.leaf_data.capacity * std::mem::size_of::<Option<u64>>()
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    collections::HashMap,
+    ops::{Index, IndexMut},
+};
+
+use stablevec::StableVec;
+
+/// A store of values addressed by index, such as each of the stores backing an [Octree](crate::Octree);
+/// see [`OctreeStorage`].
+///
+/// The index of a value must stay the same until that value is removed, except through
+/// [`Self::defragment`], [`Self::compress`], or [`Self::extend_from_other`] (for the values of
+/// `other`), each of which reports the values it moved.
+pub trait Arena<V>: Default + From<Vec<V>> + Index<usize, Output = V> + IndexMut<usize> {
+    /// Store a value, and return its index.
+    fn push(&mut self, value: V) -> usize;
+
+    /// Store each value of `iter`, and return their indices.
+    #[inline]
+    fn extend_from_iter(&mut self, iter: impl IntoIterator<Item = V>) -> Vec<usize> {
+        iter.into_iter().map(|v| self.push(v)).collect()
+    }
+
+    /// Remove & return the value at `index`, if extant.
+    fn remove(&mut self, index: usize) -> Option<V>;
+
+    /// Get the value at `index`, if extant.
+    fn get(&self, index: usize) -> Option<&V>;
+
+    /// Get the value at `index` mutably, if extant.
+    fn get_mut(&mut self, index: usize) -> Option<&mut V>;
+
+    /// Get the value at `index` mutably, if extant, without borrowing any other value; so, unlike
+    /// through [`Self::get_mut`], references to distinct values can be held at once.
+    ///
+    /// # Safety
+    ///
+    /// * `this` must be valid for `'a`, during which the arena must only be accessed through this
+    ///   function
+    /// * no `index` may be given more than once during `'a`
+    #[allow(unsafe_code)]
+    unsafe fn get_disjoint_mut<'a>(this: *mut Self, index: usize) -> Option<&'a mut V>;
+
+    /// Whether a value is stored at `index`.
+    #[inline]
+    fn is_init(&self, index: usize) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// The number of values stored.
+    fn len_init(&self) -> usize;
+
+    /// Whether the arena's own bookkeeping agrees with itself, such that it can be safely
+    /// [enumerated](Self::enumerate); only arenas built from untrusted parts should ever fail
+    /// this.
+    #[inline]
+    fn is_consistent(&self) -> bool {
+        true
+    }
+
+    /// The number of values which can be stored without reallocating; greater than the index of
+    /// every stored value.
+    fn capacity(&self) -> usize;
+
+    /// The number of bytes each value occupies while stored, which may exceed `size_of::<V>()` if
+    /// the arena wraps its values.
+    #[inline]
+    fn slot_size(&self) -> usize {
+        std::mem::size_of::<V>()
+    }
+
+    /// Ensure that at least `additional` more values can be pushed without reallocating.
+    fn reserve(&mut self, additional: usize);
+
+    /// The index which the `n`th next value pushed, counting from 0, will be stored at, given that
+    /// room for it has already been [reserved](Self::reserve).
+    fn next_index(&self, n: usize) -> usize;
+
+    /// Iterate through all stored values, with their indices, in index order.
+    fn enumerate<'a>(&'a self) -> impl Iterator<Item = (usize, &'a V)> + 'a
+    where
+        V: 'a;
+
+    /// Iterate mutably through all stored values, with their indices, in index order.
+    fn enumerate_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut V)> + 'a
+    where
+        V: 'a;
+
+    /// Iterate through all stored values, in index order.
+    #[inline]
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a V> + 'a
+    where
+        V: 'a,
+    {
+        self.enumerate().map(|(_, v)| v)
+    }
+
+    /// Iterate mutably through all stored values, in index order.
+    #[inline]
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut V> + 'a
+    where
+        V: 'a,
+    {
+        self.enumerate_mut().map(|(_, v)| v)
+    }
+
+    /// Move the stored values such that they occupy the indices `0..self.len_init()`, and return
+    /// the moved values' indices in the form `from -> to`.
+    fn defragment(&mut self) -> HashMap<usize, usize>;
+
+    /// [Defragment](Self::defragment) `self` & release any unused capacity.
+    fn compress(&mut self) -> HashMap<usize, usize>;
+
+    /// Move every value of `other` into `self`, and return their indices in the form
+    /// `from -> to`.
+    fn extend_from_other(&mut self, other: Self) -> HashMap<usize, usize>;
+}
+
+/// A family of [Arenas](Arena) in which an [Octree](crate::Octree) stores its nodes, branches &
+/// leaf data.
+pub trait OctreeStorage {
+    /// The arena storing values of type `V`.
+    type Arena<V>: Arena<V>;
+}
+
+/// Backs an [Octree](crate::Octree) with [`StableVec`]s, which reuse the indices of removed values.
+///
+/// This is the default storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StableStorage;
+
+impl OctreeStorage for StableStorage {
+    type Arena<V> = StableVec<V>;
+}
+
+/// Backs an [Octree](crate::Octree) with [`VecArena`]s, which never reuse the indices of removed
+/// values; suited to trees which are built once and rarely edited afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VecStorage;
+
+impl OctreeStorage for VecStorage {
+    type Arena<V> = VecArena<V>;
+}
+
+impl<V> Arena<V> for StableVec<V> {
+    #[inline]
+    fn push(&mut self, value: V) -> usize {
+        self.push(value)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> Option<V> {
+        self.remove(index)
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&V> {
+        self.get(index)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, index: usize) -> Option<&mut V> {
+        self.get_mut(index)
+    }
+
+    #[inline]
+    #[allow(unsafe_code)]
+    unsafe fn get_disjoint_mut<'a>(this: *mut Self, index: usize) -> Option<&'a mut V> {
+        // SAFETY: upheld by the caller
+        unsafe { StableVec::get_disjoint_mut(this, index) }
+    }
+
+    #[inline]
+    fn is_init(&self, index: usize) -> bool {
+        self.is_init(index)
+    }
+
+    #[inline]
+    fn len_init(&self) -> usize {
+        self.len_init()
+    }
+
+    fn is_consistent(&self) -> bool {
+        let flags = self.init_flags();
+        flags.len() == self.capacity() && flags.count_ones() == self.len_init()
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.capacity()
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.reserve(additional);
+    }
+
+    #[inline]
+    fn next_index(&self, n: usize) -> usize {
+        // pushed values fill the lowest empty slots first
+        self.init_flags()
+            .iter_zeros()
+            .nth(n)
+            .expect("room should be reserved before querying the next index")
+    }
+
+    #[inline]
+    fn enumerate<'a>(&'a self) -> impl Iterator<Item = (usize, &'a V)> + 'a
+    where
+        V: 'a,
+    {
+        self.enumerate()
+    }
+
+    #[inline]
+    fn enumerate_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut V)> + 'a
+    where
+        V: 'a,
+    {
+        self.enumerate_mut()
+    }
+
+    #[inline]
+    fn defragment(&mut self) -> HashMap<usize, usize> {
+        self.defragment()
+    }
+
+    #[inline]
+    fn compress(&mut self) -> HashMap<usize, usize> {
+        self.compress()
+    }
+
+    #[inline]
+    fn extend_from_other(&mut self, other: Self) -> HashMap<usize, usize> {
+        self.extend_from_other(other)
+    }
+}
+
+/// An [Arena] over a [Vec], to which values are only ever appended; removed values leave vacant
+/// entries behind until the next [defragmentation](Arena::defragment).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VecArena<V> {
+    data: Vec<Option<V>>,
+    /// Number of occupied entries in `data`
+    count: usize,
+}
+
+impl<V> Default for VecArena<V> {
+    fn default() -> Self {
+        Self {
+            data: Vec::new(),
+            count: 0,
+        }
+    }
+}
+
+impl<V> From<Vec<V>> for VecArena<V> {
+    fn from(v: Vec<V>) -> Self {
+        Self {
+            count: v.len(),
+            data: v.into_iter().map(Some).collect(),
+        }
+    }
+}
+
+impl<V> Index<usize> for VecArena<V> {
+    type Output = V;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.data[index].as_ref().expect("index should be occupied")
+    }
+}
+
+impl<V> IndexMut<usize> for VecArena<V> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.data[index].as_mut().expect("index should be occupied")
+    }
+}
+
+impl<V> Arena<V> for VecArena<V> {
+    #[inline]
+    fn push(&mut self, value: V) -> usize {
+        self.data.push(Some(value));
+        self.count += 1;
+        self.data.len() - 1
+    }
+
+    fn remove(&mut self, index: usize) -> Option<V> {
+        let res = self.data.get_mut(index)?.take();
+        if res.is_some() {
+            self.count -= 1;
+        }
+        res
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&V> {
+        self.data.get(index)?.as_ref()
+    }
+
+    #[inline]
+    fn get_mut(&mut self, index: usize) -> Option<&mut V> {
+        self.data.get_mut(index)?.as_mut()
+    }
+
+    #[inline]
+    #[allow(unsafe_code)]
+    unsafe fn get_disjoint_mut<'a>(this: *mut Self, index: usize) -> Option<&'a mut V> {
+        // SAFETY: `this` is valid per the caller, and `Vec::as_mut_ptr` never borrows the vector's
+        // values, so only the value at `index`, which is borrowed nowhere else, is borrowed here
+        unsafe {
+            let data = &mut (*this).data;
+            if index >= data.len() {
+                return None;
+            }
+            (*data.as_mut_ptr().add(index)).as_mut()
+        }
+    }
+
+    #[inline]
+    fn len_init(&self) -> usize {
+        self.count
+    }
+
+    fn is_consistent(&self) -> bool {
+        self.data.iter().filter(|v| v.is_some()).count() == self.count
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    #[inline]
+    fn slot_size(&self) -> usize {
+        std::mem::size_of::<Option<V>>()
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+    }
+
+    #[inline]
+    fn next_index(&self, n: usize) -> usize {
+        self.data.len() + n
+    }
+
+    #[inline]
+    fn enumerate<'a>(&'a self) -> impl Iterator<Item = (usize, &'a V)> + 'a
+    where
+        V: 'a,
+    {
+        self.data
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.as_ref().map(|v| (i, v)))
+    }
+
+    #[inline]
+    fn enumerate_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut V)> + 'a
+    where
+        V: 'a,
+    {
+        self.data
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, v)| v.as_mut().map(|v| (i, v)))
+    }
+
+    fn defragment(&mut self) -> HashMap<usize, usize> {
+        let mut res = HashMap::new();
+        let mut to = 0;
+        for from in 0..self.data.len() {
+            if self.data[from].is_some() {
+                if from != to {
+                    self.data.swap(from, to);
+                    res.insert(from, to);
+                }
+                to += 1;
+            }
+        }
+        self.data.truncate(to);
+        res
+    }
+
+    fn compress(&mut self) -> HashMap<usize, usize> {
+        let res = self.defragment();
+        self.data.shrink_to_fit();
+        res
+    }
+
+    fn extend_from_other(&mut self, other: Self) -> HashMap<usize, usize> {
+        self.reserve(other.count);
+        other
+            .data
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.map(|v| (i, self.push(v))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, NodePoint, Octree, OctreeSlice, VecStorage};
+
+    #[test]
+    fn vec_storage() {
+        let mut tree = Octree::<u8, u32, VecStorage>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[0]).unwrap().0;
+        for (i, c) in inner.into_iter().enumerate() {
+            tree.set_leaf(c, i as u8);
+        }
+        tree.set_leaf(root[7], 8);
+        assert_eq!(tree.set_leaf(inner[3], 9), vec![3]);
+        assert_eq!(tree.remove(inner[1]), vec![1]);
+        // removed entries aren't reused
+        tree.set_leaf(inner[1], 10);
+        assert_eq!(tree.leaf_data().len_init(), 9);
+        assert!(tree.leaf_data().capacity() > 9);
+
+        for (leaf, _) in tree.leaf_dfi_mut() {
+            *leaf += 1;
+        }
+        tree.compress();
+        assert_eq!(tree.proxies().len_init(), 17);
+        assert_eq!(tree.leaf_data().capacity(), 9);
+        assert!(tree
+            .leaf_dfi()
+            .map(|(l, _)| *l)
+            .eq([1, 11, 3, 10, 5, 6, 7, 8, 9]));
+        assert_eq!(
+            tree.leaf_dfi().last().map(|(_, np)| np),
+            Some(NodePoint::new(1, 1, 1, 1))
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::mem;
+
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{Arena, InvariantError, Octree, OctreeStorage, ProxyData};
+
+/// Check that the occupancy reported by `arena` agrees with the values it actually holds.
+fn validate_arena<V, Idx: ArrayIndex>(
+    arena: &impl Arena<V>,
+    name: &'static str,
+) -> Result<(), InvariantError<Idx>> {
+    // an inconsistent arena may not even be safe to enumerate
+    if !arena.is_consistent() {
+        return Err(InvariantError::InconsistentArena(name));
+    }
+    let mut count = 0;
+    for (i, v) in arena.enumerate() {
+        let consistent = i < arena.capacity()
+            && arena.is_init(i)
+            && arena.get(i).is_some_and(|got| std::ptr::eq(got, v));
+        if !consistent {
+            return Err(InvariantError::InconsistentArena(name));
+        }
+        count += 1;
+    }
+    match count == arena.len_init() {
+        true => Ok(()),
+        false => Err(InvariantError::InconsistentArena(name)),
+    }
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Check every structural invariant of this tree: that each arena's occupancy agrees with its
+    /// contents, that every node is reachable from the root by exactly one path, that every
+    /// branch has 8 occupied children whose parent links point back to it, and that every proxy
+    /// refers to occupied data which no other proxy refers to.
+    ///
+    /// Trees built through the public API always pass this; it's meant for catching bugs in code
+    /// which manipulates trees, and for vetting trees loaded from untrusted data. Like
+    /// [`Self::stats`], this visits every node.
+    ///
+    /// # Errors
+    ///
+    /// * The first [`InvariantError`] found.
+    pub fn validate(&self) -> Result<(), InvariantError<Idx>> {
+        validate_arena(&self.proxies, "node")?;
+        validate_arena(&self.branch_data, "branch data")?;
+        validate_arena(&self.leaf_data, "leaf data")?;
+
+        let root = self.root;
+        if !self.proxies.is_init(root.as_()) {
+            return Err(InvariantError::InvalidRoot(root));
+        }
+        let parent = self.proxies[root.as_()].parent;
+        if parent != root {
+            return Err(InvariantError::RootHasParent { root, parent });
+        }
+
+        let mut seen_nodes = vec![false; self.proxies.capacity()];
+        let mut seen_branches = vec![false; self.branch_data.capacity()];
+        let mut seen_leafs = vec![false; self.leaf_data.capacity()];
+        seen_nodes[AsPrimitive::<usize>::as_(root)] = true;
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            match self.proxies[node.as_()].data {
+                ProxyData::Void => {}
+                ProxyData::Leaf(leaf) => {
+                    if !self.leaf_data.is_init(leaf.as_()) {
+                        return Err(InvariantError::InvalidLeafData { node, leaf });
+                    }
+                    if mem::replace(&mut seen_leafs[AsPrimitive::<usize>::as_(leaf)], true) {
+                        return Err(InvariantError::SharedLeafData(leaf));
+                    }
+                }
+                ProxyData::Branch(branch) => {
+                    if !self.branch_data.is_init(branch.as_()) {
+                        return Err(InvariantError::InvalidBranchData { node, branch });
+                    }
+                    if mem::replace(&mut seen_branches[AsPrimitive::<usize>::as_(branch)], true) {
+                        return Err(InvariantError::SharedBranchData(branch));
+                    }
+                    for &child in &self.branch_data[branch.as_()] {
+                        if !self.proxies.is_init(child.as_()) {
+                            return Err(InvariantError::MissingChild { node, child });
+                        }
+                        let found = self.proxies[child.as_()].parent;
+                        if found != node {
+                            return Err(InvariantError::ParentMismatch {
+                                node: child,
+                                expected: node,
+                                found,
+                            });
+                        }
+                        if mem::replace(&mut seen_nodes[AsPrimitive::<usize>::as_(child)], true) {
+                            return Err(InvariantError::SharedNode(child));
+                        }
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+
+        let count = |seen: &[bool]| seen.iter().filter(|s| **s).count();
+        match self.proxies.len_init() - count(&seen_nodes) {
+            0 => {}
+            n => return Err(InvariantError::OrphanedNodes(n)),
+        }
+        match self.branch_data.len_init() - count(&seen_branches) {
+            0 => {}
+            n => return Err(InvariantError::OrphanedBranchData(n)),
+        }
+        match self.leaf_data.len_init() - count(&seen_leafs) {
+            0 => Ok(()),
+            n => Err(InvariantError::OrphanedLeafData(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+
+    use stablevec::{bitvec::vec::BitVec, StableVec};
+
+    use crate::{InvariantError, Octree, ProxyData};
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn validate() {
+        let mut tree = Octree::<u8, u32>::new();
+        let root = *tree.split(0).unwrap().0;
+        let inner = *tree.split(root[3]).unwrap().0;
+        tree.set_leaf(inner[5], 1);
+        tree.set_leaf(root[6], 2);
+        assert_eq!(tree.validate(), Ok(()));
+
+        let mut bad = tree.clone();
+        bad.proxies[inner[2] as usize].parent = root[0];
+        assert_eq!(
+            bad.validate(),
+            Err(InvariantError::ParentMismatch {
+                node: inner[2],
+                expected: root[3],
+                found: root[0],
+            })
+        );
+
+        let mut bad = tree.clone();
+        bad.proxies.remove(inner[7] as usize);
+        assert_eq!(
+            bad.validate(),
+            Err(InvariantError::MissingChild {
+                node: root[3],
+                child: inner[7],
+            })
+        );
+
+        let mut bad = tree.clone();
+        bad.proxies[inner[0] as usize].data = ProxyData::Leaf(0);
+        assert_eq!(bad.validate(), Err(InvariantError::SharedLeafData(0)));
+
+        let mut bad = tree.clone();
+        bad.leaf_data.push(3);
+        assert_eq!(bad.validate(), Err(InvariantError::OrphanedLeafData(1)));
+
+        // an arena whose count disagrees with its flags
+        let mut bad = tree.clone();
+        // SAFETY: both entries are initialized & flagged; only the count is wrong
+        bad.leaf_data = unsafe {
+            StableVec::from_raw_parts(
+                vec![MaybeUninit::new(1), MaybeUninit::new(2)].into_boxed_slice(),
+                BitVec::repeat(true, 2),
+                3,
+            )
+        };
+        assert_eq!(
+            bad.validate(),
+            Err(InvariantError::InconsistentArena("leaf data"))
+        );
+
+        // an arena with more flags than entries, the last flagged as initialized
+        let mut bad = std::mem::ManuallyDrop::new(tree);
+        // SAFETY: the out-of-bounds flag is never read, since the tree is never dropped
+        bad.leaf_data = unsafe {
+            StableVec::from_raw_parts(
+                vec![MaybeUninit::new(1), MaybeUninit::new(2)].into_boxed_slice(),
+                BitVec::repeat(true, 3),
+                3,
+            )
+        };
+        assert_eq!(
+            bad.validate(),
+            Err(InvariantError::InconsistentArena("leaf data"))
+        );
+    }
+}
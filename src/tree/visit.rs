@@ -0,0 +1,194 @@
+use eightfold_common::ArrayIndex;
+use num_traits::AsPrimitive;
+
+use crate::{NodePoint, Octant, Octree, OctreeStorage, Proxy, ProxyData};
+
+/// How to continue a traversal after entering a branch; see [`OctreeVisitor::enter_branch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisitControl {
+    /// Visit the children of the branch.
+    #[default]
+    Descend,
+    /// Move on to the next sibling of the branch, without visiting its children.
+    Skip,
+    /// End the traversal.
+    Stop,
+}
+
+/// Callbacks for a depth-first traversal of an [Octree], by [Octant] ordering; see
+/// [`Octree::visit`].
+///
+/// Each callback receives the index, [`NodePoint`] & [Proxy] of a node. Every callback does
+/// nothing by default, and branches are always descended into.
+pub trait OctreeVisitor<T, Idx: ArrayIndex> {
+    /// Called on reaching a branch, before any of its children.
+    #[inline]
+    fn enter_branch(
+        &mut self,
+        _index: Idx,
+        _point: NodePoint<Idx>,
+        _proxy: Proxy<Idx>,
+    ) -> VisitControl {
+        VisitControl::Descend
+    }
+
+    /// Called after visiting every child of a branch, if [`Self::enter_branch`] returned
+    /// [`Descend`](VisitControl::Descend).
+    #[inline]
+    fn exit_branch(&mut self, _index: Idx, _point: NodePoint<Idx>, _proxy: Proxy<Idx>) {}
+
+    /// Called on reaching a leaf, with its data.
+    #[inline]
+    fn visit_leaf(&mut self, _index: Idx, _point: NodePoint<Idx>, _proxy: Proxy<Idx>, _data: &T) {}
+
+    /// Called on reaching a void.
+    #[inline]
+    fn visit_void(&mut self, _index: Idx, _point: NodePoint<Idx>, _proxy: Proxy<Idx>) {}
+}
+
+/// A pending step of a traversal.
+enum Step<Idx: ArrayIndex> {
+    Enter(Idx, NodePoint<Idx>),
+    Exit(Idx, NodePoint<Idx>, Proxy<Idx>),
+}
+
+impl<T, Idx: ArrayIndex, S: OctreeStorage> Octree<T, Idx, S> {
+    /// Traverse the subtree rooted at `index`, which is given the [`NodePoint`] `point`, and
+    /// return whether the traversal ran to completion.
+    pub(crate) fn visit_from<V: OctreeVisitor<T, Idx> + ?Sized>(
+        &self,
+        index: Idx,
+        point: NodePoint<Idx>,
+        visitor: &mut V,
+    ) -> bool
+    where
+        u8: AsPrimitive<Idx>,
+    {
+        let mut stack = vec![Step::Enter(index, point)];
+        while let Some(step) = stack.pop() {
+            let (index, point) = match step {
+                Step::Enter(index, point) => (index, point),
+                Step::Exit(index, point, proxy) => {
+                    visitor.exit_branch(index, point, proxy);
+                    continue;
+                }
+            };
+            let proxy = self.proxies[index.as_()];
+            match proxy.data {
+                ProxyData::Void => visitor.visit_void(index, point, proxy),
+                ProxyData::Leaf(l_idx) => {
+                    visitor.visit_leaf(index, point, proxy, &self.leaf_data[l_idx.as_()]);
+                }
+                ProxyData::Branch(b_idx) => match visitor.enter_branch(index, point, proxy) {
+                    VisitControl::Descend => {
+                        stack.push(Step::Exit(index, point, proxy));
+                        let children = &self.branch_data[b_idx.as_()];
+                        stack.extend(
+                            Octant::ALL
+                                .into_iter()
+                                .rev()
+                                .map(|oct| Step::Enter(children[usize::from(oct)], point + oct)),
+                        );
+                    }
+                    VisitControl::Skip => {}
+                    VisitControl::Stop => return false,
+                },
+            }
+        }
+        true
+    }
+
+    /// Traverse every node depth-first, by [Octant] ordering, calling the matching callback of
+    /// `visitor` for each, and return whether the traversal ran to completion.
+    ///
+    /// The traversal ends early if [`OctreeVisitor::enter_branch`] returns
+    /// [`Stop`](VisitControl::Stop), and skips the children of a branch if it returns
+    /// [`Skip`](VisitControl::Skip).
+    #[inline]
+    pub fn visit<V: OctreeVisitor<T, Idx> + ?Sized>(&self, visitor: &mut V) -> bool
+    where
+        u8: AsPrimitive<Idx>,
+    {
+        self.visit_from(
+            self.root,
+            NodePoint::new(Idx::ZERO, Idx::ZERO, Idx::ZERO, Idx::ZERO),
+            visitor,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NodePoint, Octree, OctreeSlice, Proxy};
+
+    use super::{OctreeVisitor, VisitControl};
+
+    #[derive(Default)]
+    struct Record {
+        events: Vec<(&'static str, NodePoint<u32>)>,
+        skip: Option<NodePoint<u32>>,
+        stop: Option<NodePoint<u32>>,
+    }
+
+    impl OctreeVisitor<u8, u32> for Record {
+        fn enter_branch(&mut self, _: u32, point: NodePoint<u32>, _: Proxy<u32>) -> VisitControl {
+            self.events.push(("enter", point));
+            if self.skip == Some(point) {
+                VisitControl::Skip
+            } else if self.stop == Some(point) {
+                VisitControl::Stop
+            } else {
+                VisitControl::Descend
+            }
+        }
+
+        fn exit_branch(&mut self, _: u32, point: NodePoint<u32>, _: Proxy<u32>) {
+            self.events.push(("exit", point));
+        }
+
+        fn visit_leaf(&mut self, _: u32, point: NodePoint<u32>, _: Proxy<u32>, _: &u8) {
+            self.events.push(("leaf", point));
+        }
+    }
+
+    /// Leafs at `root[0]` & `root[6][1]`.
+    fn tree() -> Octree<u8, u32> {
+        let mut tree = Octree::new();
+        let root = *tree.split(0).unwrap().0;
+        tree.set_leaf(root[0], 1);
+        let inner = *tree.split(root[6]).unwrap().0;
+        tree.set_leaf(inner[1], 2);
+        tree
+    }
+
+    #[test]
+    fn visit() {
+        let tree = tree();
+        let mut rec = Record::default();
+        assert!(tree.visit(&mut rec));
+        let leafs = rec
+            .events
+            .iter()
+            .filter(|(e, _)| *e == "leaf")
+            .map(|(_, np)| *np);
+        assert!(leafs.eq(tree.leaf_dfi().map(|(_, np)| np)));
+        assert_eq!(
+            rec.events.iter().map(|(e, _)| *e).collect::<Vec<_>>(),
+            ["enter", "leaf", "enter", "leaf", "exit", "exit"]
+        );
+
+        let mut rec = Record {
+            skip: Some(NodePoint::new(1, 1, 0, 1)),
+            ..Default::default()
+        };
+        assert!(tree.visit(&mut rec));
+        assert_eq!(rec.events.len(), 4);
+
+        let mut rec = Record {
+            stop: Some(NodePoint::new(1, 1, 0, 1)),
+            ..Default::default()
+        };
+        assert!(!tree.visit(&mut rec));
+        assert_eq!(rec.events.len(), 3);
+    }
+}
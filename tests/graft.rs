@@ -0,0 +1,29 @@
+use eightfold::{Error, NodePoint, Octree, OctreeSlice};
+
+/// Ensure that grafting relinks every node of the grafted tree beneath the target
+#[test]
+fn graft() {
+    let mut tree = Octree::<u8, u32>::new();
+    let root = *tree.split(0).unwrap().0;
+    tree.set_leaf(root[0], 1);
+
+    let mut other = Octree::<u8, u32>::new();
+    let o_root = *other.split(0).unwrap().0;
+    let inner = *other.split(o_root[1]).unwrap().0;
+    other.set_leaf(inner[2], 2);
+    other.set_leaf(o_root[7], 3);
+
+    assert!(matches!(
+        tree.graft(Octree::new(), root[0]),
+        Err(Error::NotAVoid(_))
+    ));
+    tree.graft(other, root[5]).unwrap();
+    assert_eq!(
+        tree.leaf_dfi().map(|(l, np)| (*l, np)).collect::<Vec<_>>(),
+        [
+            (1, NodePoint::new(0, 0, 0, 1)),
+            (2, NodePoint::new(4, 1, 6, 3)),
+            (3, NodePoint::new(3, 1, 3, 2)),
+        ]
+    );
+}
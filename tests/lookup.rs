@@ -0,0 +1,45 @@
+use eightfold::{NodePoint, Octant, Octree};
+use nalgebra::point;
+
+/// Ensure that node points, node lookup & voxel lookup agree with each other below the first level
+#[test]
+fn node_point_round_trip() {
+    let mut tree = Octree::<(), u32>::new();
+    let root = *tree.split(0).unwrap().0;
+    let inner = *tree.split(root[usize::from(Octant(6))]).unwrap().0;
+    let target = inner[usize::from(Octant(3))];
+
+    let p = NodePoint::new(2, 3, 1, 2);
+    assert_eq!(tree.node_point_of_unchecked(target), p);
+    assert_eq!(tree.node_at(&p), target);
+    assert_eq!(tree.voxel_at(&point![2, 3, 1]).unwrap(), target);
+    // shallower points stop at the ancestor at their depth
+    assert_eq!(tree.node_at(&NodePoint::new(1, 1, 0, 1)), root[6]);
+    // voxels outside of the split octant resolve to its void siblings
+    assert_eq!(tree.voxel_at(&point![0, 3, 1]).unwrap(), root[2]);
+}
+
+/// Ensure that growing links the new root & its new children to each other
+#[test]
+fn grow() {
+    let mut tree = Octree::<(), u32>::new();
+    let old_root = tree.root_idx();
+    let inner = *tree.split(old_root).unwrap().0;
+    let root = tree.grow(Octant(5));
+    assert_eq!(tree.root_idx(), root);
+    assert!(tree.node(root).unwrap().parent().is_none());
+    let b = tree.root_proxy().branch().unwrap();
+    for (i, &child) in tree.branch_data()[b as usize].iter().enumerate() {
+        let parent = tree.node(child).unwrap().parent().unwrap();
+        assert_eq!(parent.index(), root);
+        assert_eq!(tree.octant_of_unchecked(child), Some(Octant(i as u8)));
+    }
+    assert_eq!(
+        tree.node_point_of_unchecked(old_root),
+        NodePoint::new(1, 0, 1, 1)
+    );
+    assert_eq!(
+        tree.node_point_of_unchecked(inner[7]),
+        NodePoint::new(3, 1, 3, 2)
+    );
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "serde")]
+
+use eightfold::{stablevec::StableVec, Octant, Octree, OctreeSlice};
+use serde_json::{json, Value};
+
+fn tree() -> Octree<u8, u16> {
+    let mut tree = Octree::<u8, u16>::new();
+    let root = *tree.split(0).unwrap().0;
+    let inner = *tree.split(root[3]).unwrap().0;
+    tree.set_leaf(root[6], 6);
+    tree.set_leaf(inner[0], 30);
+    tree.set_leaf(inner[5], 35);
+    tree
+}
+
+fn leafs(tree: &Octree<u8, u16>) -> Vec<(u8, eightfold::NodePoint<u16>)> {
+    tree.leaf_dfi().map(|(l, np)| (*l, np)).collect()
+}
+
+#[test]
+fn octree_round_trip() {
+    let tree = tree();
+    let json = serde_json::to_string(&tree).unwrap();
+    let res: Octree<u8, u16> = serde_json::from_str(&json).unwrap();
+    assert_eq!(leafs(&res), leafs(&tree));
+}
+
+#[test]
+fn octree_rejects_corruption() {
+    let value = serde_json::to_value(tree()).unwrap();
+    let reject = |f: &dyn Fn(&mut Value)| {
+        let mut v = value.clone();
+        f(&mut v);
+        serde_json::from_value::<Octree<u8, u16>>(v).unwrap_err()
+    };
+    // broken parent link
+    reject(&|v| v["proxies"][1]["parent"] = json!(2));
+    // root with a parent
+    reject(&|v| v["proxies"][0]["parent"] = json!(1));
+    // out-of-range root
+    reject(&|v| v["root"] = json!(99));
+    // out-of-range child
+    reject(&|v| v["branch_data"][0][0] = json!(99));
+    // child listed twice
+    reject(&|v| v["branch_data"][0][1] = v["branch_data"][0][0].clone());
+    // out-of-range leaf
+    reject(&|v| {
+        let leaf = v["proxies"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|p| p["data"].get("Leaf").is_some())
+            .unwrap();
+        leaf["data"]["Leaf"] = json!(99);
+    });
+    // unused leaf data
+    reject(&|v| v["leaf_data"].as_array_mut().unwrap().push(json!(7)));
+}
+
+#[test]
+fn stablevec_keeps_indices() {
+    let mut vec = StableVec::from(vec![1u8, 2, 3]);
+    vec.remove(1);
+    let json = serde_json::to_value(&vec).unwrap();
+    assert_eq!(json, json!([1, null, 3]));
+    let res: StableVec<u8> = serde_json::from_value(json).unwrap();
+    assert!(!res.is_init(1));
+    assert_eq!(res.enumerate().collect::<Vec<_>>(), [(0, &1), (2, &3)]);
+}
+
+#[test]
+fn octant_range() {
+    assert_eq!(serde_json::to_value(Octant(5)).unwrap(), json!(5));
+    assert_eq!(
+        serde_json::from_value::<Octant>(json!(7)).unwrap(),
+        Octant(7)
+    );
+    assert!(serde_json::from_value::<Octant>(json!(8)).is_err());
+}
+
+#[cfg(feature = "spatial")]
+#[test]
+fn voxel_octree_rejects_mismatched_bounds() {
+    use eightfold::spatial::VoxelOctree;
+    use nalgebra::{point, vector};
+
+    let mut tree = VoxelOctree::<u8, f32, u16>::new(vector![1.0, 2.0, 0.5]);
+    tree.grow_to_contain(&point![3.5, 0.5, 0.25]);
+    let value = serde_json::to_value(&tree).unwrap();
+    let res: VoxelOctree<u8, f32, u16> = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(res.aabb(), tree.aabb());
+
+    let reject = |f: &dyn Fn(&mut Value)| {
+        let mut v = value.clone();
+        f(&mut v);
+        serde_json::from_value::<VoxelOctree<u8, f32, u16>>(v).unwrap_err()
+    };
+    // bounds too large for the grid
+    reject(&|v| v["aabb"]["maxs"][0] = json!(100.0));
+    // voxels too small for the bounds
+    reject(&|v| v["voxel_size"][1] = json!(1.0));
+    // grid too tall for the bounds
+    reject(&|v| v["height"] = json!(5));
+}